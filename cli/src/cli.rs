@@ -5,7 +5,8 @@ use git_version::git_version;
 
 use crate::{
     completions::GenCompletionsCommand, eval::EvalCommand, export::ExportCommand,
-    pprint_ast::PprintAstCommand, query::QueryCommand, typecheck::TypecheckCommand,
+    pprint_ast::PprintAstCommand, query::QueryCommand, schema::SchemaCommand,
+    typecheck::TypecheckCommand,
 };
 
 #[cfg(feature = "repl")]
@@ -54,6 +55,23 @@ pub struct GlobalOptions {
     /// Input file, omit to read from stdin
     #[arg(long, short, global = true)]
     pub file: Option<PathBuf>,
+
+    /// Cache the parsed, typechecked and transformed program, its imports and the standard
+    /// library in this directory, so that subsequent runs on unchanged sources skip this work
+    #[arg(long, global = true, value_name = "DIR")]
+    pub cache_dir: Option<PathBuf>,
+
+    /// Abort the evaluation after this number of steps
+    #[arg(long, global = true, value_name = "STEPS")]
+    pub max_steps: Option<u64>,
+
+    /// Forbid imports
+    #[arg(long, global = true, conflicts_with = "import_root")]
+    pub no_imports: bool,
+
+    /// Only allow importing files under this directory. Can be repeated
+    #[arg(long, global = true, value_name = "DIR")]
+    pub import_root: Vec<PathBuf>,
 }
 
 /// Available subcommands.
@@ -78,6 +96,8 @@ pub enum Command {
     /// Generates the documentation files for the specified nickel file
     #[cfg(feature = "doc")]
     Doc(DocCommand),
+    /// Converts JSON Schemas to Nickel contracts and back
+    Schema(SchemaCommand),
     /// Format Nickel files
     #[cfg(feature = "format")]
     Format(FormatCommand),
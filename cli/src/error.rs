@@ -1,17 +1,21 @@
 use nickel_lang_core::{
     error::{Diagnostic, Files, IntoDiagnostics},
-    eval::cache::lazy::CBNCache,
+    eval::cache::CacheImpl,
     program::Program,
 };
 
 /// Errors related to mishandling the CLI.
 pub enum CliUsageError {
-    InvalidOverride { path: String },
+    InvalidOverride {
+        path: String,
+    },
+    /// Watch mode was requested on a program which isn't read from a file.
+    WatchWithoutFile,
 }
 
 pub enum Error {
     Program {
-        program: Program<CBNCache>,
+        program: Program<CacheImpl>,
         error: nickel_lang_core::error::Error,
     },
     Io {
@@ -25,10 +29,13 @@ pub enum Error {
     Format {
         error: crate::format::FormatError,
     },
+    JsonSchema {
+        error: nickel_lang_core::json_schema::JsonSchemaError,
+    },
     /// An invalid invocation of the CLI that couldn't be caught by the simple parsing provided by
     /// clap.
     CliUsage {
-        program: Program<CBNCache>,
+        program: Program<CacheImpl>,
         error: CliUsageError,
     },
 }
@@ -48,6 +55,15 @@ impl<FileId> IntoDiagnostics<FileId> for CliUsageError {
                         configuration and thus can't be the target of `--override`."
                     )])]
             }
+            CliUsageError::WatchWithoutFile => {
+                vec![Diagnostic::error()
+                    .with_message("watch mode requires an input file")
+                    .with_notes(vec![
+                        "The standard input can't be watched for changes. Use `--file` to \
+                        specify the file to watch."
+                            .into(),
+                    ])]
+            }
         }
     }
 }
@@ -97,6 +113,12 @@ impl From<crate::format::FormatError> for Error {
     }
 }
 
+impl From<nickel_lang_core::json_schema::JsonSchemaError> for Error {
+    fn from(error: nickel_lang_core::json_schema::JsonSchemaError) -> Self {
+        Error::JsonSchema { error }
+    }
+}
+
 #[cfg(feature = "repl")]
 impl From<nickel_lang_core::repl::InitError> for Error {
     fn from(error: nickel_lang_core::repl::InitError) -> Self {
@@ -105,11 +127,11 @@ impl From<nickel_lang_core::repl::InitError> for Error {
 }
 
 pub trait ResultErrorExt<T> {
-    fn report_with_program(self, program: Program<CBNCache>) -> CliResult<T>;
+    fn report_with_program(self, program: Program<CacheImpl>) -> CliResult<T>;
 }
 
 impl<T> ResultErrorExt<T> for Result<T, nickel_lang_core::error::Error> {
-    fn report_with_program(self, program: Program<CBNCache>) -> CliResult<T> {
+    fn report_with_program(self, program: Program<CacheImpl>) -> CliResult<T> {
         self.map_err(|error| Error::Program { program, error })
     }
 }
@@ -133,6 +155,7 @@ impl Error {
             }
             #[cfg(feature = "format")]
             Error::Format { error } => eprintln!("{error}"),
+            Error::JsonSchema { error } => eprintln!("{error}"),
             Error::CliUsage { error, mut program } => program.report(error),
         }
     }
@@ -1,4 +1,8 @@
-use nickel_lang_core::{eval::cache::lazy::CBNCache, program::Program};
+use nickel_lang_core::{
+    cache::ImportPolicy,
+    eval::{cache::CacheImpl, limits::Limits},
+    program::Program,
+};
 
 use crate::{
     cli::GlobalOptions,
@@ -17,12 +21,12 @@ impl EvalCommand {
             .report_with_program(program)
     }
 
-    pub fn prepare(&self, global: &GlobalOptions) -> CliResult<Program<CBNCache>> {
+    pub fn prepare(&self, global: &GlobalOptions) -> CliResult<Program<CacheImpl>> {
         prepare(global)
     }
 }
 
-pub fn prepare(global: &GlobalOptions) -> CliResult<Program<CBNCache>> {
+pub fn prepare(global: &GlobalOptions) -> CliResult<Program<CacheImpl>> {
     let mut program = global
         .file
         .clone()
@@ -34,6 +38,21 @@ pub fn prepare(global: &GlobalOptions) -> CliResult<Program<CBNCache>> {
         program.set_skip_stdlib();
     }
 
+    if let Some(cache_dir) = &global.cache_dir {
+        program.enable_persistent_cache(cache_dir);
+    }
+
+    program.set_limits(Limits {
+        max_steps: global.max_steps,
+        ..Default::default()
+    });
+
+    if global.no_imports {
+        program.set_import_policy(ImportPolicy::Deny);
+    } else if !global.import_root.is_empty() {
+        program.set_import_policy(ImportPolicy::Roots(global.import_root.clone()));
+    }
+
     program.color_opt = global.color.into();
 
     Ok(program)
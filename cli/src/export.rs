@@ -2,6 +2,7 @@ use std::{
     collections::{BTreeMap, HashMap},
     ffi::OsString,
     io::Write,
+    num::NonZeroUsize,
     {fs, path::PathBuf},
 };
 
@@ -10,7 +11,7 @@ use clap::{parser::ValueSource, ArgAction, Command};
 use nickel_lang_core::{
     combine::Combine,
     error::{Error, IOError},
-    eval::cache::lazy::CBNCache,
+    eval::cache::{CacheImpl, SelectedCache},
     identifier::LocIdent,
     program::{FieldOverride, Program},
     repl::query_print::{write_query_result, Attributes},
@@ -22,11 +23,7 @@ use nickel_lang_core::{
     typ::{RecordRowF, RecordRowsIteratorItem, Type, TypeF},
 };
 
-use crate::{
-    cli::GlobalOptions,
-    error::{CliResult, ResultErrorExt},
-    eval::EvalCommand,
-};
+use crate::{cli::GlobalOptions, error::CliResult, eval::EvalCommand, watch::WatchOptions};
 
 /// The maximal number of overridable fields displayed. Because there might be a lot of them, we
 /// don't list them all by default.
@@ -48,9 +45,18 @@ pub struct ExportCommand {
     #[arg(short, long)]
     pub output: Option<PathBuf>,
 
+    /// Evaluate the top-level fields of the configuration on up to this number of threads. The
+    /// configuration is processed once and then restored by each thread, which only pays off for
+    /// expensive configurations
+    #[arg(short, long, value_name = "THREADS")]
+    pub jobs: Option<NonZeroUsize>,
+
     #[command(flatten)]
     pub evaluation: EvalCommand,
 
+    #[command(flatten)]
+    pub watch: WatchOptions,
+
     /// \[WARNING\] Customize mode is experimental. Its interface is subject to breaking changes.
     ///
     /// Customize mode turns the nickel invocation into a new CLI based on the configuration to be
@@ -599,8 +605,25 @@ impl ExportCommand {
                 .collect::<Vec<_>>()
         };
 
-        self.export(&mut program, overrides)
-            .report_with_program(program)
+        // In watch mode, the overrides are set once and for all, and the program is evaluated
+        // with the incremental cache, which keeps the values that don't depend on the files that
+        // changed between two runs.
+        let overrides = if self.watch.watch && self.jobs.is_none() {
+            program.set_eval_cache(SelectedCache::with_incremental(true));
+
+            for ovd in overrides {
+                if let Err(error) = program.set_override(ovd) {
+                    return CliResult::Err(crate::error::Error::Program { error, program });
+                }
+            }
+
+            Vec::new()
+        } else {
+            overrides
+        };
+
+        self.watch
+            .run(program, |program| self.export(program, overrides.clone()))
     }
 
     // XXX: do we want this attached specifically to the `export` command?
@@ -608,11 +631,15 @@ impl ExportCommand {
     //      expression that has unset values, telling them they can set them using
     //      this method
     fn export(
-        self,
-        program: &mut Program<CBNCache>,
+        &self,
+        program: &mut Program<CacheImpl>,
         overrides: Vec<FieldOverride>,
     ) -> Result<(), Error> {
-        let rt = program.eval_full_for_export(overrides)?;
+        let rt = match self.jobs {
+            Some(threads) => program.eval_full_for_export_parallel(overrides, threads)?,
+            None if self.watch.watch => program.eval_incremental()?,
+            None => program.eval_full_for_export(overrides)?,
+        };
 
         // We only add a trailing newline for JSON exports. Both YAML and TOML
         // exporters already append a trailing newline by default.
@@ -620,7 +647,7 @@ impl ExportCommand {
 
         serialize::validate(self.format, &rt)?;
 
-        if let Some(file) = self.output {
+        if let Some(file) = &self.output {
             let mut file = fs::File::create(file).map_err(IOError::from)?;
             serialize::to_writer(&mut file, self.format, &rt)?;
 
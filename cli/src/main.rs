@@ -14,7 +14,9 @@ mod eval;
 mod export;
 mod pprint_ast;
 mod query;
+mod schema;
 mod typecheck;
+mod watch;
 
 use std::process::ExitCode;
 
@@ -31,6 +33,7 @@ fn main() -> ExitCode {
         Command::Export(export) => export.run(opts.global),
         Command::Query(query) => query.run(opts.global),
         Command::Typecheck(typecheck) => typecheck.run(opts.global),
+        Command::Schema(schema) => schema.run(opts.global),
         Command::GenCompletions(completions) => completions.run(opts.global),
 
         #[cfg(feature = "repl")]
@@ -2,8 +2,9 @@ use nickel_lang_core::repl::query_print;
 
 use crate::{
     cli::GlobalOptions,
-    error::{CliResult, ResultErrorExt, Warning},
+    error::{CliResult, Warning},
     eval::EvalCommand,
+    watch::WatchOptions,
 };
 
 #[derive(clap::Parser, Debug)]
@@ -27,6 +28,9 @@ pub struct QueryCommand {
 
     #[command(flatten)]
     pub evaluation: EvalCommand,
+
+    #[command(flatten)]
+    pub watch: WatchOptions,
 }
 
 impl QueryCommand {
@@ -49,29 +53,28 @@ impl QueryCommand {
         }
     }
 
-    pub fn run(mut self, global: GlobalOptions) -> CliResult<()> {
+    pub fn run(self, global: GlobalOptions) -> CliResult<()> {
         let mut program = self.evaluation.prepare(&global)?;
 
         if self.path.is_none() {
             program.report(Warning::EmptyQueryPath)
         }
 
-        let found = program
-            .query(std::mem::take(&mut self.path))
-            .map(|field| {
+        self.watch.run(program, |program| {
+            let found = program.query(self.path.clone()).map(|field| {
                 query_print::write_query_result(
                     &mut std::io::stdout(),
                     &field,
                     self.query_attributes(),
                 )
                 .unwrap()
-            })
-            .report_with_program(program)?;
+            })?;
 
-        if !found {
-            eprintln!("No metadata found for this field.")
-        }
+            if !found {
+                eprintln!("No metadata found for this field.")
+            }
 
-        Ok(())
+            Ok(())
+        })
     }
 }
@@ -0,0 +1,86 @@
+use std::{fs, io::Write, path::PathBuf};
+
+use nickel_lang_core::json_schema;
+
+use crate::{cli::GlobalOptions, error::CliResult};
+
+#[cfg(feature = "doc")]
+use crate::{error::ResultErrorExt, eval};
+
+#[derive(clap::Parser, Debug)]
+pub struct SchemaCommand {
+    #[command(subcommand)]
+    pub command: SchemaSubcommand,
+}
+
+#[derive(clap::Subcommand, Debug)]
+pub enum SchemaSubcommand {
+    /// Compiles a JSON Schema to a Nickel contract
+    Import(ImportCommand),
+    /// Converts the record contract defined by the input program to a JSON Schema
+    #[cfg(feature = "doc")]
+    Export(ExportCommand),
+}
+
+#[derive(clap::Parser, Debug)]
+pub struct ImportCommand {
+    /// The JSON Schema to compile
+    pub schema: PathBuf,
+
+    /// Output file. Standard output by default
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+}
+
+#[cfg(feature = "doc")]
+#[derive(clap::Parser, Debug)]
+pub struct ExportCommand {
+    /// Output file. Standard output by default
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+}
+
+impl SchemaCommand {
+    #[cfg_attr(not(feature = "doc"), allow(unused_variables))]
+    pub fn run(self, global: GlobalOptions) -> CliResult<()> {
+        match self.command {
+            SchemaSubcommand::Import(import) => import.run(),
+            #[cfg(feature = "doc")]
+            SchemaSubcommand::Export(export) => export.run(global),
+        }
+    }
+}
+
+impl ImportCommand {
+    fn run(self) -> CliResult<()> {
+        let schema: serde_json::Value =
+            serde_json::from_reader(fs::File::open(&self.schema)?).map_err(std::io::Error::from)?;
+        let contract = json_schema::import(&schema)?;
+
+        write_output(self.output, contract.as_bytes())
+    }
+}
+
+#[cfg(feature = "doc")]
+impl ExportCommand {
+    fn run(self, global: GlobalOptions) -> CliResult<()> {
+        let mut program = eval::prepare(&global)?;
+        let term = program.eval_record_spine().report_with_program(program)?;
+        let schema = json_schema::export(&term)?;
+
+        let mut out = serde_json::to_vec_pretty(&schema).map_err(std::io::Error::from)?;
+        out.push(b'\n');
+
+        write_output(self.output, &out)
+    }
+}
+
+fn write_output(output: Option<PathBuf>, content: &[u8]) -> CliResult<()> {
+    if let Some(file) = output {
+        fs::write(file, content)?;
+    } else {
+        std::io::stdout().write_all(content)?;
+    }
+
+    Ok(())
+}
@@ -1,15 +1,14 @@
-use crate::{
-    cli::GlobalOptions,
-    error::{CliResult, ResultErrorExt},
-    eval,
-};
+use crate::{cli::GlobalOptions, error::CliResult, eval, watch::WatchOptions};
 
 #[derive(clap::Parser, Debug)]
-pub struct TypecheckCommand {}
+pub struct TypecheckCommand {
+    #[command(flatten)]
+    pub watch: WatchOptions,
+}
 
 impl TypecheckCommand {
     pub fn run(self, global: GlobalOptions) -> CliResult<()> {
-        let mut program = eval::prepare(&global)?;
-        program.typecheck().report_with_program(program)
+        let program = eval::prepare(&global)?;
+        self.watch.run(program, |program| program.typecheck())
     }
 }
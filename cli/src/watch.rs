@@ -0,0 +1,155 @@
+//! Watch mode: run a command again each time one of the files of the program changes on disk.
+use std::{
+    collections::HashSet,
+    io,
+    path::PathBuf,
+    sync::mpsc::{channel, Receiver},
+    thread,
+    time::Duration,
+};
+
+use nickel_lang_core::{error::Error, eval::cache::CacheImpl, program::Program};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher as _};
+
+use crate::error::{self, CliResult, CliUsageError, ResultErrorExt};
+
+/// Editors often save a file through several successive operations. After a first change has
+/// been notified, we wait for this amount of time before running the command again.
+const DEBOUNCE_DELAY: Duration = Duration::from_millis(50);
+
+#[derive(clap::Args, Debug)]
+pub struct WatchOptions {
+    /// Run the command again each time the input file or one of its imports changes on disk
+    #[arg(long)]
+    pub watch: bool,
+
+    /// Detect changes by checking the modification time of files at this interval, instead of
+    /// relying on filesystem notifications
+    #[arg(long, value_name = "MILLISECONDS", requires = "watch")]
+    pub poll_interval: Option<u64>,
+}
+
+impl WatchOptions {
+    /// Run `command` on `program` once, or repeatedly if watch mode is enabled. In the latter case,
+    /// errors are reported without stopping, and the command is only run again when a file
+    /// actually changed, so that each change prints exactly one new result or error.
+    pub fn run<F>(&self, mut program: Program<CacheImpl>, mut command: F) -> CliResult<()>
+    where
+        F: FnMut(&mut Program<CacheImpl>) -> Result<(), Error>,
+    {
+        if !self.watch {
+            return command(&mut program).report_with_program(program);
+        }
+
+        if program.source_files().is_empty() {
+            return Err(error::Error::CliUsage {
+                error: CliUsageError::WatchWithoutFile,
+                program,
+            });
+        }
+
+        let mut watcher = match self.poll_interval {
+            Some(interval) => Watcher::Poll(Duration::from_millis(interval)),
+            None => Watcher::notify()?,
+        };
+
+        loop {
+            if let Err(error) = command(&mut program) {
+                program.report(error);
+            }
+
+            loop {
+                // The files of the program are only known up to the point where the last run
+                // failed, if it did, so they must be computed again after each run.
+                watcher.wait(&program.watched_files())?;
+
+                match program.reload() {
+                    Ok(true) => break,
+                    Ok(false) => (),
+                    // The main file might be temporarily missing while being saved. We report
+                    // the error and wait for the next change.
+                    Err(error) => program.report(error),
+                }
+            }
+        }
+    }
+}
+
+/// The mechanism used to wait for changes on disk.
+enum Watcher {
+    /// Check for changes at regular intervals.
+    Poll(Duration),
+    /// Use the filesystem notifications of the operating system.
+    Notify {
+        watcher: RecommendedWatcher,
+        events: Receiver<notify::Result<notify::Event>>,
+        /// The directories currently watched.
+        watched: HashSet<PathBuf>,
+    },
+}
+
+impl Watcher {
+    fn notify() -> io::Result<Self> {
+        let (sender, events) = channel();
+        let watcher = notify::recommended_watcher(sender).map_err(notify_error)?;
+
+        Ok(Watcher::Notify {
+            watcher,
+            events,
+            watched: HashSet::new(),
+        })
+    }
+
+    /// Block until something might have changed in `files`. Spurious wake-ups are possible: the
+    /// caller is responsible for checking whether the files actually changed.
+    fn wait(&mut self, files: &[PathBuf]) -> io::Result<()> {
+        match self {
+            Watcher::Poll(interval) => {
+                thread::sleep(*interval);
+                Ok(())
+            }
+            Watcher::Notify {
+                watcher,
+                events,
+                watched,
+            } => {
+                // We watch the parent directories rather than the files themselves, because many
+                // editors save a file by replacing it, which silently ends watching the original.
+                // The directory of a missing import might not exist either, in which case we can't
+                // watch it.
+                let dirs: HashSet<PathBuf> = files
+                    .iter()
+                    .filter_map(|file| file.parent().map(PathBuf::from))
+                    .filter(|dir| dir.is_dir())
+                    .collect();
+
+                for dir in watched.difference(&dirs) {
+                    // The directory might have been removed in the meantime, in which case it's
+                    // not watched anymore anyway.
+                    let _ = watcher.unwatch(dir);
+                }
+
+                for dir in dirs.difference(watched) {
+                    watcher
+                        .watch(dir, RecursiveMode::NonRecursive)
+                        .map_err(notify_error)?;
+                }
+
+                *watched = dirs;
+
+                events
+                    .recv()
+                    .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?
+                    .map_err(notify_error)?;
+                thread::sleep(DEBOUNCE_DELAY);
+                events.try_iter().for_each(drop);
+
+                Ok(())
+            }
+        }
+    }
+}
+
+fn notify_error(error: notify::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, error)
+}
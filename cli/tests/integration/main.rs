@@ -1,6 +1,10 @@
 use std::{
-    io::Write,
+    fs,
+    io::{BufRead, BufReader, Write},
     process::{Command, Stdio},
+    sync::mpsc,
+    thread,
+    time::{Duration, SystemTime},
 };
 
 use tempfile::tempdir;
@@ -78,3 +82,59 @@ fn automatic_color_on_non_tty() {
         );
     }
 }
+
+#[test]
+fn export_watch_reruns_on_import_change() {
+    let dir = tempdir().expect("should be able to make a temporary directory");
+    let main = dir.path().join("main.ncl");
+    let dep = dir.path().join("dep.ncl");
+    fs::write(&main, "{ foo = import \"dep.ncl\" }").unwrap();
+    fs::write(&dep, "1").unwrap();
+
+    let nickel_bin = env!("CARGO_BIN_EXE_nickel");
+    let mut nickel = Command::new(nickel_bin)
+        .args([
+            "export",
+            "--format",
+            "toml",
+            "--watch",
+            "--poll-interval",
+            "10",
+            "-f",
+        ])
+        .arg(&main)
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("Nickel should be runnable");
+    let stdout = nickel
+        .stdout
+        .take()
+        .expect("couldn't retrieve stdout handle to Nickel");
+
+    // Watch mode never exits: read the output from another thread, so that a missing line makes
+    // the test fail instead of blocking forever.
+    let (sender, lines) = mpsc::channel();
+    thread::spawn(move || {
+        for line in BufReader::new(stdout).lines() {
+            if sender.send(line.unwrap()).is_err() {
+                break;
+            }
+        }
+    });
+    let next_line = || lines.recv_timeout(Duration::from_secs(30));
+
+    assert_eq!(next_line(), Ok(String::from("foo = 1")));
+
+    fs::write(&dep, "2").unwrap();
+    // Make sure the timestamp changes even on filesystems with a coarse resolution.
+    fs::File::options()
+        .write(true)
+        .open(&dep)
+        .unwrap()
+        .set_modified(SystemTime::now() + Duration::from_secs(60))
+        .unwrap();
+
+    let result = next_line();
+    nickel.kill().expect("Nickel should be killable");
+    assert_eq!(result, Ok(String::from("foo = 2")));
+}
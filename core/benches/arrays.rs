@@ -1,5 +1,3 @@
-use std::rc::Rc;
-
 use criterion::{criterion_main, Criterion};
 use nickel_lang_core::term::{
     array::{Array, ArrayAttrs},
@@ -24,7 +22,7 @@ fn ncl_random_array(len: usize) -> String {
     }
 
     let xs = RichTerm::from(Term::Array(
-        Array::new(Rc::from(numbers)),
+        Array::from_iter(numbers),
         ArrayAttrs::default(),
     ));
     let doc: DocBuilder<_, ()> = xs.pretty(&BoxAllocator);
@@ -140,6 +138,10 @@ config = Criterion::default().with_profiler(PProfProfiler::new(100, Output::Flam
         name = "sort normal",
         path = "arrays/sort",
         args = (ncl_random_array(50)),
+    }, {
+        name = "sort_by_key normal",
+        path = "arrays/sort_by_key",
+        args = (ncl_random_array(50)),
     }, {
         name = "sum normal 50",
         path = "arrays/sum",
@@ -158,6 +160,21 @@ config = Criterion::default().with_profiler(PProfProfiler::new(100, Output::Flam
         path = "arrays/primes",
         args = (30),
         eval_mode = EvalMode::DeepSeq,
+    }, {
+        name = "build append 50000",
+        path = "arrays/build",
+        subtest = "append",
+        args = (50000),
+    }, {
+        name = "build prepend 50000",
+        path = "arrays/build",
+        subtest = "prepend",
+        args = (50000),
+    }, {
+        name = "build concat 50000",
+        path = "arrays/build",
+        subtest = "concat",
+        args = (50000),
     }, {
         name = "random normal",
         path = "arrays/random",
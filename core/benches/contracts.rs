@@ -0,0 +1,38 @@
+use criterion::{criterion_main, Criterion};
+use nickel_lang_utils::{bench::EvalMode, ncl_bench_group};
+use pprof::criterion::{Output, PProfProfiler};
+
+/// Generates a nested record contract as a string, with `width` fields at each level and `depth`
+/// levels of records. Each field has a default value, so that `{}` satisfies the contract.
+fn ncl_schema(depth: usize, width: usize) -> String {
+    if depth == 0 {
+        return String::from("Number | default = 0");
+    }
+
+    let sub_schema = ncl_schema(depth - 1, width);
+    let default = if depth == 1 { "" } else { " | default = {}" };
+    let fields: Vec<String> = (0..width)
+        .map(|i| format!("field{i} | {sub_schema}{default}"))
+        .collect();
+
+    format!("{{ {} }}", fields.join(", "))
+}
+
+ncl_bench_group! {
+    name = benches;
+    config = Criterion::default().with_profiler(PProfProfiler::new(100, Output::Flamegraph(None)));
+    {
+        name = "merge pieces with the same schema",
+        path = "contracts/schema",
+        subtest = "merge",
+        args = (ncl_schema(4, 4), 20),
+        eval_mode = EvalMode::DeepSeq,
+    }, {
+        name = "array through typed functions",
+        path = "contracts/schema",
+        subtest = "arrays",
+        args = (ncl_schema(4, 4), 20),
+        eval_mode = EvalMode::DeepSeq,
+    }
+}
+criterion_main!(benches);
@@ -0,0 +1,58 @@
+use std::io::Cursor;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use pprof::criterion::{Output, PProfProfiler};
+
+use nickel_lang_core::{
+    eval::cache::SelectedCache,
+    program::{FieldOverride, Program},
+    term::MergePriority,
+};
+
+/// A record with `size` fields depending on `input` and `size` fields that don't. Each field does
+/// a bit of work, so that re-evaluating it is noticeable.
+fn source(size: usize) -> String {
+    let work = "std.array.fold_left (+) 0 (std.array.generate std.function.id 100)";
+    let fields: String = (0..size)
+        .map(|i| format!("dep_{i} = input + {work}, indep_{i} = {i} + {work},"))
+        .collect();
+    format!("{{ input | default = 0, {fields} }}")
+}
+
+/// Repeatedly override `input` and re-evaluate the program.
+fn bench_override(c: &mut Criterion, name: &str, incremental: bool) {
+    let mut program: Program<SelectedCache> =
+        Program::new_from_source(Cursor::new(source(100)), "<bench>", std::io::sink()).unwrap();
+    program.set_eval_cache(SelectedCache::with_incremental(incremental));
+    program.eval_incremental().unwrap();
+
+    let mut value = 0;
+    c.bench_function(name, |b| {
+        b.iter(|| {
+            value += 1;
+            program
+                .set_override(FieldOverride {
+                    path: vec![String::from("input")],
+                    value: value.to_string(),
+                    priority: MergePriority::Top,
+                })
+                .unwrap();
+            program.eval_incremental().unwrap()
+        })
+    });
+}
+
+pub fn override_lazy(c: &mut Criterion) {
+    bench_override(c, "override lazy", false)
+}
+
+pub fn override_incremental(c: &mut Criterion) {
+    bench_override(c, "override incremental", true)
+}
+
+criterion_group!(
+name = benches;
+config = Criterion::default().with_profiler(PProfProfiler::new(100, Output::Flamegraph(None)));
+targets = override_lazy, override_incremental
+);
+criterion_main!(benches);
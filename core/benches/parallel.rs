@@ -0,0 +1,61 @@
+use std::{io::Cursor, num::NonZeroUsize};
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use pprof::criterion::{Output, PProfProfiler};
+
+use nickel_lang_core::{eval::cache::CacheImpl, program::Program};
+
+/// A record with `size` independent fields, each of which takes a while to evaluate.
+fn source(size: usize) -> String {
+    let fields: String = (0..size)
+        .map(|i| {
+            format!(
+                "field_{i} = std.array.fold_left (+) {i} \
+                (std.array.generate (fun x => x * x) 20000),"
+            )
+        })
+        .collect();
+    format!("{{ {fields} }}")
+}
+
+/// Export the record generated by [source] on `threads` threads.
+fn bench_export(c: &mut Criterion, threads: usize) {
+    let source = source(16);
+    let threads = NonZeroUsize::new(threads).unwrap();
+
+    c.bench_function(&format!("export on {threads} threads"), |b| {
+        b.iter_batched(
+            || {
+                Program::<CacheImpl>::new_from_source(
+                    Cursor::new(source.clone()),
+                    "<bench>",
+                    std::io::sink(),
+                )
+                .unwrap()
+            },
+            |mut program| {
+                program
+                    .eval_full_for_export_parallel(Vec::new(), threads)
+                    .unwrap()
+            },
+            criterion::BatchSize::LargeInput,
+        )
+    });
+}
+
+pub fn export_sequential(c: &mut Criterion) {
+    bench_export(c, 1)
+}
+
+pub fn export_parallel(c: &mut Criterion) {
+    bench_export(c, 4)
+}
+
+criterion_group!(
+name = benches;
+config = Criterion::default()
+    .sample_size(10)
+    .with_profiler(PProfProfiler::new(100, Output::Flamegraph(None)));
+targets = export_sequential, export_parallel
+);
+criterion_main!(benches);
@@ -0,0 +1,34 @@
+use criterion::{criterion_main, Criterion};
+use nickel_lang_utils::ncl_bench_group;
+use pprof::criterion::{Output, PProfProfiler};
+
+ncl_bench_group! {
+    name = benches;
+    config = Criterion::default().with_profiler(PProfProfiler::new(100, Output::Flamegraph(None)));
+    {
+        name = "interpolation 1000",
+        path = "strings/interpolation",
+        args = (1000),
+    }, {
+        name = "concat append 10000",
+        path = "strings/concat",
+        subtest = "append",
+        args = (10000),
+    }, {
+        name = "concat append char 100000",
+        path = "strings/concat",
+        subtest = "append_char",
+        args = (100000),
+    }, {
+        name = "concat join 10000",
+        path = "strings/concat",
+        subtest = "join",
+        args = (10000),
+    }, {
+        name = "concat template 10000",
+        path = "strings/concat",
+        subtest = "template",
+        args = (10000),
+    }
+}
+criterion_main!(benches);
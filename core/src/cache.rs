@@ -4,6 +4,7 @@ use crate::error::{Error, ImportError, ParseError, ParseErrors, TypecheckError};
 use crate::eval::cache::Cache as EvalCache;
 use crate::eval::Closure;
 use crate::parser::{lexer::Lexer, ErrorTolerantParser};
+use crate::persistent_cache::{self, PersistentCache, Stage};
 use crate::position::TermPos;
 use crate::stdlib::{self as nickel_stdlib, StdlibModule};
 use crate::term::array::Array;
@@ -12,6 +13,7 @@ use crate::term::{RichTerm, SharedTerm, Term};
 use crate::transform::import_resolution;
 use crate::typ::UnboundTypeVariableError;
 use crate::typecheck::{self, type_check, Wildcards};
+use crate::vfs::{FileSystem, OsFileSystem};
 use crate::{eval, parser, transform};
 use codespan::{FileId, Files};
 use io::Read;
@@ -73,6 +75,9 @@ pub struct Cache {
     imports: HashMap<FileId, HashSet<FileId>>,
     /// Map containing for each FileId a list of files importing them (directly).
     rev_imports: HashMap<FileId, HashSet<FileId>>,
+    /// Map containing for each FileId the paths it failed to import because they couldn't be
+    /// read.
+    failed_imports: HashMap<FileId, HashSet<PathBuf>>,
     /// The table storing parsed terms corresponding to the entries of the file database.
     terms: HashMap<FileId, TermEntry>,
     /// The list of ids corresponding to the stdlib modules
@@ -81,6 +86,12 @@ pub struct Cache {
     wildcards: HashMap<FileId, Wildcards>,
     /// Whether processing should try to continue even in case of errors. Needed by the NLS.
     error_tolerance: ErrorTolerance,
+    /// The on-disk cache of transformed terms, if enabled. See [crate::persistent_cache].
+    persistent: Option<PersistentCache>,
+    /// The files which can be imported.
+    import_policy: ImportPolicy,
+    /// The filesystem which files are read from, if not the one of the operating system.
+    file_system: Option<Rc<dyn FileSystem>>,
 
     #[cfg(debug_assertions)]
     /// Skip loading the stdlib, used for debugging purpose
@@ -95,6 +106,35 @@ pub enum ErrorTolerance {
     Strict,
 }
 
+/// The files that `import` expressions are allowed to read, used to evaluate untrusted programs.
+///
+/// The policy applies to imports and to the files loaded with [Cache::add_file_checked], such as
+/// by the `:load` command of the REPL: the main program, the standard library and the sources
+/// added directly to the cache are not affected. Except for [ImportPolicy::Allow], import paths
+/// containing `..` are always rejected.
+///
+/// To only allow files provided by the host, without accessing the filesystem of the operating
+/// system, set a [crate::vfs::MemoryFileSystem] with [Cache::set_file_system] instead.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum ImportPolicy {
+    /// Any file can be imported.
+    #[default]
+    Allow,
+    /// No file can be imported.
+    Deny,
+    /// Only files located under one of these directories can be imported. Files reached through
+    /// a symbolic link are rejected, even if the link is itself under an allowed directory.
+    Roots(Vec<PathBuf>),
+}
+
+/// Where to read an import from, once allowed by the [ImportPolicy].
+enum ImportSource {
+    /// The resolved path of the import.
+    Resolved,
+    /// The canonical path of the import, which has been checked instead of the resolved path.
+    Canonical(PathBuf),
+}
+
 /// The different environments maintained during the REPL session for evaluation and typechecking.
 #[derive(Debug, Clone)]
 pub struct Envs {
@@ -314,18 +354,77 @@ impl Cache {
             wildcards: HashMap::new(),
             imports: HashMap::new(),
             rev_imports: HashMap::new(),
+            failed_imports: HashMap::new(),
             stdlib_ids: None,
             error_tolerance,
+            persistent: None,
+            import_policy: ImportPolicy::default(),
+            file_system: None,
 
             #[cfg(debug_assertions)]
             skip_stdlib: false,
         }
     }
 
+    /// Restrict the files which can be imported. Only affects the imports resolved after this
+    /// call.
+    pub fn set_import_policy(&mut self, policy: ImportPolicy) {
+        self.import_policy = policy;
+    }
+
+    /// Return the current import policy.
+    pub fn import_policy(&self) -> &ImportPolicy {
+        &self.import_policy
+    }
+
+    /// Read files, including imports, from `file_system` instead of the filesystem of the
+    /// operating system. Only affects the files loaded after this call.
+    pub fn set_file_system(&mut self, file_system: impl FileSystem + 'static) {
+        self.file_system = Some(Rc::new(file_system));
+    }
+
+    /// Return `true` if files are read from the filesystem of the operating system, that is if
+    /// no other one has been set with [Self::set_file_system].
+    pub fn uses_os_file_system(&self) -> bool {
+        self.file_system.is_none()
+    }
+
+    fn fs(&self) -> &dyn FileSystem {
+        self.file_system.as_deref().unwrap_or(&OsFileSystem)
+    }
+
+    /// Same as [normalize_path], but relatively to the current directory of the filesystem of
+    /// the cache.
+    fn normalize_path(&self, path: impl Into<PathBuf>) -> io::Result<PathBuf> {
+        normalize_path_in(self.fs(), path.into())
+    }
+
+    /// Enable the persistent cache: terms are restored from `persistent` when possible by
+    /// [Self::prepare] and [Self::prepare_stdlib], and stored there after having been processed.
+    pub fn set_persistent_cache(&mut self, persistent: PersistentCache) {
+        self.persistent = Some(persistent);
+    }
+
+    /// Return the persistent cache, if enabled.
+    pub fn persistent_cache(&self) -> Option<&PersistentCache> {
+        self.persistent.as_ref()
+    }
+
+    /// Return `true` if the standard library has been loaded.
+    pub fn stdlib_loaded(&self) -> bool {
+        self.stdlib_ids.is_some()
+    }
+
     /// Same as [Self::add_file], but assume that the path is already normalized, and take the
-    /// timestamp as a parameter.
-    fn add_file_(&mut self, path: PathBuf, timestamp: SystemTime) -> io::Result<FileId> {
-        let contents = std::fs::read_to_string(&path)?;
+    /// timestamp as a parameter. The file is read from `source`, which is the same as `path`
+    /// unless the import policy has checked a canonical path instead.
+    fn add_file_(
+        &mut self,
+        path: PathBuf,
+        source: &Path,
+        timestamp: SystemTime,
+    ) -> io::Result<FileId> {
+        let contents = self.fs().read_to_string(source)?;
         let file_id = self.files.add(&path, contents);
         self.file_paths
             .insert(file_id, SourcePath::Path(path.clone()));
@@ -344,22 +443,130 @@ impl Cache {
     /// Uses the normalized path and the *modified at* timestamp as the name-id table entry.
     /// Overrides any existing entry with the same name.
     pub fn add_file(&mut self, path: impl Into<OsString>) -> io::Result<FileId> {
+        let normalized = self.normalize_path(path.into())?;
+        let timestamp = self.fs().modified(&normalized)?;
+        self.add_file_(normalized.clone(), &normalized, timestamp)
+    }
+
+    /// Same as [Self::add_file], but for a file loaded on behalf of the program, such as by the
+    /// `:load` command of the REPL: the file is subject to the import policy, as imports are.
+    pub fn add_file_checked(
+        &mut self,
+        path: impl Into<OsString>,
+        pos: &TermPos,
+    ) -> Result<FileId, ImportError> {
         let path = path.into();
-        let timestamp = timestamp(&path)?;
-        let normalized = normalize_path(&path)?;
-        self.add_file_(normalized, timestamp)
+        let resolved = PathBuf::from(&path);
+
+        let id = match self.check_import(&path, &resolved, pos)? {
+            ImportSource::Resolved => self.add_file(&path),
+            ImportSource::Canonical(canonical) => {
+                self.normalize_path(&resolved).and_then(|normalized| {
+                    let timestamp = self.fs().modified(&canonical)?;
+                    self.add_file_(normalized, &canonical, timestamp)
+                })
+            }
+        };
+
+        id.map_err(|err| {
+            ImportError::IOError(
+                resolved.to_string_lossy().into_owned(),
+                format!("{err}"),
+                *pos,
+            )
+        })
     }
 
     /// Try to retrieve the id of a file from the cache.
     ///
     /// If it was not in cache, try to read it from the filesystem and add it as a new entry.
     pub fn get_or_add_file(&mut self, path: impl Into<OsString>) -> io::Result<CacheOp<FileId>> {
-        let path = path.into();
-        let normalized = normalize_path(&path)?;
-        match self.id_or_new_timestamp_of(path.as_ref())? {
+        let normalized = self.normalize_path(path.into())?;
+        match self.id_or_new_timestamp_of(&normalized)? {
             SourceState::UpToDate(id) => Ok(CacheOp::Cached(id)),
-            SourceState::Stale(timestamp) => {
-                self.add_file_(normalized, timestamp).map(CacheOp::Done)
+            SourceState::Stale(timestamp) => self
+                .add_file_(normalized.clone(), &normalized, timestamp)
+                .map(CacheOp::Done),
+        }
+    }
+
+    /// Same as [Self::get_or_add_file], but read the file from `canonical`, the canonical path of
+    /// `path` which has been checked by the import policy. Reading `path` again would resolve
+    /// symbolic links anew, which might have been changed in the meantime.
+    fn get_or_add_canonical_file(
+        &mut self,
+        path: &Path,
+        canonical: &Path,
+    ) -> io::Result<CacheOp<FileId>> {
+        let normalized = self.normalize_path(path)?;
+        match self.id_or_new_timestamp_of(&normalized)? {
+            SourceState::UpToDate(id) => Ok(CacheOp::Cached(id)),
+            SourceState::Stale(_) => {
+                let timestamp = self.fs().modified(canonical)?;
+                self.add_file_(normalized, canonical, timestamp)
+                    .map(CacheOp::Done)
+            }
+        }
+    }
+
+    /// Check that the import policy allows to import `path`, which resolves to `resolved`, and
+    /// return where the imported file must be read from.
+    fn check_import(
+        &self,
+        path: &OsStr,
+        resolved: &Path,
+        pos: &TermPos,
+    ) -> Result<ImportSource, ImportError> {
+        use std::path::Component;
+
+        let forbidden = |reason: &str| {
+            ImportError::Forbidden(
+                resolved.to_string_lossy().into_owned(),
+                String::from(reason),
+                *pos,
+            )
+        };
+
+        if self.import_policy != ImportPolicy::Allow
+            && Path::new(path)
+                .components()
+                .any(|c| c == Component::ParentDir)
+        {
+            return Err(forbidden("the path contains `..`"));
+        }
+
+        match &self.import_policy {
+            ImportPolicy::Allow => Ok(ImportSource::Resolved),
+            ImportPolicy::Deny => Err(forbidden("imports are disabled")),
+            ImportPolicy::Roots(roots) => {
+                let io_error = |err: io::Error| {
+                    ImportError::IOError(
+                        resolved.to_string_lossy().into_owned(),
+                        format!("{err}"),
+                        *pos,
+                    )
+                };
+
+                let normalized = self.normalize_path(resolved).map_err(io_error)?;
+                let (root, relative) = roots
+                    .iter()
+                    .filter_map(|root| self.normalize_path(root).ok())
+                    .find_map(|root| {
+                        let relative = normalized.strip_prefix(&root).ok()?.to_owned();
+                        Some((root, relative))
+                    })
+                    .ok_or_else(|| forbidden("the file is outside of the allowed directories"))?;
+
+                // The allowed directory itself may be reached through a symbolic link, but
+                // nothing below it: the canonical path must match the lexically normalized one.
+                let canonical_root = self.fs().canonicalize(&root).map_err(io_error)?;
+                let canonical = self.fs().canonicalize(&normalized).map_err(io_error)?;
+
+                if canonical != canonical_root.join(relative) {
+                    return Err(forbidden("the path goes through a symbolic link"));
+                }
+
+                Ok(ImportSource::Canonical(canonical))
             }
         }
     }
@@ -561,13 +768,7 @@ impl Cache {
                     ))
                 } else {
                     Ok((
-                        attach_pos(
-                            Term::Array(
-                                Array::new(Rc::from(terms.into_boxed_slice())),
-                                Default::default(),
-                            )
-                            .into(),
-                        ),
+                        attach_pos(Term::Array(Array::from_iter(terms), Default::default()).into()),
                         ParseErrors::default(),
                     ))
                 }
@@ -853,6 +1054,10 @@ impl Cache {
         file_id: FileId,
         initial_ctxt: &typecheck::Context,
     ) -> Result<CacheOp<()>, Error> {
+        if self.entry_state(file_id).is_none() && self.restore(file_id) {
+            return Ok(CacheOp::Done(()));
+        }
+
         let mut result = CacheOp::Cached(());
 
         if let CacheOp::Done(_) = self.parse(file_id)? {
@@ -890,6 +1095,10 @@ impl Cache {
             result = CacheOp::Done(());
         };
 
+        if result == CacheOp::Done(()) {
+            self.persist(file_id);
+        }
+
         Ok(result)
     }
 
@@ -950,12 +1159,12 @@ impl Cache {
     /// the timestamp, keep it around.
     fn id_or_new_timestamp_of(&self, name: &Path) -> io::Result<SourceState> {
         match self.file_ids.get(&SourcePath::Path(name.to_owned())) {
-            None => Ok(SourceState::Stale(timestamp(name)?)),
+            None => Ok(SourceState::Stale(self.fs().modified(name)?)),
             Some(NameIdEntry {
                 id,
                 source: SourceKind::Filesystem(ts),
             }) => {
-                let new_timestamp = timestamp(name)?;
+                let new_timestamp = self.fs().modified(name)?;
                 if ts == &new_timestamp {
                     Ok(SourceState::UpToDate(*id))
                 } else {
@@ -1046,6 +1255,76 @@ impl Cache {
             .copied()
     }
 
+    /// Returns the paths that this file failed to import because they couldn't be read, for
+    /// example because they didn't exist yet.
+    pub fn get_failed_imports(&self, file: FileId) -> impl Iterator<Item = &Path> + '_ {
+        self.failed_imports
+            .get(&file)
+            .into_iter()
+            .flat_map(|s| s.iter())
+            .map(PathBuf::as_path)
+    }
+
+    /// Returns the set of files that this file transitively imports.
+    pub fn get_imports_transitive(&self, file: FileId) -> HashSet<FileId> {
+        let mut ret = HashSet::new();
+        let mut stack = vec![file];
+
+        while let Some(file) = stack.pop() {
+            for f in self.get_imports(file) {
+                if ret.insert(f) {
+                    stack.push(f);
+                }
+            }
+        }
+
+        ret
+    }
+
+    /// Returns the files among `files` that were read from the filesystem and have been modified
+    /// on disk since, or that failed to import a file which can now be read, together with all
+    /// the files that transitively depend on them. The latter are stale as well, because their
+    /// resolved imports refer to the previous versions.
+    pub fn get_stale_transitive(&self, files: impl IntoIterator<Item = FileId>) -> HashSet<FileId> {
+        let mut ret = HashSet::new();
+
+        for file in files {
+            let Some(path) = self.get_filesystem_path(file) else {
+                continue;
+            };
+
+            let up_to_date = matches!(
+                self.id_or_new_timestamp_of(path),
+                Ok(SourceState::UpToDate(id)) if id == file
+            ) && self
+                .get_failed_imports(file)
+                .all(|import| self.fs().modified(import).is_err());
+
+            if !up_to_date && ret.insert(file) {
+                ret.extend(self.get_rev_imports_transitive(file));
+            }
+        }
+
+        ret
+    }
+
+    /// Returns the path of a file read from the filesystem, or `None` if the source of this file
+    /// lives in memory.
+    pub fn get_filesystem_path(&self, file: FileId) -> Option<&Path> {
+        let source_path = self.file_paths.get(&file)?;
+
+        match self.file_ids.get(source_path) {
+            Some(NameIdEntry {
+                source: SourceKind::Filesystem(_),
+                ..
+            }) => match source_path {
+                SourcePath::Path(path) => Some(path.as_path()),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
     /// Returns the set of files that transitively depend on this file.
     pub fn get_rev_imports_transitive(&self, file: FileId) -> HashSet<FileId> {
         let mut ret = HashSet::new();
@@ -1086,7 +1365,10 @@ impl Cache {
             .collect();
 
         for (_, file_id) in file_ids.iter() {
-            self.parse(*file_id)?;
+            if !self.restore_single(*file_id, Stage::Parsed) {
+                self.parse(*file_id)?;
+                self.persist_single(*file_id, Stage::Parsed);
+            }
         }
         self.stdlib_ids.replace(file_ids);
         Ok(CacheOp::Done(()))
@@ -1149,11 +1431,18 @@ impl Cache {
             // is bound directly in the environment without evaluating it first, so we can't
             // tolerate top-level let bindings that would be introduced by `transform`.
             .try_for_each(|(module, file_id)| {
+                if self.entry_state(file_id) >= Some(EntryState::Transformed)
+                    || self.restore_single(file_id, Stage::Transformed)
+                {
+                    return Ok(());
+                }
+
                 if let nickel_stdlib::StdlibModule::Internals = module {
                     self.transform_inner(file_id)?;
                 } else {
                     self.transform(file_id)?;
                 }
+                self.persist_single(file_id, Stage::Transformed);
                 Ok(())
             })
             .map_err(|cache_err: CacheError<UnboundTypeVariableError>| {
@@ -1172,6 +1461,197 @@ impl Cache {
         })
     }
 
+    /// Return the persistent cache if it is enabled and can be used for this cache. Error
+    /// tolerant mode is excluded, as it can produce terms containing parse errors. So are caches
+    /// without the standard library, which would make typechecking depend on something else than
+    /// the key of the entries. Restoring an entry loads its imports without going through
+    /// [ImportResolver::resolve], so the persistent cache is also disabled by import policies.
+    fn usable_persistent_cache(&self) -> Option<PersistentCache> {
+        match (&self.persistent, &self.error_tolerance) {
+            (Some(persistent), ErrorTolerance::Strict)
+                if self.stdlib_ids.is_some() && self.import_policy == ImportPolicy::Allow =>
+            {
+                Some(persistent.clone())
+            }
+            _ => None,
+        }
+    }
+
+    /// Return the key of a source in the persistent cache at the given stage.
+    fn persistent_key(
+        &self,
+        persistent: &PersistentCache,
+        file_id: FileId,
+        stage: Stage,
+    ) -> String {
+        persistent.key(stage, self.name(file_id), self.source(file_id))
+    }
+
+    /// Try to restore a source without imports, such as a stdlib module, from the persistent
+    /// cache at the given stage. Return `true` on success.
+    fn restore_single(&mut self, file_id: FileId, stage: Stage) -> bool {
+        let Some(persistent) = self.persistent.clone() else {
+            return false;
+        };
+        let key = self.persistent_key(&persistent, file_id, stage);
+        let Some(term) = persistent
+            .load(&key)
+            .filter(|entry| entry.imports().is_empty())
+            .and_then(|entry| entry.decode_term(file_id, &[]).ok())
+        else {
+            return false;
+        };
+
+        let state = match stage {
+            Stage::Parsed => EntryState::Parsed,
+            Stage::Transformed => EntryState::Transformed,
+        };
+        self.terms.insert(
+            file_id,
+            TermEntry {
+                term,
+                state,
+                parse_errs: ParseErrors::none(),
+            },
+        );
+        true
+    }
+
+    /// Store a source at the given stage in the persistent cache, if enabled.
+    fn persist_single(&self, file_id: FileId, stage: Stage) {
+        let Some(persistent) = self.persistent.as_ref() else {
+            return;
+        };
+        let Some(TermEntry {
+            term, parse_errs, ..
+        }) = self.terms.get(&file_id)
+        else {
+            return;
+        };
+
+        if !parse_errs.no_errors() {
+            return;
+        }
+
+        let key = self.persistent_key(persistent, file_id, stage);
+
+        // An existing entry is overwritten on purpose: its key doesn't account for the imports of
+        // the file, which may have changed since it was written.
+        persistent.store(&key, file_id, term, |import| {
+            match self.file_paths.get(&import) {
+                Some(SourcePath::Path(path)) => Some((
+                    path.clone(),
+                    persistent_cache::content_hash(self.source(import)),
+                )),
+                _ => None,
+            }
+        });
+    }
+
+    /// Try to restore a source and all of its transitive imports from the persistent cache, in
+    /// the [EntryState::Transformed] state. Return `true` on success.
+    ///
+    /// Either every file is restored or none is: typechecking a file against imports which have
+    /// already been transformed could give different results than the standard pipeline.
+    fn restore(&mut self, file_id: FileId) -> bool {
+        let Some(persistent) = self.usable_persistent_cache() else {
+            return false;
+        };
+
+        // First, load the entries of all the files involved and check that their imports are
+        // unchanged, without touching the term cache.
+        let mut loaded = Vec::new();
+        let mut visited = HashSet::new();
+        let mut pending = vec![file_id];
+
+        while let Some(id) = pending.pop() {
+            if !visited.insert(id) {
+                continue;
+            }
+
+            match self.entry_state(id) {
+                Some(state) if state >= EntryState::Transformed => continue,
+                Some(_) => return false,
+                None => (),
+            }
+
+            let key = self.persistent_key(&persistent, id, Stage::Transformed);
+            let Some(entry) = persistent.load(&key) else {
+                return false;
+            };
+
+            let mut imports = Vec::with_capacity(entry.imports().len());
+            for (path, hash) in entry.imports() {
+                let Ok(import_id) = self.get_or_add_file(path).map(CacheOp::inner) else {
+                    return false;
+                };
+
+                if persistent_cache::content_hash(self.source(import_id)) != *hash {
+                    return false;
+                }
+
+                imports.push(import_id);
+                pending.push(import_id);
+            }
+
+            loaded.push((id, entry, imports));
+        }
+
+        let Ok(decoded) = loaded
+            .into_iter()
+            .map(|(id, entry, imports)| {
+                entry
+                    .decode_term(id, &imports)
+                    .map(|term| (id, term, imports))
+            })
+            .collect::<Result<Vec<_>, _>>()
+        else {
+            return false;
+        };
+
+        for (id, term, imports) in decoded {
+            self.terms.insert(
+                id,
+                TermEntry {
+                    term,
+                    state: EntryState::Transformed,
+                    parse_errs: ParseErrors::none(),
+                },
+            );
+
+            for import_id in imports {
+                self.imports.entry(id).or_default().insert(import_id);
+                self.rev_imports.entry(import_id).or_default().insert(id);
+            }
+        }
+
+        true
+    }
+
+    /// Store a source and its transitive imports in the persistent cache, if enabled. Sources
+    /// which aren't files, or which can't be persisted, are skipped.
+    fn persist(&self, file_id: FileId) {
+        if self.usable_persistent_cache().is_none() {
+            return;
+        }
+
+        let mut visited = HashSet::new();
+        let mut pending = vec![file_id];
+
+        while let Some(id) = pending.pop() {
+            if !visited.insert(id) {
+                continue;
+            }
+
+            let is_file = matches!(self.file_paths.get(&id), Some(SourcePath::Path(_)));
+            if is_file && self.entry_state(id) == Some(EntryState::Transformed) {
+                self.persist_single(id, Stage::Transformed);
+            }
+
+            pending.extend(self.get_imports(id));
+        }
+    }
+
     /// Generate the initial typing context from the list of `file_ids` corresponding to the
     /// standard library parts.
     pub fn mk_type_ctxt(&self) -> Result<typecheck::Context, CacheError<Void>> {
@@ -1278,7 +1758,20 @@ impl ImportResolver for Cache {
         let parent_path = parent.and_then(|p| self.get_path(p)).map(PathBuf::from);
         let path_buf = with_parent(path, parent_path);
         let format = InputFormat::from_path(&path_buf).unwrap_or(InputFormat::Nickel);
-        let id_op = self.get_or_add_file(&path_buf).map_err(|err| {
+        let id_op = match self.check_import(path, &path_buf, pos)? {
+            ImportSource::Resolved => self.get_or_add_file(&path_buf),
+            ImportSource::Canonical(canonical) => {
+                self.get_or_add_canonical_file(&path_buf, &canonical)
+            }
+        }
+        .map_err(|err| {
+            if let Some(parent) = parent {
+                self.failed_imports
+                    .entry(parent)
+                    .or_default()
+                    .insert(path_buf.clone());
+            }
+
             ImportError::IOError(
                 path_buf.to_string_lossy().into_owned(),
                 format!("{err}"),
@@ -1329,9 +1822,13 @@ fn with_parent(path: &OsStr, parent: Option<PathBuf>) -> PathBuf {
 ///
 /// The returned path will be an absolute path.
 pub fn normalize_path(path: impl Into<PathBuf>) -> std::io::Result<PathBuf> {
-    let mut path = path.into();
+    normalize_path_in(&OsFileSystem, path.into())
+}
+
+/// Same as [normalize_path], but relatively to the current directory of `fs`.
+fn normalize_path_in(fs: &dyn FileSystem, mut path: PathBuf) -> io::Result<PathBuf> {
     if path.is_relative() {
-        path = std::env::current_dir()?.join(path);
+        path = fs.current_dir()?.join(path);
     }
     Ok(normalize_abs_path(&path))
 }
@@ -1346,7 +1843,7 @@ pub fn normalize_path(path: impl Into<PathBuf>) -> std::io::Result<PathBuf> {
 /// [`std::fs::canonicalize`] can be hard to use correctly, since it can often
 /// fail, or on Windows returns annoying device paths. This is a problem Cargo
 /// needs to improve on.
-fn normalize_abs_path(path: &Path) -> PathBuf {
+pub(crate) fn normalize_abs_path(path: &Path) -> PathBuf {
     use std::path::Component;
 
     let mut components = path.components().peekable();
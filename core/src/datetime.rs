@@ -0,0 +1,356 @@
+//! Date, time and duration support for the `std.datetime` module.
+//!
+//! Datetimes are represented in Nickel as RFC 3339 strings, and durations as numbers of seconds,
+//! which can be converted from and to ISO 8601 duration strings. Every operation is pure: there
+//! is no way to access the current time from a Nickel program, which would break reproducibility.
+//! A host that needs the current time must pass it explicitly, e.g. as an input field.
+use chrono::{
+    format::{Item, StrftimeItems},
+    DateTime, Datelike, FixedOffset, SecondsFormat, Timelike, Utc,
+};
+use malachite::{
+    num::{
+        basic::traits::Zero,
+        conversion::traits::{RoundingFrom, ToSci},
+    },
+    rounding_modes::RoundingMode,
+    Integer,
+};
+
+use crate::{parser::utils::parse_number, term::Number};
+
+const NANOS_PER_SECOND: i64 = 1_000_000_000;
+const SECONDS_PER_MINUTE: i64 = 60;
+const SECONDS_PER_HOUR: i64 = 60 * SECONDS_PER_MINUTE;
+const SECONDS_PER_DAY: i64 = 24 * SECONDS_PER_HOUR;
+const SECONDS_PER_WEEK: i64 = 7 * SECONDS_PER_DAY;
+
+/// The components of a parsed datetime, as exposed by `std.datetime.parse`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Components {
+    /// The number of seconds since the Unix epoch, including the fractional part.
+    pub timestamp: Number,
+    /// The offset from UTC, in seconds.
+    pub offset: i32,
+    pub year: i32,
+    pub month: u32,
+    pub day: u32,
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+    pub nanosecond: u32,
+    /// The day of the week, from `1` (Monday) to `7` (Sunday).
+    pub weekday: u32,
+    /// The day of the year, from `1` to `366`.
+    pub day_of_year: u32,
+}
+
+/// Parse an RFC 3339 datetime, such as `2023-06-01T12:30:00+02:00`.
+pub fn parse(s: &str) -> Result<DateTime<FixedOffset>, String> {
+    DateTime::parse_from_rfc3339(s).map_err(|err| format!("invalid RFC 3339 datetime `{s}`: {err}"))
+}
+
+/// Parse an RFC 3339 datetime and return its components.
+pub fn components(s: &str) -> Result<Components, String> {
+    let datetime = parse(s)?;
+
+    Ok(Components {
+        timestamp: timestamp(&datetime),
+        offset: datetime.offset().local_minus_utc(),
+        year: datetime.year(),
+        month: datetime.month(),
+        day: datetime.day(),
+        hour: datetime.hour(),
+        minute: datetime.minute(),
+        // A leap second is represented by chrono as a nanosecond count greater than one
+        // billion. We expose it as the 60th second instead.
+        second: datetime.second() + datetime.nanosecond() / NANOS_PER_SECOND as u32,
+        nanosecond: datetime.nanosecond() % NANOS_PER_SECOND as u32,
+        weekday: datetime.weekday().number_from_monday(),
+        day_of_year: datetime.ordinal(),
+    })
+}
+
+/// Return the exact number of seconds elapsed since the Unix epoch.
+fn timestamp(datetime: &DateTime<FixedOffset>) -> Number {
+    Number::from(datetime.timestamp())
+        + Number::from_signeds(
+            i64::from(datetime.timestamp_subsec_nanos()),
+            NANOS_PER_SECOND,
+        )
+}
+
+/// Render a Unix timestamp as an RFC 3339 datetime with the given offset from UTC, in seconds.
+/// The fractional part of the seconds is only printed when non-zero.
+pub fn from_timestamp(timestamp: &Number, offset: &Number) -> Result<String, String> {
+    let offset = i32::try_from(offset)
+        .ok()
+        .and_then(FixedOffset::east_opt)
+        .ok_or_else(|| {
+            format!(
+                "invalid offset {}: expected an integer number of seconds strictly between \
+                -86400 and 86400",
+                offset.to_sci()
+            )
+        })?;
+
+    let secs = Integer::rounding_from(timestamp.clone(), RoundingMode::Floor);
+    let nanos = (timestamp - Number::from(secs.clone())) * Number::from(NANOS_PER_SECOND);
+    let nanos = u32::try_from(&nanos).map_err(|_| {
+        format!(
+            "invalid timestamp {}: the precision is limited to nanoseconds",
+            timestamp.to_sci()
+        )
+    })?;
+
+    let datetime = i64::try_from(&secs)
+        .ok()
+        .and_then(|secs| DateTime::<Utc>::from_timestamp(secs, nanos))
+        .ok_or_else(|| {
+            format!(
+                "invalid timestamp {}: out of the supported range",
+                timestamp.to_sci()
+            )
+        })?;
+
+    Ok(datetime
+        .with_timezone(&offset)
+        .to_rfc3339_opts(SecondsFormat::AutoSi, true))
+}
+
+/// Format an RFC 3339 datetime according to a `strftime`-like format string, as documented in
+/// [chrono::format::strftime].
+pub fn format(fmt: &str, s: &str) -> Result<String, String> {
+    let datetime = parse(s)?;
+    let items = StrftimeItems::new(fmt);
+
+    if items.clone().any(|item| matches!(item, Item::Error)) {
+        return Err(format!("invalid format string `{fmt}`"));
+    }
+
+    Ok(datetime.format_with_items(items).to_string())
+}
+
+/// Parse an ISO 8601 duration, such as `P1DT2H30M` or `PT0.5S`, and return its length in
+/// seconds.
+///
+/// Only units with a fixed length are supported: weeks (`W`), days (`D`), hours (`H`), minutes
+/// (`M` after `T`) and seconds (`S`). Days are considered to last exactly 24 hours. Years and
+/// months are rejected, as their length depends on the date they are added to. Only the seconds
+/// may have a fractional part. A leading `-` denotes a negative duration.
+pub fn parse_duration(s: &str) -> Result<Number, String> {
+    let invalid = |reason: &str| format!("invalid ISO 8601 duration `{s}`: {reason}");
+
+    let (negative, rest) = match s.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, s),
+    };
+
+    let rest = rest
+        .strip_prefix('P')
+        .ok_or_else(|| invalid("expected a leading `P`"))?;
+
+    let (date_part, time_part) = match rest.split_once('T') {
+        Some((_, "")) => return Err(invalid("expected at least one component after `T`")),
+        Some((date, time)) => (date, Some(time)),
+        None => (rest, None),
+    };
+
+    let mut total = Number::ZERO;
+    let mut has_components = false;
+
+    let date_units: &[(char, i64)] = &[('W', SECONDS_PER_WEEK), ('D', SECONDS_PER_DAY)];
+    let time_units: &[(char, i64)] =
+        &[('H', SECONDS_PER_HOUR), ('M', SECONDS_PER_MINUTE), ('S', 1)];
+
+    for (part, is_date, units) in [
+        (date_part, true, date_units),
+        (time_part.unwrap_or(""), false, time_units),
+    ] {
+        let mut remaining = part;
+        // The index of the next allowed unit, which enforces the order of the components.
+        let mut next_unit = 0;
+
+        while !remaining.is_empty() {
+            let end = remaining
+                .find(|c: char| !c.is_ascii_digit() && c != '.')
+                .ok_or_else(|| invalid("missing unit designator"))?;
+            let (value, tail) = remaining.split_at(end);
+            let designator = tail.chars().next().unwrap();
+
+            if designator == 'Y' || (designator == 'M' && is_date) {
+                return Err(invalid(
+                    "years and months aren't supported, as their length isn't fixed",
+                ));
+            }
+
+            let unit_idx = units[next_unit..]
+                .iter()
+                .position(|(unit, _)| *unit == designator)
+                .map(|idx| idx + next_unit)
+                .ok_or_else(|| invalid(&format!("unexpected designator `{designator}`")))?;
+
+            let is_decimal = value.contains('.');
+            let well_formed = value
+                .split_once('.')
+                .map_or(!value.is_empty(), |(int, frac)| {
+                    !int.is_empty() && !frac.is_empty() && !frac.contains('.')
+                });
+
+            if !well_formed {
+                return Err(invalid(&format!("invalid number `{value}`")));
+            }
+
+            if is_decimal && designator != 'S' {
+                return Err(invalid("only seconds can have a fractional part"));
+            }
+
+            let value = parse_number(value).map_err(|_| invalid("invalid number"))?;
+            total = total + value * Number::from(units[unit_idx].1);
+            has_components = true;
+            next_unit = unit_idx + 1;
+            remaining = &tail[designator.len_utf8()..];
+        }
+    }
+
+    if !has_components {
+        return Err(invalid("expected at least one component"));
+    }
+
+    Ok(if negative { -total } else { total })
+}
+
+/// Render a number of seconds as an ISO 8601 duration, using days, hours, minutes and seconds.
+/// This is the inverse of [parse_duration] up to normalization: for example, `90` is rendered as
+/// `PT1M30S`.
+pub fn format_duration(seconds: &Number) -> String {
+    let sign = if *seconds < Number::ZERO { "-" } else { "" };
+    let seconds = if sign.is_empty() {
+        seconds.clone()
+    } else {
+        -seconds.clone()
+    };
+
+    let whole = Integer::rounding_from(seconds.clone(), RoundingMode::Floor);
+    let fraction = seconds - Number::from(whole.clone());
+
+    let days = &whole / Integer::from(SECONDS_PER_DAY);
+    let rem = &whole % Integer::from(SECONDS_PER_DAY);
+    let hours = &rem / Integer::from(SECONDS_PER_HOUR);
+    let rem = &rem % Integer::from(SECONDS_PER_HOUR);
+    let minutes = &rem / Integer::from(SECONDS_PER_MINUTE);
+    let secs = Number::from(rem % Integer::from(SECONDS_PER_MINUTE)) + fraction;
+
+    let zero = Integer::from(0);
+    let mut result = format!("{sign}P");
+
+    if days != zero {
+        result.push_str(&format!("{days}D"));
+    }
+
+    if hours != zero || minutes != zero || secs != Number::ZERO || days == zero {
+        result.push('T');
+
+        if hours != zero {
+            result.push_str(&format!("{hours}H"));
+        }
+
+        if minutes != zero {
+            result.push_str(&format!("{minutes}M"));
+        }
+
+        if secs != Number::ZERO || (hours == zero && minutes == zero) {
+            result.push_str(&format!("{}S", format_seconds(&secs)));
+        }
+    }
+
+    result
+}
+
+/// Render a non-negative number of seconds in decimal notation. Numbers with an infinite decimal
+/// expansion are truncated to the nanosecond.
+fn format_seconds(secs: &Number) -> String {
+    let nanos = Integer::rounding_from(secs * Number::from(NANOS_PER_SECOND), RoundingMode::Down);
+    let whole = &nanos / Integer::from(NANOS_PER_SECOND);
+    let frac = (&nanos % Integer::from(NANOS_PER_SECOND)).to_string();
+
+    if frac == "0" {
+        whole.to_string()
+    } else {
+        let frac = format!("{frac:0>9}");
+        format!("{whole}.{}", frac.trim_end_matches('0'))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_components() {
+        let c = components("2023-06-01T12:30:15.25+02:00").unwrap();
+
+        assert_eq!(c.timestamp, Number::from_signeds(6_742_461_661i64, 4));
+        assert_eq!(c.offset, 7200);
+        assert_eq!((c.year, c.month, c.day), (2023, 6, 1));
+        assert_eq!((c.hour, c.minute, c.second), (12, 30, 15));
+        assert_eq!(c.nanosecond, 250_000_000);
+        assert_eq!(c.weekday, 4);
+        assert_eq!(c.day_of_year, 152);
+
+        assert!(components("2023-02-30T00:00:00Z").is_err());
+        assert!(components("2023-06-01").is_err());
+    }
+
+    #[test]
+    fn timestamp_round_trip() {
+        let c = components("2023-06-01T12:30:15.25+02:00").unwrap();
+        assert_eq!(
+            from_timestamp(&c.timestamp, &Number::from(c.offset)).unwrap(),
+            "2023-06-01T12:30:15.250+02:00"
+        );
+
+        assert_eq!(
+            from_timestamp(&Number::from(0), &Number::from(0)).unwrap(),
+            "1970-01-01T00:00:00Z"
+        );
+        assert_eq!(
+            from_timestamp(&Number::from(-1), &Number::from(0)).unwrap(),
+            "1969-12-31T23:59:59Z"
+        );
+        assert!(from_timestamp(&Number::from_signeds(1, 3), &Number::from(0)).is_err());
+        assert!(from_timestamp(&Number::from(0), &Number::from(86400)).is_err());
+    }
+
+    #[test]
+    fn format_datetime() {
+        assert_eq!(
+            format("%Y/%m/%d %H:%M", "2023-06-01T12:30:00Z").unwrap(),
+            "2023/06/01 12:30"
+        );
+        assert!(format("%Q", "2023-06-01T12:30:00Z").is_err());
+    }
+
+    #[test]
+    fn durations() {
+        let parse = |s| parse_duration(s).unwrap();
+
+        assert_eq!(parse("PT1H30M"), Number::from(5400));
+        assert_eq!(parse("P1W2D"), Number::from(9 * SECONDS_PER_DAY));
+        assert_eq!(parse("P1DT0.5S"), Number::from_signeds(172_801, 2));
+        assert_eq!(parse("-PT90S"), Number::from(-90));
+        assert_eq!(parse("PT0S"), Number::ZERO);
+
+        for invalid in [
+            "", "P", "PT", "1D", "P1Y", "P1M", "PT1.5M", "PT1S1M", "P1DT", "PT.5S",
+        ] {
+            assert!(parse_duration(invalid).is_err(), "{invalid}");
+        }
+
+        assert_eq!(format_duration(&Number::from(5400)), "PT1H30M");
+        assert_eq!(format_duration(&Number::from(90061)), "P1DT1H1M1S");
+        assert_eq!(format_duration(&Number::from(SECONDS_PER_DAY)), "P1D");
+        assert_eq!(format_duration(&Number::from(0)), "PT0S");
+        assert_eq!(format_duration(&Number::from_signeds(-3, 2)), "-PT1.5S");
+    }
+}
@@ -134,6 +134,38 @@ impl<K: Hash + Eq, V: PartialEq> Environment<K, V> {
     }
 }
 
+impl<K: Hash + Eq + Clone, V: PartialEq + Clone> Environment<K, V> {
+    /// Merges the previous layers into the current one, as long as they are not much larger than
+    /// the current layer.
+    ///
+    /// Environments which are repeatedly cloned and extended with a few elements, as when
+    /// building an array by successive concatenations, end up with one layer per extension,
+    /// making lookups linear in the number of extensions. Compacting after each extension keeps
+    /// the size of the layers growing geometrically, and thus their number logarithmic, while
+    /// each element is only copied a logarithmic number of times overall.
+    ///
+    /// This does nothing if the current layer has already been cloned.
+    pub fn compact(&mut self) {
+        while !self.was_cloned() {
+            let Some(prev) = self.previous.borrow().clone() else {
+                break;
+            };
+
+            let current = Rc::get_mut(&mut self.current).unwrap();
+
+            if prev.current.len() > 2 * current.len() {
+                break;
+            }
+
+            for (k, v) in prev.current.iter() {
+                current.entry(k.clone()).or_insert_with(|| v.clone());
+            }
+
+            self.previous.replace(prev.previous.borrow().clone());
+        }
+    }
+}
+
 impl<K: Hash + Eq, V: PartialEq> FromIterator<(K, V)> for Environment<K, V> {
     fn from_iter<T: IntoIterator<Item = (K, V)>>(iter: T) -> Self {
         Self {
@@ -296,6 +328,27 @@ mod tests {
         assert_eq!(env5.depth(), 2);
     }
 
+    #[test]
+    fn test_compact() {
+        let mut env = Environment::<u32, u32>::new();
+
+        for i in 0..1024 {
+            let base = env.clone();
+            env = base.clone();
+            env.insert(i, i);
+            env.compact();
+        }
+
+        assert!(env.depth() <= 11);
+        assert!((0..1024).all(|i| env.get(&i) == Some(&i)));
+
+        let shadowed = env.clone();
+        env.insert(0, 42);
+        env.compact();
+        assert_eq!(env.get(&0), Some(&42));
+        assert_eq!(shadowed.get(&0), Some(&0));
+    }
+
     #[test]
     fn test_iter_layer() {
         let mut env_base = Environment::<u8, char>::new();
@@ -12,7 +12,7 @@ use lalrpop_util::ErrorRecovery;
 use malachite::num::conversion::traits::ToSci;
 
 use crate::{
-    eval::callstack::CallStack,
+    eval::{callstack::CallStack, limits::Limit},
     identifier::LocIdent,
     label::{
         self,
@@ -129,6 +129,12 @@ pub enum EvalError {
         String,  /* error message */
         TermPos, /* position of the call to deserialize */
     ),
+    /// A decoding error occurred during a call to a builtin such as `base64_decode`.
+    DecodingError(
+        String,  /* encoding */
+        String,  /* error message */
+        TermPos, /* position of the call to the decoding primop */
+    ),
     /// A polymorphic record contract was broken somewhere.
     IllegalPolymorphicTailAccess {
         action: IllegalPolymorphicTailAction,
@@ -147,6 +153,15 @@ pub enum EvalError {
         /// Evaluated expression
         value: RichTerm,
     },
+    /// The evaluation exceeded one of the limits set on the virtual machine.
+    ResourceLimitExceeded {
+        /// The limit which has been exceeded.
+        limit: Limit,
+        /// The callstack when the limit was exceeded.
+        call_stack: CallStack,
+        /// The position of the term being evaluated when the limit was exceeded.
+        pos: TermPos,
+    },
     /// An unexpected internal error.
     InternalError(String, TermPos),
     /// Errors occurring rarely enough to not deserve a dedicated variant.
@@ -513,6 +528,13 @@ pub enum ImportError {
         /* error */ ParseErrors,
         /* import position */ TermPos,
     ),
+    /// The import is not allowed by the import policy of the cache. See
+    /// [crate::cache::ImportPolicy].
+    Forbidden(
+        /* imported file */ String,
+        /* reason */ String,
+        /* import position */ TermPos,
+    ),
 }
 
 /// An error occurred during serialization.
@@ -1146,6 +1168,33 @@ impl IntoDiagnostics<FileId> for EvalError {
                     .with_message("infinite recursion")
                     .with_labels(labels)]
             }
+            EvalError::ResourceLimitExceeded {
+                limit,
+                call_stack,
+                pos,
+            } => {
+                let labels = pos
+                    .as_opt_ref()
+                    .map(
+                        |span| vec![primary(span).with_message("while evaluating this expression")],
+                    )
+                    .unwrap_or_default();
+
+                let mut diagnostics = vec![Diagnostic::error()
+                    .with_message(format!("evaluation exceeded {limit}"))
+                    .with_labels(labels)
+                    .with_notes(vec![String::from(
+                        "The limits on the resources used by the evaluation are set by the \
+                        embedding application or on the command line.",
+                    )])];
+
+                if let Some(stdlib_ids) = stdlib_ids {
+                    use blame_error::ExtendWithCallStack;
+                    diagnostics.extend_with_call_stack(stdlib_ids, &call_stack);
+                }
+
+                diagnostics
+            }
             EvalError::Other(msg, span_opt) => {
                 let labels = span_opt
                     .as_opt_ref()
@@ -1176,6 +1225,16 @@ impl IntoDiagnostics<FileId> for EvalError {
                     .with_message(format!("{format} parse error: {msg}"))
                     .with_labels(labels)]
             }
+            EvalError::DecodingError(encoding, msg, span_opt) => {
+                let labels = span_opt
+                    .as_opt_ref()
+                    .map(|span| vec![primary(span).with_message("here")])
+                    .unwrap_or_default();
+
+                vec![Diagnostic::error()
+                    .with_message(format!("{encoding} decoding error: {msg}"))
+                    .with_labels(labels)]
+            }
             EvalError::EqError { eq_pos, term: t } => {
                 let label = format!(
                     "an argument has type {}, which cannot be compared for equality",
@@ -1393,6 +1452,13 @@ mod blame_error {
         labels
     }
 
+    /// Converts the related locations attached to a contract diagnostic to secondary labels.
+    fn related_labels(related: Vec<label::RelatedSpan>) -> impl Iterator<Item = Label<FileId>> {
+        related
+            .into_iter()
+            .map(|related| secondary(&related.span).with_message(related.message))
+    }
+
     pub trait ExtendWithCallStack {
         fn extend_with_call_stack(&mut self, stdlib_ids: &[FileId], call_stack: &CallStack);
     }
@@ -1553,12 +1619,14 @@ is None but last_arrow_elem is Some"
             write!(&mut msg, ": {}", &super::escape(contract_msg)).unwrap();
         }
 
-        let contract_notes = head_contract_diagnostic
-            .map(|diag| diag.notes)
+        let (contract_notes, related) = head_contract_diagnostic
+            .map(|diag| (diag.notes, diag.related))
             .unwrap_or_default();
         let path_label = report_ty_path(files, &label);
 
-        let labels = build_diagnostic_labels(evaluated_arg, &label, path_label, files, stdlib_ids);
+        let mut labels =
+            build_diagnostic_labels(evaluated_arg, &label, path_label, files, stdlib_ids);
+        labels.extend(related_labels(related));
 
         // If there are notes in the head contract diagnostic, we build the first
         // diagnostic using them and will put potential generated notes on higher-order
@@ -1585,6 +1653,7 @@ is None but last_arrow_elem is Some"
             diagnostics.push(
                 Diagnostic::note()
                     .with_message(msg)
+                    .with_labels(related_labels(ctr_diag.related).collect())
                     .with_notes(ctr_diag.notes),
             );
         }
@@ -2113,6 +2182,19 @@ impl IntoDiagnostics<FileId> for ImportError {
 
                 diagnostic
             }
+            ImportError::Forbidden(path, reason, span_opt) => {
+                let labels = span_opt
+                    .as_opt_ref()
+                    .map(|span| vec![primary(span).with_message("forbidden import")])
+                    .unwrap_or_default();
+
+                vec![Diagnostic::error()
+                    .with_message(format!("import of {path} is not allowed: {reason}"))
+                    .with_labels(labels)
+                    .with_notes(vec![String::from(
+                        "The import policy in effect restricts which files can be imported.",
+                    )])]
+            }
         }
     }
 }
@@ -1,373 +1,449 @@
-use std::collections::{HashMap, HashSet};
-
-//! A [Cache] implementation with incremental computation features.
-use super::{BlackholedError, Cache, CacheIndex, Closure, Environment, IdentKind};
+//! A [Cache] implementation supporting incremental re-evaluation.
+//!
+//! [IncCache] uses the same thunks as [CBNCache](super::lazy::CBNCache), but additionally records
+//! a dependency graph during evaluation: whenever the content of a thunk is accessed while another
+//! thunk is being evaluated, the latter is registered as a dependent of the former. The graph is
+//! dynamic: it only holds the dependencies which have actually been exercised, and is rebuilt as
+//! thunks are evaluated again.
+//!
+//! When the content of a thunk is replaced with [Cache::replace], the thunks depending on it,
+//! directly or transitively, are restored to the unevaluated closure they held when they were
+//! first entered. All the other thunks keep their value, so that evaluating the same expression
+//! again only recomputes what depends on the change. This is what
+//! [`Program::eval_incremental`](crate::program::Program::eval_incremental) relies on to cheaply
+//! re-evaluate a program after overriding one of its fields.
+//!
+//! Dependencies are over-approximated: a thunk which doesn't depend on a replaced thunk anymore
+//! since its last evaluation might still be invalidated. Tracking them has a cost in time, and in
+//! memory as well, since the original closure of each evaluated thunk depending on other thunks is
+//! retained. The nodes of the thunks which have been dropped are collected at the beginning of each
+//! evaluation round (see [Cache::start_revision]).
+use super::{
+    lazy::{Thunk, ThunkId, ThunkState, ThunkUpdateFrame, WeakThunk},
+    BlackholedError, Cache, CacheIndex, Closure, Environment, IdentKind,
+};
 use crate::{
     identifier::Ident,
-    term::{record::FieldDeps, BindingType, RichTerm, Term},
+    term::{record::FieldDeps, BindingType, RichTerm},
+};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
 };
 
-/// The evaluation state of an [IncNode].
-#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
-pub enum IncNodeState {
-    #[default]
-    Suspended,
-    Blackholed,
-    Evaluated,
-}
-
-#[derive(Debug, Copy, Clone, Eq, PartialEq)]
-pub struct DependencyLink {
-    id: Ident,
-    idx: CacheIndex,
-}
-
-/// A node in the dependent computation graph stored in [IncCache].
+/// A node of the dependency graph, corresponding to a thunk.
 #[derive(Debug, Clone)]
-pub struct IncNode {
-    // The original closure.
-    orig: Closure,
-    // The cached value of the closure.
-    cached: Option<Closure>,
-    kind: IdentKind,
-    bty: BindingType,
-    // The state of the node.
-    state: IncNodeState,
-    // Forward links to dependencies.
-    fwdlinks: Vec<DependencyLink>,
-    // Backlinks to nodes depending on this node.
-    backlinks: Vec<DependencyLink>,
+struct IncNode {
+    thunk: WeakThunk,
+    /// The unevaluated closure of the thunk, recorded the first time it is entered. `None` if it
+    /// has never been entered, or if its evaluation didn't access any other thunk, in which cases
+    /// it can't be invalidated.
+    orig: Option<Closure>,
+    /// The thunks whose evaluation accessed this thunk. Duplicates and stale dependents are
+    /// harmless, as invalidating is idempotent.
+    dependents: Vec<ThunkId>,
 }
 
-impl IncNode {
-    fn new(clos: Closure, kind: IdentKind, bty: BindingType) -> Self {
-        IncNode {
-            orig: clos,
-            cached: None,
-            kind,
-            bty,
-            state: IncNodeState::default(),
-            fwdlinks: Vec::new(),
-            backlinks: Vec::new(),
-        }
-    }
+/// The dependency graph between thunks.
+#[derive(Debug, Clone, Default)]
+struct DepGraph {
+    nodes: HashMap<ThunkId, IncNode>,
+    /// The thunks currently being evaluated, the innermost one last, together with whether their
+    /// evaluation accessed another thunk so far.
+    active: Vec<(ThunkId, bool)>,
 }
 
-/// A [Cache] implementation supporting incremental computation features.
-/// Stores a dependent computation graph to keep track of what needs to be recomputed.
-#[derive(Debug, Clone)]
-pub struct IncCache {
-    store: Vec<IncNode>,
-    next: CacheIndex,
-}
+impl DepGraph {
+    fn node(&mut self, thunk: &Thunk) -> &mut IncNode {
+        self.nodes.entry(thunk.id()).or_insert_with(|| IncNode {
+            thunk: thunk.downgrade(),
+            orig: None,
+            dependents: Vec::new(),
+        })
+    }
 
-impl IncCache {
-    fn add_node(&mut self, node: IncNode) -> CacheIndex {
-        let idx = self.next;
-        self.store.insert(idx, node);
-        self.next += 1;
+    /// Record that the innermost thunk being evaluated, if any, accessed `thunk`.
+    fn record_access(&mut self, thunk: &Thunk) {
+        let Some((reader, accessed)) = self.active.last_mut() else {
+            return;
+        };
+        let reader = *reader;
 
-        idx
-    }
+        if reader != thunk.id() {
+            *accessed = true;
+            let dependents = &mut self.node(thunk).dependents;
 
-    fn revnode_as_explicit_fun<'a, I>(node: &IncNode, args: I) -> IncNode
-    where
-        I: DoubleEndedIterator<Item = &'a Ident>,
-    {
-        match &node.bty {
-            BindingType::Revertible(deps) if !deps.is_empty() => {
-                let Closure { body, env } = node.orig.clone();
-
-                // Build a list of the arguments that the function will need in the same order as
-                // the original iterator. If the identifiers inside `args` are `a`, `b` and `c`, in
-                // that order, we want to build `fun a => (fun b => (fun c => body))`. We thus need a
-                // reverse fold.
-                let as_function =
-                    args.rfold(body, |built, id| RichTerm::from(Term::Fun(*id, built)));
-
-                IncNode::new(
-                    Closure {
-                        body: as_function,
-                        env,
-                    },
-                    node.kind,
-                    node.bty.clone(),
-                )
+            if dependents.last() != Some(&reader) {
+                dependents.push(reader);
             }
-            _ => node.clone(),
         }
     }
 
-    fn update_backlinks(&mut self, idx: CacheIndex) {
-        let node = self.store.get(idx).unwrap().clone();
-        for i in node.fwdlinks {
-            let n = self.store.get_mut(i.idx).unwrap();
-            n.backlinks.push(DependencyLink { id: i.id, idx: idx });
-        }
-    }
+    /// Record that `thunk` is being evaluated.
+    fn enter(&mut self, thunk: &Thunk) {
+        let node = self.node(thunk);
 
-    fn propagate_dirty(&mut self, idx: CacheIndex) {
-        let mut node = self.store.get_mut(idx).unwrap();
-        node.cached = None;
-        node.state = IncNodeState::Suspended;
-
-        let mut visited = HashSet::new();
-        let mut stack = node.backlinks.clone();
-
-        visited.insert(idx);
-
-        while !stack.is_empty() {
-            let i = stack.pop().unwrap();
-            visited.insert(i.idx);
-            let mut current_node = self.store.get_mut(i.idx).unwrap();
-            current_node.cached = None;
-            current_node.state = IncNodeState::Suspended;
-            stack.extend(
-                current_node
-                    .backlinks
-                    .iter()
-                    .filter(|x| !visited.contains(&x.idx)),
-            )
+        if node.orig.is_none() {
+            node.orig = Some(thunk.get_owned());
         }
-    }
-
-    /* Do we need this when we can revert in place?
 
-    fn propagate_revert(&mut self, id: Ident, idx: CacheIndex) -> HashMap<Ident, CacheIndex> {
-        let mut nodes_reverted = HashMap::new();
-
-        let mut visited = HashSet::new();
-        let mut stack = vec![idx];
+        self.active.push((thunk.id(), false));
+    }
 
-        while !stack.is_empty() {
-            let i = stack.pop().unwrap();
-            visited.insert(i);
+    /// Record that the evaluation of the thunk `id` is over. The thunks entered after it, whose
+    /// evaluation has been aborted, are left as well.
+    ///
+    /// If the evaluation of the thunk didn't access any other thunk, its value can't be
+    /// invalidated, and its original closure is dropped.
+    fn leave(&mut self, id: ThunkId) {
+        let Some(pos) = self.active.iter().rposition(|(active, _)| *active == id) else {
+            return;
+        };
 
-            let idx_reverted = self.revert(&idx);
-            //FIXME: use the actual node's id
-            let node_id = Ident::from("TODO!");
-            nodes_reverted.insert(node_id, idx_reverted);
+        let (_, accessed) = self.active[pos];
+        self.active.truncate(pos);
 
-            let current_node = self.store.get(i).unwrap();
+        if !accessed {
+            if let Some(node) = self.nodes.get_mut(&id) {
+                node.orig = None;
 
-            stack.extend(
-                current_node
-                    .backlinks
-                    .iter()
-                    .map(|x| x.idx)
-                    .filter(|x| !visited.contains(x)),
-            )
+                if node.dependents.is_empty() {
+                    self.nodes.remove(&id);
+                }
+            }
         }
+    }
 
-        nodes_reverted
-    } */
+    /// Restore the thunks depending on the thunk `id`, transitively, to their unevaluated
+    /// closure.
+    fn invalidate_dependents(&mut self, id: ThunkId) {
+        let mut visited = HashSet::from([id]);
+        let mut todo = vec![id];
 
-    fn smart_clone(&mut self, v: Vec<CacheIndex>) -> HashMap<CacheIndex, CacheIndex> {
-        let mut new_indices = HashMap::new();
+        while let Some(id) = todo.pop() {
+            let Some(node) = self.nodes.get_mut(&id) else {
+                continue;
+            };
 
-        for i in v.iter() {
-            let current_node = self.store.get(*i).unwrap().clone();
-            new_indices.insert(*i, self.add_node(current_node));
-        }
-
-        for i in new_indices.values() {
-            let current_node = self.store.get_mut(*i).unwrap();
+            for dependent in std::mem::take(&mut node.dependents) {
+                if !visited.insert(dependent) {
+                    continue;
+                }
 
-            for dep in current_node.backlinks.iter_mut() {
-                dep.idx = *new_indices.get(i).unwrap();
-            }
+                if let Some(IncNode {
+                    thunk,
+                    orig: Some(orig),
+                    ..
+                }) = self.nodes.get(&dependent)
+                {
+                    if let Some(mut thunk) = thunk.upgrade() {
+                        thunk.reset(orig.clone());
+                    }
+                }
 
-            for dep in current_node.fwdlinks.iter_mut() {
-                dep.idx = *new_indices.get(i).unwrap();
+                todo.push(dependent);
             }
         }
+    }
 
-        new_indices
+    /// Remove the nodes of the thunks which have been dropped.
+    fn collect(&mut self) {
+        self.nodes.retain(|_, node| node.thunk.is_alive());
     }
 }
 
-impl Cache for IncCache {
-    type UpdateIndex = CacheIndex;
+/// A [Cache] implementation tracking the dependencies between thunks, such that replacing one of
+/// them only invalidates the thunks depending on it.
+#[derive(Debug, Clone, Default)]
+pub struct IncCache {
+    // Accesses are also recorded by [Cache::get], which only takes `&self`.
+    graph: RefCell<DepGraph>,
+}
 
-    fn new() -> Self {
-        IncCache {
-            store: Vec::new(),
-            next: 0,
-        }
+impl IncCache {
+    /// Record that the thunk being evaluated, if any, accessed the content of `idx`.
+    pub(super) fn record_access(&self, idx: &CacheIndex) {
+        self.graph.borrow_mut().record_access(idx);
     }
 
-    fn get(&self, idx: CacheIndex) -> Closure {
-        let node = self.store.get(idx).unwrap();
+    /// Return the number of thunks currently tracked.
+    pub fn tracked(&self) -> usize {
+        self.graph.borrow().nodes.len()
+    }
+}
 
-        node.cached.clone().unwrap_or(node.orig.clone())
+impl Cache for IncCache {
+    type UpdateIndex = ThunkUpdateFrame;
+
+    fn get(&self, idx: CacheIndex) -> Closure {
+        self.record_access(&idx);
+        idx.get_owned()
     }
 
     fn get_update_index(
         &mut self,
         idx: &mut CacheIndex,
     ) -> Result<Option<Self::UpdateIndex>, BlackholedError> {
-        let node = self.store.get_mut(*idx).unwrap();
+        let graph = self.graph.get_mut();
+        graph.record_access(idx);
 
-        if node.state == IncNodeState::Blackholed {
-            Err(BlackholedError)
-        } else if node.state == IncNodeState::Evaluated {
+        if idx.state() == ThunkState::Evaluated {
             Ok(None)
+        } else if idx.should_update() {
+            let frame = idx.mk_update_frame()?;
+            graph.enter(idx);
+            Ok(Some(frame))
         } else {
-            node.state = IncNodeState::Blackholed;
-            Ok(Some(*idx))
+            idx.set_evaluated();
+            Ok(None)
         }
     }
 
     fn add(&mut self, clos: Closure, kind: IdentKind, bty: BindingType) -> CacheIndex {
-        let node = IncNode::new(clos, kind, bty);
+        match bty {
+            BindingType::Normal => Thunk::new(clos, kind),
+            BindingType::Revertible(deps) => Thunk::new_rev(clos, kind, deps),
+        }
+    }
 
-        self.add_node(node)
+    fn patch<F: FnOnce(&mut Closure)>(&mut self, mut idx: CacheIndex, f: F) {
+        f(&mut idx.borrow_mut());
     }
 
-    fn update(&mut self, clos: Closure, idx: Self::UpdateIndex) {
-        let node = self.store.get_mut(idx).unwrap();
+    fn get_then<T, F: FnOnce(&Closure) -> T>(&self, idx: CacheIndex, f: F) -> T {
+        self.record_access(&idx);
+        f(&idx.borrow())
+    }
 
-        node.cached = Some(clos);
-        node.state = IncNodeState::Evaluated;
+    fn update(&mut self, clos: Closure, uidx: Self::UpdateIndex) {
+        self.graph.get_mut().leave(uidx.id());
+        uidx.update(clos);
     }
 
-    fn revert(&mut self, idx: &CacheIndex) -> CacheIndex {
-        let node = self.store.get(*idx).unwrap();
+    fn new() -> Self {
+        Self::default()
+    }
 
-        let new_node = match node.bty.clone() {
-            BindingType::Normal => node.clone(),
-            BindingType::Revertible(_) => {
-                IncNode::new(node.orig.clone(), node.kind, node.bty.clone())
-            }
-        };
+    fn start_revision(&mut self) {
+        let graph = self.graph.get_mut();
+        graph.active.clear();
+        graph.collect();
+    }
 
-        self.add_node(new_node)
+    fn reset_index_state(&mut self, idx: &mut Self::UpdateIndex) {
+        self.graph.get_mut().leave(idx.id());
+        idx.reset_state();
     }
 
-    fn ident_kind(&self, idx: &CacheIndex) -> IdentKind {
-        self.store.get(*idx).unwrap().kind
+    fn map_at_index<F: FnMut(&mut Self, &Closure) -> Closure>(
+        &mut self,
+        idx: &CacheIndex,
+        mut f: F,
+    ) -> CacheIndex {
+        self.record_access(idx);
+        idx.map(|v| f(self, v))
     }
 
-    fn deps(&self, idx: &CacheIndex) -> Option<FieldDeps> {
-        let node = self.store.get(*idx).unwrap();
-        match node.bty.clone() {
-            BindingType::Normal => None,
-            BindingType::Revertible(deps) => Some(deps),
-        }
+    fn build_cached(&mut self, idx: &mut CacheIndex, rec_env: &[(Ident, CacheIndex)]) {
+        idx.build_cached(rec_env)
     }
 
-    fn get_then<T, F: FnOnce(&Closure) -> T>(&self, idx: CacheIndex, f: F) -> T {
-        f(&self.get(idx))
+    fn ident_kind(&self, idx: &CacheIndex) -> IdentKind {
+        idx.ident_kind()
+    }
+
+    fn saturate<I: DoubleEndedIterator<Item = Ident> + Clone>(
+        &mut self,
+        idx: CacheIndex,
+        env: &mut Environment,
+        fields: I,
+    ) -> RichTerm {
+        // Saturating a standard thunk copies its current content.
+        self.record_access(&idx);
+        idx.saturate(env, fields)
     }
 
-    fn patch<F: Fn(&mut Closure)>(&mut self, idx: CacheIndex, f: F) {
-        let node = self.store.get_mut(idx).unwrap();
+    fn deps(&self, idx: &CacheIndex) -> Option<FieldDeps> {
+        Some(idx.deps())
+    }
 
-        f(&mut node.orig);
-        node.cached.as_mut().map(|mut clos| f(&mut clos));
+    fn revert(&mut self, idx: &CacheIndex) -> CacheIndex {
+        idx.revert()
     }
 
     fn make_update_index(
         &mut self,
         idx: &mut CacheIndex,
     ) -> Result<Self::UpdateIndex, BlackholedError> {
-        let node = self.store.get_mut(*idx).unwrap();
+        let frame = idx.mk_update_frame()?;
+        self.graph.get_mut().enter(idx);
+        Ok(frame)
+    }
 
-        if node.state == IncNodeState::Blackholed {
-            return Err(BlackholedError);
-        } else {
-            node.state = IncNodeState::Blackholed;
+    fn replace(&mut self, idx: &CacheIndex, clos: Closure) -> bool {
+        let graph = self.graph.get_mut();
+        graph.collect();
 
-            Ok(*idx)
-        }
+        let mut thunk = idx.clone();
+        thunk.reset(clos.clone());
+        graph.node(&thunk).orig = Some(clos);
+        graph.invalidate_dependents(thunk.id());
+
+        true
     }
+}
 
-    fn reset_index_state(&mut self, idx: &mut Self::UpdateIndex) {
-        let node = self.store.get_mut(*idx).unwrap();
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        identifier::LocIdent,
+        term::{make as mk_term, BinaryOp, Term},
+    };
 
-        node.state = IncNodeState::default();
+    fn plus(t1: RichTerm, t2: RichTerm) -> RichTerm {
+        mk_term::op2(BinaryOp::Plus(), t1, t2)
     }
 
-    fn map_at_index<F: FnMut(&mut Self, &Closure) -> Closure>(
-        &mut self,
-        idx: &CacheIndex,
-        mut f: F,
+    fn add_thunk(
+        cache: &mut IncCache,
+        body: RichTerm,
+        bindings: &[(LocIdent, &CacheIndex)],
     ) -> CacheIndex {
-        let node = self.store.get(*idx).unwrap().clone();
-
-        let new_node = IncNode {
-            orig: f(self, &node.orig),
-            cached: node.cached.clone().map(|clos| f(self, &clos)),
-            kind: node.kind,
-            bty: node.bty.clone(),
-            state: node.state,
-            fwdlinks: node.fwdlinks.clone(),
-            backlinks: node.backlinks.clone(),
-        };
-
-        // TODO: Should this push the dependencies?
-
-        self.add_node(new_node)
+        let mut env = Environment::new();
+        for (id, idx) in bindings {
+            env.insert(id.ident(), (*idx).clone());
+        }
+        cache.add(Closure { body, env }, IdentKind::Let, BindingType::Normal)
     }
 
-    fn build_cached(&mut self, idx: &mut CacheIndex, rec_env: &[(Ident, CacheIndex)]) {
-        let node = self.store.get_mut(*idx).unwrap();
+    fn value(n: i64) -> Closure {
+        Closure::atomic_closure(mk_term::integer(n))
+    }
 
-        if node.cached.is_some() {
-            return ();
-        }
+    #[test]
+    fn replace_invalidates_dependents_only() {
+        let mut cache = IncCache::new();
+        let (x, y) = (LocIdent::fresh(), LocIdent::fresh());
+
+        let input = add_thunk(
+            &mut cache,
+            plus(mk_term::integer(1), mk_term::integer(1)),
+            &[],
+        );
+        let dependent = add_thunk(
+            &mut cache,
+            plus(mk_term::var(x), mk_term::integer(1)),
+            &[(x, &input)],
+        );
+        let transitive = add_thunk(
+            &mut cache,
+            plus(mk_term::var(y), mk_term::integer(1)),
+            &[(y, &dependent)],
+        );
+        let reader = add_thunk(
+            &mut cache,
+            plus(mk_term::integer(2), mk_term::integer(2)),
+            &[],
+        );
+
+        // Simulate the evaluation of `transitive`, which forces `dependent`, which forces `input`.
+        let transitive_frame = cache
+            .get_update_index(&mut transitive.clone())
+            .unwrap()
+            .unwrap();
+        let dependent_frame = cache
+            .get_update_index(&mut dependent.clone())
+            .unwrap()
+            .unwrap();
+        let input_frame = cache.get_update_index(&mut input.clone()).unwrap().unwrap();
+        cache.update(value(2), input_frame);
+        cache.update(value(3), dependent_frame);
+        cache.update(value(4), transitive_frame);
+
+        let reader_frame = cache
+            .get_update_index(&mut reader.clone())
+            .unwrap()
+            .unwrap();
+        // Accessing an evaluated thunk is recorded as well.
+        cache.get(input.clone());
+        cache.update(value(4), reader_frame);
+
+        assert!(cache.replace(&input, value(10)));
+
+        assert_eq!(input.state(), ThunkState::Suspended);
+        assert_eq!(dependent.state(), ThunkState::Suspended);
+        assert_eq!(transitive.state(), ThunkState::Suspended);
+        assert_eq!(reader.state(), ThunkState::Suspended);
+
+        // Invalidated thunks are restored to their original expression.
+        assert_eq!(input.get_owned().body, mk_term::integer(10));
+        assert_eq!(
+            dependent.get_owned().body,
+            plus(mk_term::var(x), mk_term::integer(1))
+        );
+
+        // Thunks which don't depend on the replaced one keep their value.
+        let unrelated = add_thunk(
+            &mut cache,
+            plus(mk_term::integer(3), mk_term::integer(3)),
+            &[],
+        );
+        let unrelated_frame = cache
+            .get_update_index(&mut unrelated.clone())
+            .unwrap()
+            .unwrap();
+        cache.update(value(6), unrelated_frame);
+
+        assert!(cache.replace(&input, value(20)));
+        assert_eq!(unrelated.state(), ThunkState::Evaluated);
+    }
 
-        let mut new_cached = Closure::clone(&node.orig);
-
-        match node.bty {
-            BindingType::Normal => (),
-            BindingType::Revertible(ref deps) => match deps {
-                FieldDeps::Unknown => new_cached.env.extend(rec_env.iter().cloned()),
-                FieldDeps::Known(deps) if deps.is_empty() => (),
-                FieldDeps::Known(deps) => {
-                    let deps = rec_env.iter().filter(|(id, _)| deps.contains(id)).cloned();
-                    node.fwdlinks = deps
-                        .clone()
-                        .map(|(id, idx)| DependencyLink { id, idx })
-                        .collect();
-                    new_cached.env.extend(deps);
-                }
-            },
+    #[test]
+    fn dropped_thunks_are_collected() {
+        let mut cache = IncCache::new();
+        let input = add_thunk(
+            &mut cache,
+            plus(mk_term::integer(1), mk_term::integer(1)),
+            &[],
+        );
+
+        {
+            let dependent = add_thunk(
+                &mut cache,
+                plus(mk_term::integer(2), mk_term::integer(2)),
+                &[],
+            );
+            let frame = cache
+                .get_update_index(&mut dependent.clone())
+                .unwrap()
+                .unwrap();
+            cache.get(input.clone());
+            cache.update(value(4), frame);
         }
 
-        node.cached = Some(new_cached);
-        self.update_backlinks(*idx);
+        assert_eq!(cache.tracked(), 2);
+        cache.start_revision();
+        assert_eq!(cache.tracked(), 1);
+        assert!(cache.replace(&input, value(3)));
+        assert_eq!(cache.tracked(), 1);
     }
 
-    fn saturate<'a, I: DoubleEndedIterator<Item = &'a Ident> + Clone>(
-        &mut self,
-        idx: CacheIndex,
-        env: &mut Environment,
-        fields: I,
-    ) -> RichTerm {
-        let node = self.store.get(idx).unwrap();
-
-        let mut deps_filter: Box<dyn FnMut(&&Ident) -> bool> = match node.bty.clone() {
-            BindingType::Revertible(FieldDeps::Known(deps)) => {
-                Box::new(move |id: &&Ident| deps.contains(id))
-            }
-            BindingType::Revertible(FieldDeps::Unknown) => Box::new(|_: &&Ident| true),
-            BindingType::Normal => Box::new(|_: &&Ident| false),
-        };
-
-        let node_as_function = self.add_node(IncCache::revnode_as_explicit_fun(
-            node,
-            fields.clone().filter(&mut deps_filter),
-        ));
-
-        let fresh_var = Ident::fresh();
-        env.insert(fresh_var, node_as_function);
-
-        let as_function_closurized = RichTerm::from(Term::Var(fresh_var));
-        let args = fields.filter_map(|id| deps_filter(&id).then(|| RichTerm::from(Term::Var(*id))));
-
-        args.fold(as_function_closurized, |partial_app, arg| {
-            RichTerm::from(Term::App(partial_app, arg))
-        })
+    #[test]
+    fn independent_thunks_are_not_retained() {
+        let mut cache = IncCache::new();
+        let thunk = add_thunk(
+            &mut cache,
+            plus(mk_term::integer(1), mk_term::integer(1)),
+            &[],
+        );
+
+        let frame = cache.get_update_index(&mut thunk.clone()).unwrap().unwrap();
+        cache.update(value(2), frame);
+
+        // The value of the thunk can't be invalidated, so neither its original closure nor its
+        // node are retained.
+        assert_eq!(cache.tracked(), 0);
+        assert_eq!(thunk.state(), ThunkState::Evaluated);
     }
 }
@@ -274,6 +274,17 @@ impl ThunkData {
         }
     }
 
+    /// Restore an unevaluated closure and set the state back to suspended, such that the thunk is
+    /// evaluated again on the next access.
+    pub fn reset(&mut self, closure: Closure) {
+        match self.inner {
+            InnerThunkData::Standard(ref mut inner) => *inner = closure,
+            InnerThunkData::Revertible { ref mut cached, .. } => *cached = Some(closure),
+        }
+
+        self.state = ThunkState::Suspended;
+    }
+
     /// Update the cached closure.
     pub fn update(&mut self, new: Closure) {
         match self.inner {
@@ -344,6 +355,11 @@ impl ThunkData {
     }
 }
 
+/// The identifier of a thunk, shared by all the copies of a [Thunk] or of a [WeakThunk] pointing to
+/// the same data. Identifiers are never reused as long as such a copy exists.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ThunkId(usize);
+
 /// A thunk.
 ///
 /// A thunk is a shared suspended computation. It is the primary device for the implementation of
@@ -387,6 +403,25 @@ impl Thunk {
         self.data.borrow().state
     }
 
+    /// Return the identifier of the thunk.
+    pub fn id(&self) -> ThunkId {
+        ThunkId(Rc::as_ptr(&self.data) as usize)
+    }
+
+    /// Create a weak reference to this thunk.
+    pub fn downgrade(&self) -> WeakThunk {
+        WeakThunk {
+            data: Rc::downgrade(&self.data),
+            ident_kind: self.ident_kind,
+        }
+    }
+
+    /// Restore an unevaluated closure and set the state back to suspended. See
+    /// [ThunkData::reset].
+    pub fn reset(&mut self, closure: Closure) {
+        self.data.borrow_mut().reset(closure)
+    }
+
     /// Set the state to evaluated.
     pub fn set_evaluated(&mut self) {
         self.data.borrow_mut().state = ThunkState::Evaluated;
@@ -412,6 +447,18 @@ impl Thunk {
         Ref::map(self.data.borrow(), |data| data.closure())
     }
 
+    /// Immutably borrow the inner closure if it is available. Return `None` if there is an active
+    /// mutable borrow, or if the thunk is revertible and its cached value hasn't been built yet.
+    pub fn try_borrow(&self) -> Option<Ref<'_, Closure>> {
+        let data = self.data.try_borrow().ok()?;
+
+        Ref::filter_map(data, |data| match data.inner {
+            InnerThunkData::Standard(ref closure) => Some(closure),
+            InnerThunkData::Revertible { ref cached, .. } => cached.as_ref(),
+        })
+        .ok()
+    }
+
     /// Mutably borrow the inner closure. Panic if there is any other active borrow.
     pub fn borrow_mut(&mut self) -> RefMut<'_, Closure> {
         RefMut::map(self.data.borrow_mut(), |data| data.closure_mut())
@@ -435,6 +482,12 @@ impl Thunk {
         }
     }
 
+    /// Return `true` if `self` and `other` point to the same shared data, which is the case for
+    /// a thunk and its clones (as well as for standard thunks and their reverted versions).
+    pub fn ptr_eq(&self, other: &Thunk) -> bool {
+        Rc::ptr_eq(&self.data, &other.data)
+    }
+
     /// Create a fresh unevaluated thunk from `self`, reverted to its original state before the
     /// first update. For a standard thunk, the content is unchanged and the state is conserved: in
     /// this case, `revert()` is the same as `clone()`.
@@ -562,6 +615,29 @@ impl Thunk {
         self.data.borrow().deps()
     }
 }
+
+/// A weak reference to a thunk, which doesn't keep the thunk alive.
+#[derive(Clone, Debug)]
+pub struct WeakThunk {
+    data: Weak<RefCell<ThunkData>>,
+    ident_kind: IdentKind,
+}
+
+impl WeakThunk {
+    /// Return the thunk, or `None` if it has been dropped since.
+    pub fn upgrade(&self) -> Option<Thunk> {
+        Weak::upgrade(&self.data).map(|data| Thunk {
+            data,
+            ident_kind: self.ident_kind,
+        })
+    }
+
+    /// Return `true` if the thunk hasn't been dropped.
+    pub fn is_alive(&self) -> bool {
+        self.data.strong_count() > 0
+    }
+}
+
 /// A thunk update frame.
 ///
 /// A thunk update frame is put on the stack whenever a variable is entered, such that once this
@@ -575,6 +651,11 @@ pub struct ThunkUpdateFrame {
 }
 
 impl ThunkUpdateFrame {
+    /// Return the identifier of the thunk to update.
+    pub fn id(&self) -> ThunkId {
+        ThunkId(Weak::as_ptr(&self.data) as usize)
+    }
+
     /// Update the corresponding thunk with a closure. Set the state to `Evaluated`
     ///
     /// # Return
@@ -651,6 +732,8 @@ impl Cache for CBNCache {
         CBNCache {}
     }
 
+    fn start_revision(&mut self) {}
+
     fn reset_index_state(&mut self, idx: &mut Self::UpdateIndex) {
         idx.reset_state();
     }
@@ -694,4 +777,8 @@ impl Cache for CBNCache {
     ) -> Result<Self::UpdateIndex, BlackholedError> {
         idx.mk_update_frame()
     }
+
+    fn replace(&mut self, _idx: &CacheIndex, _clos: Closure) -> bool {
+        false
+    }
 }
@@ -10,16 +10,15 @@ use crate::{
     term::{record::FieldDeps, BindingType, RichTerm},
 };
 
+pub mod incremental;
 pub mod lazy;
-// pub mod incremental;
 
 /// An index to a specific item stored in the cache
 pub type CacheIndex = lazy::Thunk;
-// pub type CacheIndex = usize;
 
-/// The current Cache implementation
-pub type CacheImpl = lazy::CBNCache;
-// pub type CacheImpl = incremental::IncCache;
+/// The current Cache implementation. It defaults to [lazy::CBNCache], but can be switched to
+/// [incremental::IncCache] at runtime.
+pub type CacheImpl = SelectedCache;
 
 /// A black-holed node was accessed, which would lead to infinite recursion.
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
@@ -56,6 +55,10 @@ pub trait Cache: Clone {
     /// Initializes a new [Cache].
     fn new() -> Self;
 
+    /// Signals the beginning of a new evaluation round. Called each time the virtual machine is
+    /// reset.
+    fn start_revision(&mut self);
+
     /// Resets the state of the element at index `idx` to `Suspended`
     fn reset_index_state(&mut self, idx: &mut Self::UpdateIndex);
 
@@ -94,4 +97,135 @@ pub trait Cache: Clone {
         &mut self,
         idx: &mut CacheIndex,
     ) -> Result<Self::UpdateIndex, BlackholedError>;
+
+    /// Replaces the element at index `idx` with the unevaluated closure `clos`, and invalidates
+    /// the elements whose evaluation depended on it, such that they are evaluated again on the
+    /// next access. Returns `false` if the cache doesn't track dependencies, in which case nothing
+    /// is done.
+    fn replace(&mut self, idx: &CacheIndex, clos: Closure) -> bool;
+}
+
+/// A [Cache] whose implementation is selected at runtime.
+///
+/// [SelectedCache::new] selects [lazy::CBNCache]. [incremental::IncCache] is only worth its
+/// overhead when the same program is re-evaluated after changing some of its inputs.
+#[derive(Clone, Debug)]
+pub enum SelectedCache {
+    Lazy(lazy::CBNCache),
+    Incremental(incremental::IncCache),
+}
+
+impl SelectedCache {
+    /// Select [lazy::CBNCache] if `incremental` is false, or [incremental::IncCache] otherwise.
+    pub fn with_incremental(incremental: bool) -> Self {
+        if incremental {
+            SelectedCache::Incremental(incremental::IncCache::new())
+        } else {
+            SelectedCache::Lazy(lazy::CBNCache::new())
+        }
+    }
+}
+
+/// Forward a method call to the selected implementation.
+macro_rules! dispatch {
+    ($self:expr, $cache:ident => $call:expr) => {
+        match $self {
+            SelectedCache::Lazy($cache) => $call,
+            SelectedCache::Incremental($cache) => $call,
+        }
+    };
+}
+
+impl Cache for SelectedCache {
+    // Both implementations use the same thunks, and thus the same update frames.
+    type UpdateIndex = lazy::ThunkUpdateFrame;
+
+    fn get(&self, idx: CacheIndex) -> Closure {
+        dispatch!(self, cache => cache.get(idx))
+    }
+
+    fn get_update_index(
+        &mut self,
+        idx: &mut CacheIndex,
+    ) -> Result<Option<Self::UpdateIndex>, BlackholedError> {
+        dispatch!(self, cache => cache.get_update_index(idx))
+    }
+
+    fn add(&mut self, clos: Closure, kind: IdentKind, bty: BindingType) -> CacheIndex {
+        dispatch!(self, cache => cache.add(clos, kind, bty))
+    }
+
+    fn patch<F: Fn(&mut Closure)>(&mut self, idx: CacheIndex, f: F) {
+        dispatch!(self, cache => cache.patch(idx, f))
+    }
+
+    fn get_then<T, F: FnOnce(&Closure) -> T>(&self, idx: CacheIndex, f: F) -> T {
+        dispatch!(self, cache => cache.get_then(idx, f))
+    }
+
+    fn update(&mut self, clos: Closure, idx: Self::UpdateIndex) {
+        dispatch!(self, cache => cache.update(clos, idx))
+    }
+
+    fn new() -> Self {
+        SelectedCache::Lazy(lazy::CBNCache::new())
+    }
+
+    fn start_revision(&mut self) {
+        dispatch!(self, cache => cache.start_revision())
+    }
+
+    fn reset_index_state(&mut self, idx: &mut Self::UpdateIndex) {
+        dispatch!(self, cache => cache.reset_index_state(idx))
+    }
+
+    fn map_at_index<F: FnMut(&mut Self, &Closure) -> Closure>(
+        &mut self,
+        idx: &CacheIndex,
+        mut f: F,
+    ) -> CacheIndex {
+        // The mapping function expects the selected cache itself, so we can't just forward the
+        // call to the underlying implementation.
+        if let SelectedCache::Incremental(cache) = self {
+            cache.record_access(idx);
+        }
+
+        idx.map(|clos| f(self, clos))
+    }
+
+    fn build_cached(&mut self, idx: &mut CacheIndex, rec_env: &[(Ident, CacheIndex)]) {
+        dispatch!(self, cache => cache.build_cached(idx, rec_env))
+    }
+
+    fn ident_kind(&self, idx: &CacheIndex) -> IdentKind {
+        dispatch!(self, cache => cache.ident_kind(idx))
+    }
+
+    fn saturate<I: DoubleEndedIterator<Item = Ident> + Clone>(
+        &mut self,
+        idx: CacheIndex,
+        env: &mut Environment,
+        fields: I,
+    ) -> RichTerm {
+        dispatch!(self, cache => cache.saturate(idx, env, fields))
+    }
+
+    fn revert(&mut self, idx: &CacheIndex) -> CacheIndex {
+        dispatch!(self, cache => cache.revert(idx))
+    }
+
+    fn deps(&self, idx: &CacheIndex) -> Option<FieldDeps> {
+        dispatch!(self, cache => cache.deps(idx))
+    }
+
+    fn make_update_index(
+        &mut self,
+        idx: &mut CacheIndex,
+    ) -> Result<Self::UpdateIndex, BlackholedError> {
+        dispatch!(self, cache => cache.make_update_index(idx))
+    }
+
+    fn replace(&mut self, idx: &CacheIndex, clos: Closure) -> bool {
+        dispatch!(self, cache => cache.replace(idx, clos))
+    }
 }
@@ -0,0 +1,298 @@
+//! Identity of runtime contracts.
+//!
+//! The same contract is frequently applied several times to the same value: when merging records
+//! whose pieces are all annotated with the same schema, or when an array goes through several
+//! functions with the same type annotation. Pending contracts are delayed until the data they
+//! guard is accessed, so before adding a contract to the pending contracts of a record field or
+//! of an array, we check if an equivalent one is already there, in which case the new one is
+//! dropped.
+//!
+//! Two contracts are equivalent if their labels agree on everything which influences the check
+//! (see [same_label]) and if their contract terms are known to be the same. As for
+//! [crate::typecheck::eq], equality of terms is undecidable in general: we only test if both
+//! terms are variables bound to the same cache element, following variables a limited number of
+//! times, and compare simple constructs (applications, primitive operators and constants)
+//! structurally. A false negative just costs an additional contract check, but a false positive
+//! would skip a check, so we stay conservative.
+//!
+//! When a contract is dropped, the blame label of the one which was already pending is kept, so
+//! that a violation might be reported at a different annotation than the one which would have
+//! been blamed otherwise.
+use super::{cache::CacheIndex, Environment};
+use crate::{
+    label::Label,
+    term::{RichTerm, RuntimeContract, Term},
+    typ::{RecordRows, RecordRowsF, Type, TypeF},
+};
+use std::rc::Rc;
+
+/// The maximal number of variables to follow when comparing contract terms. The same remarks
+/// apply as for [crate::typecheck::eq::MAX_GAS], but closurization introduces a few more
+/// indirections at runtime than in the source program.
+const MAX_GAS: u8 = 16;
+
+/// Return `true` if the contract `ctr1`, closurized in `env1`, is known to perform the same
+/// check as `ctr2`, closurized in `env2`.
+pub fn contract_eq(
+    ctr1: &RuntimeContract,
+    env1: &Environment,
+    ctr2: &RuntimeContract,
+    env2: &Environment,
+) -> bool {
+    let mut gas = MAX_GAS;
+
+    term_eq(&mut gas, &ctr1.contract, env1, &ctr2.contract, env2)
+        && same_label(&ctr1.label, &ctr2.label)
+}
+
+/// Return `true` if a contract equivalent to `ctr` is in `pending`. All the contracts are
+/// closurized in `env`.
+pub fn is_pending(pending: &[RuntimeContract], ctr: &RuntimeContract, env: &Environment) -> bool {
+    pending
+        .iter()
+        .any(|other| contract_eq(other, env, ctr, env))
+}
+
+/// Add the contracts of `ctrs` to `pending`, skipping the ones which are already pending. All
+/// the contracts are closurized in `env`.
+pub fn extend_pending<I>(pending: &mut Vec<RuntimeContract>, ctrs: I, env: &Environment)
+where
+    I: IntoIterator<Item = RuntimeContract>,
+{
+    for ctr in ctrs {
+        if !is_pending(pending, &ctr, env) {
+            pending.push(ctr);
+        }
+    }
+}
+
+/// Return `true` if two labels lead to the same check. The position of the annotation and the
+/// diagnostics only matter for error reporting, but the polarity, the path and the environment
+/// of polymorphic contracts change what is checked. The types are compared structurally (see
+/// [type_eq]), as the same annotation is often repeated at different places.
+fn same_label(label1: &Label, label2: &Label) -> bool {
+    label1.polarity == label2.polarity
+        && label1.dualize == label2.dualize
+        && label1.path == label2.path
+        && label1.type_environment == label2.type_environment
+        && (Rc::ptr_eq(&label1.typ, &label2.typ) || type_eq(&label1.typ, &label2.typ))
+}
+
+/// Compare two types structurally, without taking the positions of the types and of the
+/// contracts they contain into account. The contracts of flat types are compared syntactically
+/// (see [flat_eq]).
+fn type_eq(ty1: &Type, ty2: &Type) -> bool {
+    match (&ty1.typ, &ty2.typ) {
+        (TypeF::Flat(t1), TypeF::Flat(t2)) => flat_eq(t1, t2),
+        (TypeF::Arrow(dom1, codom1), TypeF::Arrow(dom2, codom2)) => {
+            type_eq(dom1, dom2) && type_eq(codom1, codom2)
+        }
+        (
+            TypeF::Forall {
+                var: var1,
+                var_kind: var_kind1,
+                body: body1,
+            },
+            TypeF::Forall {
+                var: var2,
+                var_kind: var_kind2,
+                body: body2,
+            },
+        ) => var1 == var2 && var_kind1 == var_kind2 && type_eq(body1, body2),
+        (TypeF::Record(rows1), TypeF::Record(rows2)) => record_rows_eq(rows1, rows2),
+        (
+            TypeF::Dict {
+                type_fields: ty1,
+                flavour: flavour1,
+            },
+            TypeF::Dict {
+                type_fields: ty2,
+                flavour: flavour2,
+            },
+        ) => flavour1 == flavour2 && type_eq(ty1, ty2),
+        (TypeF::Array(ty1), TypeF::Array(ty2)) => type_eq(ty1, ty2),
+        // The other types, including enums, don't hold any position.
+        (typ1, typ2) => typ1 == typ2,
+    }
+}
+
+/// Compare two record rows structurally, without taking positions into account.
+fn record_rows_eq(rows1: &RecordRows, rows2: &RecordRows) -> bool {
+    match (&rows1.0, &rows2.0) {
+        (
+            RecordRowsF::Extend {
+                row: row1,
+                tail: tail1,
+            },
+            RecordRowsF::Extend {
+                row: row2,
+                tail: tail2,
+            },
+        ) => row1.id == row2.id && type_eq(&row1.typ, &row2.typ) && record_rows_eq(tail1, tail2),
+        (rows1, rows2) => rows1 == rows2,
+    }
+}
+
+/// Compare the contracts of two flat types syntactically, without taking positions into account.
+/// Those terms aren't closurized: variables are compared by name. As for [term_eq], we only
+/// compare simple constructs, and consider the other ones as different.
+fn flat_eq(t1: &RichTerm, t2: &RichTerm) -> bool {
+    match (t1.as_ref(), t2.as_ref()) {
+        (Term::Var(id1), Term::Var(id2)) => id1 == id2,
+        (Term::Null, Term::Null) => true,
+        (Term::Bool(b1), Term::Bool(b2)) => b1 == b2,
+        (Term::Num(n1), Term::Num(n2)) => n1 == n2,
+        (Term::Str(s1), Term::Str(s2)) => s1 == s2,
+        (Term::Enum(id1), Term::Enum(id2)) => id1 == id2,
+        (Term::App(head1, arg1), Term::App(head2, arg2)) => {
+            flat_eq(head1, head2) && flat_eq(arg1, arg2)
+        }
+        (Term::Op1(op1, t1), Term::Op1(op2, t2)) => op1 == op2 && flat_eq(t1, t2),
+        (Term::Op2(op1, fst1, snd1), Term::Op2(op2, fst2, snd2)) => {
+            op1 == op2 && flat_eq(fst1, fst2) && flat_eq(snd1, snd2)
+        }
+        _ => false,
+    }
+}
+
+/// Follow a variable in its environment, consuming gas. Return `None` if the variable is unbound,
+/// or if there's no gas left.
+fn lookup(gas: &mut u8, t: &RichTerm, env: &Environment) -> Option<CacheIndex> {
+    match t.as_ref() {
+        Term::Var(id) if *gas > 0 => {
+            *gas -= 1;
+            env.get(&id.ident()).cloned()
+        }
+        _ => None,
+    }
+}
+
+fn term_eq(
+    gas: &mut u8,
+    t1: &RichTerm,
+    env1: &Environment,
+    t2: &RichTerm,
+    env2: &Environment,
+) -> bool {
+    match (t1.as_ref(), t2.as_ref()) {
+        (Term::Var(_), Term::Var(_)) => match (lookup(gas, t1, env1), lookup(gas, t2, env2)) {
+            (Some(idx1), Some(idx2)) => idx1.ptr_eq(&idx2) || index_eq(gas, &idx1, &idx2),
+            _ => false,
+        },
+        (Term::Var(_), _) => match lookup(gas, t1, env1) {
+            Some(idx1) => index_term_eq(gas, &idx1, t2, env2),
+            None => false,
+        },
+        (_, Term::Var(_)) => match lookup(gas, t2, env2) {
+            Some(idx2) => index_term_eq(gas, &idx2, t1, env1),
+            None => false,
+        },
+        (Term::Null, Term::Null) => true,
+        (Term::Bool(b1), Term::Bool(b2)) => b1 == b2,
+        (Term::Num(n1), Term::Num(n2)) => n1 == n2,
+        (Term::Str(s1), Term::Str(s2)) => s1 == s2,
+        (Term::Enum(id1), Term::Enum(id2)) => id1 == id2,
+        (Term::App(head1, arg1), Term::App(head2, arg2)) => {
+            term_eq(gas, head1, env1, head2, env2) && term_eq(gas, arg1, env1, arg2, env2)
+        }
+        (Term::Op1(op1, t1), Term::Op1(op2, t2)) => op1 == op2 && term_eq(gas, t1, env1, t2, env2),
+        (Term::Op2(op1, fst1, snd1), Term::Op2(op2, fst2, snd2)) => {
+            op1 == op2
+                && term_eq(gas, fst1, env1, fst2, env2)
+                && term_eq(gas, snd1, env1, snd2, env2)
+        }
+        // Functions, records and the like are only equal when they are bound to the same cache
+        // element, which is handled by the variable case.
+        _ => false,
+    }
+}
+
+/// Compare the content of two cache elements.
+fn index_eq(gas: &mut u8, idx1: &CacheIndex, idx2: &CacheIndex) -> bool {
+    let (Some(clos1), Some(clos2)) = (idx1.try_borrow(), idx2.try_borrow()) else {
+        return false;
+    };
+
+    term_eq(gas, &clos1.body, &clos1.env, &clos2.body, &clos2.env)
+}
+
+/// Compare the content of a cache element with the term `t` in `env`.
+fn index_term_eq(gas: &mut u8, idx: &CacheIndex, t: &RichTerm, env: &Environment) -> bool {
+    let Some(clos) = idx.try_borrow() else {
+        return false;
+    };
+
+    term_eq(gas, &clos.body, &clos.env, t, env)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        identifier::LocIdent,
+        label::{ty_path, Polarity},
+        mk_app,
+        position::TermPos,
+        term::make as mk_term,
+    };
+
+    fn greater_than(n: i64, pos: TermPos) -> Rc<Type> {
+        let contract = mk_app!(mk_term::var("GreaterThan"), mk_term::integer(n)).with_pos(pos);
+        Rc::new(Type::from(TypeF::Flat(contract)).with_pos(pos))
+    }
+
+    #[test]
+    fn same_label_ignores_positions() {
+        let label = Label {
+            typ: greater_than(1, TermPos::None),
+            ..Label::dummy()
+        };
+        let elsewhere = Label {
+            typ: greater_than(1, TermPos::Original(label.span)),
+            ..Label::dummy()
+        };
+
+        assert!(same_label(&label, &elsewhere));
+
+        let array_of = |typ: Rc<Type>, pos: TermPos| {
+            Rc::new(Type::from(TypeF::Array(Box::new(Type::clone(&typ)))).with_pos(pos))
+        };
+        let label = Label {
+            typ: array_of(label.typ, TermPos::None),
+            ..label
+        };
+        let elsewhere = Label {
+            typ: array_of(elsewhere.typ, TermPos::Original(label.span)),
+            ..elsewhere
+        };
+
+        assert!(same_label(&label, &elsewhere));
+    }
+
+    #[test]
+    fn different_labels() {
+        let label = Label {
+            typ: greater_than(1, TermPos::None),
+            path: vec![ty_path::Elem::Field(LocIdent::from("a"))],
+            ..Label::dummy()
+        };
+
+        let other_type = Label {
+            typ: greater_than(2, TermPos::None),
+            ..label.clone()
+        };
+        let other_polarity = Label {
+            polarity: Polarity::Negative,
+            ..label.clone()
+        };
+        let other_path = Label {
+            path: vec![ty_path::Elem::Field(LocIdent::from("b"))],
+            ..label.clone()
+        };
+
+        assert!(same_label(&label, &label.clone()));
+        assert!(!same_label(&label, &other_type));
+        assert!(!same_label(&label, &other_polarity));
+        assert!(!same_label(&label, &other_path));
+    }
+}
@@ -1,6 +1,6 @@
 //! Compute the fixpoint of a recursive record.
 use super::{merge::RevertClosurize, *};
-use crate::{label::Label, position::TermPos};
+use crate::{label::Label, position::TermPos, transform::Closurizable};
 
 // Update the environment of a term by extending it with a recursive environment. In the general
 // case, the term is expected to be a variable pointing to the element to be patched. Otherwise, it's
@@ -233,8 +233,13 @@ pub fn revert<C: Cache>(
         .map(|(id, field)| (id, field.revert_closurize(cache, env, local_env.clone())))
         .collect();
 
+    let invariants = record_data
+        .invariants
+        .closurize(cache, env, local_env.clone());
+
     let record_data = RecordData {
         fields,
+        invariants,
         ..record_data
     };
 
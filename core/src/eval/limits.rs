@@ -0,0 +1,192 @@
+//! Limits on the resources used by an evaluation.
+//!
+//! Evaluating an untrusted configuration might loop forever, recurse without bound or build huge
+//! values. [Limits] can be set on the virtual machine to abort such evaluations: when one of them
+//! is exceeded, the evaluation fails with [crate::error::EvalError::ResourceLimitExceeded].
+use std::{
+    fmt, io,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+
+use crate::term::Term;
+
+/// The resources an evaluation is allowed to use. All limits are disabled by default.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Limits {
+    /// The maximum number of evaluation steps. A step corresponds to one iteration of the main
+    /// loop of the virtual machine.
+    pub max_steps: Option<u64>,
+    /// The maximum number of elements on the stack of the virtual machine, which bounds the depth
+    /// of recursion.
+    pub max_stack_depth: Option<usize>,
+    /// The maximum size of strings, in bytes, and of arrays, in elements.
+    pub max_size: Option<usize>,
+    /// The maximum duration of an evaluation. The clock can't be read on `wasm32` targets, where
+    /// this limit is ignored.
+    pub timeout: Option<Duration>,
+}
+
+/// A limit which has been exceeded, together with its value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Limit {
+    Steps(u64),
+    StackDepth(usize),
+    Size(usize),
+    Timeout(Duration),
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Limit::Steps(max) => write!(f, "the maximum number of evaluation steps ({max})"),
+            Limit::StackDepth(max) => write!(f, "the maximum stack depth ({max})"),
+            Limit::Size(max) => write!(f, "the maximum size of strings and arrays ({max})"),
+            Limit::Timeout(timeout) => write!(f, "the timeout ({}ms)", timeout.as_millis()),
+        }
+    }
+}
+
+impl Limits {
+    /// Check that a string or an array of `size` bytes or elements is allowed.
+    pub fn check_size(&self, size: usize) -> Result<(), Limit> {
+        match self.max_size {
+            Some(max) if size > max => Err(Limit::Size(max)),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The resources used by the current evaluation, to be checked against [Limits].
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Usage {
+    steps: u64,
+    /// A step counter shared with other evaluations, possibly running on other threads, in which
+    /// case the step limit applies to all of them together. Unlike `steps`, it isn't reset.
+    shared_steps: Option<Arc<AtomicU64>>,
+    #[cfg(not(target_arch = "wasm32"))]
+    started: Option<Instant>,
+}
+
+impl Usage {
+    /// Reading the clock at each step would noticeably slow down evaluation, so the timeout is
+    /// only checked every this many steps.
+    #[cfg(not(target_arch = "wasm32"))]
+    const TIMEOUT_CHECK_INTERVAL: u64 = 1024;
+
+    /// Start accounting for a new evaluation. The shared step counter, if any, is kept.
+    pub fn reset(&mut self) {
+        *self = Usage {
+            shared_steps: self.shared_steps.take(),
+            ..Usage::default()
+        };
+    }
+
+    /// The number of steps accounted for since the last reset, or by the shared counter if any.
+    pub fn steps(&self) -> u64 {
+        match &self.shared_steps {
+            Some(shared) => shared.load(Ordering::Relaxed),
+            None => self.steps,
+        }
+    }
+
+    /// Account for steps in `shared_steps` from now on, or stop sharing if `None`.
+    pub fn share_steps(&mut self, shared_steps: Option<Arc<AtomicU64>>) {
+        self.shared_steps = shared_steps;
+    }
+
+    /// Account for one evaluation step, where `term` is the term being evaluated and
+    /// `stack_depth` the current size of the stack.
+    pub fn step(&mut self, limits: &Limits, stack_depth: usize, term: &Term) -> Result<(), Limit> {
+        self.steps += 1;
+
+        if let Some(max) = limits.max_steps {
+            let steps = match &self.shared_steps {
+                Some(shared) => shared.fetch_add(1, Ordering::Relaxed) + 1,
+                None => self.steps,
+            };
+
+            if steps > max {
+                return Err(Limit::Steps(max));
+            }
+        }
+
+        if let Some(max) = limits.max_stack_depth {
+            if stack_depth > max {
+                return Err(Limit::StackDepth(max));
+            }
+        }
+
+        match term {
+            Term::Str(s) => limits.check_size(s.len())?,
+            Term::Array(ts, _) => limits.check_size(ts.len())?,
+            _ => (),
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(timeout) = limits.timeout {
+            if self.steps % Self::TIMEOUT_CHECK_INTERVAL == 1 {
+                let started = *self.started.get_or_insert_with(Instant::now);
+
+                if started.elapsed() > timeout {
+                    return Err(Limit::Timeout(timeout));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// An in-memory writer which refuses to grow beyond a maximum size, such that the size of an
+/// output can be checked before it's allocated entirely.
+pub(crate) struct BoundedWriter {
+    buf: Vec<u8>,
+    max: Option<usize>,
+    exceeded: bool,
+}
+
+impl BoundedWriter {
+    /// Create a writer accepting up to `max` bytes, or an unbounded one if `max` is `None`.
+    pub fn new(max: Option<usize>) -> Self {
+        BoundedWriter {
+            buf: Vec::new(),
+            max,
+            exceeded: false,
+        }
+    }
+
+    /// Return `true` if a write has been refused because of the maximum size.
+    pub fn exceeded(&self) -> bool {
+        self.exceeded
+    }
+
+    pub fn into_inner(self) -> Vec<u8> {
+        self.buf
+    }
+}
+
+impl io::Write for BoundedWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self.max {
+            Some(max) if self.buf.len() + buf.len() > max => {
+                self.exceeded = true;
+                Err(io::Error::new(
+                    io::ErrorKind::Other,
+                    "the output exceeds the maximum size",
+                ))
+            }
+            _ => self.buf.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -313,6 +313,15 @@ Append `, ..` at the end of the record contract, as in `{some_field | SomeContra
                 );
             }
 
+            // Record-level invariants of both sides are accumulated, so that they are checked
+            // against the final record.
+            let invariants = r1
+                .invariants
+                .closurize(cache, &mut env, env1)
+                .into_iter()
+                .chain(r2.invariants.closurize(cache, &mut env, env2))
+                .collect();
+
             Ok(Closure {
                 body: RichTerm::new(
                     // We don't have to provide RecordDeps, which are required in a previous stage
@@ -320,7 +329,10 @@ Append `, ..` at the end of the record contract, as in `{some_field | SomeContra
                     // about them anymore, and dependencies are stored at the level of revertible
                     // cache elements directly.
                     Term::RecRecord(
-                        RecordData::new(m, RecordAttrs::merge(r1.attrs, r2.attrs), None),
+                        RecordData {
+                            invariants,
+                            ..RecordData::new(m, RecordAttrs::merge(r1.attrs, r2.attrs), None)
+                        },
                         Vec::new(),
                         None,
                     ),
@@ -404,8 +416,11 @@ fn merge_fields<'a, C: Cache, I: DoubleEndedIterator<Item = &'a LocIdent> + Clon
         _ => unreachable!(),
     };
 
+    // Both sides are often subject to the same contracts, typically when merging several pieces
+    // of a configuration annotated with the same schema: we only keep one copy of each.
     let mut pending_contracts = pending_contracts1.revert_closurize(cache, env_final, env1.clone());
-    pending_contracts.extend(pending_contracts2.revert_closurize(cache, env_final, env2.clone()));
+    let pending_contracts2 = pending_contracts2.revert_closurize(cache, env_final, env2.clone());
+    contract_eq::extend_pending(&mut pending_contracts, pending_contracts2, env_final);
 
     // Annotation aren't used anymore at runtime. We still accumulate them to answer metadata
     // queries, but we don't need to e.g. closurize or revert them.
@@ -32,7 +32,9 @@
 //!   popped from the stack and are updated to point to the current evaluated term.
 //! - **Import**: Import must have been resolved before the evaluation starts. An unresolved import
 //!   causes an [`crate::error::EvalError::InternalError`]. A resolved import, identified by a
-//!   `FileId`, is retrieved from the import resolver and evaluation proceeds.
+//!   `FileId`, is retrieved from the import resolver and evaluation proceeds. If imports are
+//!   shared (see [VirtualMachine::share_imports]), the term of each file is instead stored in a
+//!   cache element on first access, and evaluated at most once.
 //!
 //! ## Contracts
 //!
@@ -93,18 +95,27 @@ use crate::{
     transform::Closurizable,
 };
 
-use std::io::Write;
+use std::{
+    collections::HashMap,
+    io::Write,
+    sync::{atomic::AtomicU64, Arc},
+};
 
 pub mod cache;
 pub mod callstack;
+pub mod contract_eq;
 pub mod fixpoint;
+pub mod limits;
 pub mod merge;
 pub mod operation;
+pub mod regex_cache;
 pub mod stack;
 
 use callstack::*;
 use codespan::FileId;
+use limits::{Limit, Limits, Usage};
 use operation::OperationCont;
+use regex_cache::RegexCache;
 use stack::{Stack, StrAccData};
 
 use self::cache::{Cache, CacheIndex};
@@ -127,6 +138,14 @@ pub struct VirtualMachine<R: ImportResolver, C: Cache> {
     pub cache: C,
     // The stream for writing trace output.
     trace: Box<dyn Write>,
+    // The compiled regular expressions, shared across evaluations.
+    regex_cache: RegexCache,
+    // The limits on the resources used by an evaluation.
+    limits: Limits,
+    // The resources used by the current evaluation.
+    usage: Usage,
+    // The cache elements holding the imported files, if imports are shared.
+    shared_imports: Option<HashMap<FileId, CacheIndex>>,
 }
 
 impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
@@ -137,6 +156,10 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
             stack: Stack::new(),
             cache: Cache::new(),
             trace: Box::new(trace),
+            regex_cache: RegexCache::new(),
+            limits: Limits::default(),
+            usage: Usage::default(),
+            shared_imports: None,
         }
     }
 
@@ -147,6 +170,10 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
             stack: Stack::new(),
             cache,
             trace: Box::new(trace),
+            regex_cache: RegexCache::new(),
+            limits: Limits::default(),
+            usage: Usage::default(),
+            shared_imports: None,
         }
     }
 
@@ -155,6 +182,110 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
     pub fn reset(&mut self) {
         self.call_stack.0.clear();
         self.stack.reset(&mut self.cache);
+        self.usage.reset();
+        self.cache.start_revision();
+    }
+
+    /// Evaluate each imported file at most once, and share its value between all the places where
+    /// it's imported, including in subsequent evaluations. This is only correct as long as the
+    /// imported files don't change: see [Self::forget_imports].
+    pub fn share_imports(&mut self) {
+        self.shared_imports.get_or_insert_with(HashMap::new);
+    }
+
+    /// Forget the shared values of the given files, for example because they have been reloaded.
+    pub fn forget_imports(&mut self, files: impl IntoIterator<Item = FileId>) {
+        if let Some(shared) = &mut self.shared_imports {
+            for file in files {
+                shared.remove(&file);
+            }
+        }
+    }
+
+    /// Return the closure to evaluate for the imported file `id`. If imports are shared, push an
+    /// update frame for the cache element of this file if it hasn't been evaluated yet.
+    fn import_closure(&mut self, id: FileId, pos: TermPos) -> Result<Closure, EvalError> {
+        let fetch = |import_resolver: &R| {
+            import_resolver.get(id).ok_or_else(|| {
+                EvalError::InternalError(format!("Resolved import not found ({id:?})"), pos)
+            })
+        };
+
+        let Some(shared) = &mut self.shared_imports else {
+            return fetch(&self.import_resolver).map(Closure::atomic_closure);
+        };
+
+        let mut idx = match shared.get(&id) {
+            Some(idx) => idx.clone(),
+            None => {
+                let idx = self.cache.add(
+                    Closure::atomic_closure(fetch(&self.import_resolver)?),
+                    IdentKind::Let,
+                    BindingType::Normal,
+                );
+                shared.insert(id, idx.clone());
+                idx
+            }
+        };
+
+        match self.cache.get_update_index(&mut idx) {
+            Ok(Some(idx_upd)) => self.stack.push_update_index(idx_upd),
+            Ok(None) => {}
+            Err(_blackholed_error) => {
+                return Err(EvalError::InfiniteRecursion(self.call_stack.clone(), pos))
+            }
+        }
+
+        Ok(self.cache.get(idx))
+    }
+
+    /// Set the limits on the resources used by subsequent evaluations. The resources used are
+    /// accounted from the last call to [Self::reset].
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    pub fn limits(&self) -> &Limits {
+        &self.limits
+    }
+
+    /// Check that a string or an array of `size` bytes or elements is allowed by the limits,
+    /// before building it at position `pos`.
+    fn check_size(&self, size: usize, pos: TermPos) -> Result<(), EvalError> {
+        self.limits
+            .check_size(size)
+            .map_err(|limit| self.limit_exceeded(limit, pos))
+    }
+
+    /// Same as [Self::check_size], but only compute the size if the size is limited, as computing
+    /// it might take as long as building the string or the array.
+    fn check_size_with(&self, size: impl FnOnce() -> usize, pos: TermPos) -> Result<(), EvalError> {
+        if self.limits.max_size.is_some() {
+            self.check_size(size(), pos)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// The number of steps of the current evaluation, or of all the evaluations sharing the step
+    /// counter (see [Self::share_steps]).
+    pub(crate) fn steps(&self) -> u64 {
+        self.usage.steps()
+    }
+
+    /// Account for the evaluation steps in `steps` from now on, including in subsequent
+    /// evaluations, such that the step limit applies to all the machines sharing the counter
+    /// together. Stop sharing if `steps` is `None`.
+    pub(crate) fn share_steps(&mut self, steps: Option<Arc<AtomicU64>>) {
+        self.usage.share_steps(steps);
+    }
+
+    fn limit_exceeded(&self, limit: Limit, pos: TermPos) -> EvalError {
+        EvalError::ResourceLimitExceeded {
+            limit,
+            call_stack: self.call_stack.clone(),
+            pos,
+        }
     }
 
     pub fn import_resolver(&self) -> &R {
@@ -165,6 +296,11 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
         &mut self.import_resolver
     }
 
+    /// The stream trace output is written to.
+    pub fn trace_mut(&mut self) -> &mut dyn Write {
+        &mut *self.trace
+    }
+
     /// Evaluate a Nickel term. Wrapper around [VirtualMachine::eval_closure] that starts from an empty local
     /// environment and drops the final environment.
     pub fn eval(&mut self, t0: RichTerm, initial_env: &Environment) -> Result<RichTerm, EvalError> {
@@ -343,6 +479,10 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                 mut env,
             } = clos;
 
+            self.usage
+                .step(&self.limits, self.stack.depth(), &shared_term)
+                .map_err(|limit| self.limit_exceeded(limit, pos))?;
+
             clos = match &*shared_term {
                 Term::Sealed(_, inner, lbl) => {
                     let stack_item = self.stack.peek_op_cont();
@@ -361,7 +501,7 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                     update_at_indices(&mut self.cache, &mut self.stack, &closure);
                     match stack_item {
                         Some(OperationCont::Op2Second(BinaryOp::Unseal(), _, _, _)) => {
-                            self.continuate_operation(closure)?
+                            self.continuate_operation(closure, initial_env)?
                         }
                         Some(OperationCont::Op1(UnaryOp::Seq(), _)) => {
                             // Then, evaluate / `Seq` the inner value.
@@ -538,7 +678,7 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
 
                             self.stack.push_str_chunks(chunks_iter.cloned());
                             self.stack.push_str_acc(StrAccData {
-                                acc: String::new(),
+                                acc: NickelString::new(),
                                 env: env.clone(),
                                 curr_indent: indent,
                                 curr_pos: arg.pos,
@@ -629,16 +769,7 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                         env,
                     }
                 }
-                Term::ResolvedImport(id) => {
-                    if let Some(t) = self.import_resolver.get(*id) {
-                        Closure::atomic_closure(t)
-                    } else {
-                        return Err(EvalError::InternalError(
-                            format!("Resolved import not found ({id:?})"),
-                            pos,
-                        ));
-                    }
-                }
+                Term::ResolvedImport(id) => self.import_closure(*id, pos)?,
                 Term::Import(path) => {
                     return Err(EvalError::InternalError(
                         format!("Unresolved import ({})", path.to_string_lossy()),
@@ -728,7 +859,7 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                         update_at_indices(&mut self.cache, &mut self.stack, &clos);
                         clos
                     } else {
-                        self.continuate_operation(clos)?
+                        self.continuate_operation(clos, initial_env)?
                     }
                 }
                 // Function call
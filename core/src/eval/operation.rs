@@ -7,19 +7,23 @@
 //! On the other hand, the functions `process_unary_operation` and `process_binary_operation`
 //! receive evaluated operands and implement the actual semantics of operators.
 use super::{
+    contract_eq,
+    limits::{BoundedWriter, Limit},
     merge::{self, MergeMode},
-    stack::StrAccData,
+    stack::{Stack, StrAccData},
     subst, Cache, Closure, Environment, ImportResolver, VirtualMachine,
 };
 
 use crate::{
+    datetime,
     error::{EvalError, IllegalPolymorphicTailAction},
+    hash::{HashAlgorithm, HmacAlgorithm},
     identifier::LocIdent,
     label::{ty_path, Polarity, TypeVarData},
-    match_sharedterm, mk_app, mk_fun, mk_opn, mk_record,
+    match_sharedterm, mk_app, mk_fun, mk_opn, mk_record, net,
     parser::utils::parse_number,
     position::TermPos,
-    serialize,
+    semver, serialize,
     serialize::ExportFormat,
     stdlib::internals,
     term::{
@@ -27,30 +31,43 @@ use crate::{
         make as mk_term,
         record::{self, Field, FieldMetadata, RecordData},
         string::NickelString,
-        BinaryOp, CompiledRegex, IndexMap, MergePriority, NAryOp, Number, RecordExtKind, RichTerm,
-        RuntimeContract, SharedTerm, StrChunk, Term, UnaryOp,
+        BinaryOp, Encoding, IndexMap, MergePriority, NAryOp, Number, RecordExtKind, RichTerm,
+        RuntimeContract, SharedTerm, StrChunk, StringFormat, Term, UnaryOp,
     },
     transform::Closurizable,
 };
 
 use malachite::{
     num::{
-        arithmetic::traits::Pow,
-        basic::traits::Zero,
+        arithmetic::traits::{Abs, CheckedSqrt, Gcd, Pow, UnsignedAbs},
+        basic::traits::{One, Zero},
         conversion::traits::{RoundingFrom, ToSci},
+        logic::traits::SignificantBits,
     },
     rounding_modes::RoundingMode,
-    Integer,
+    Integer, Natural,
 };
 
-use md5::digest::Digest;
+use base64::Engine;
+use percent_encoding::{AsciiSet, NON_ALPHANUMERIC};
 use simple_counter::*;
 use unicode_segmentation::UnicodeSegmentation;
 
-use std::{convert::TryFrom, iter::Extend, rc::Rc};
+use std::{cmp::Ordering, convert::TryFrom, iter::Extend};
 
 generate_counter!(FreshVariableCounter, usize);
 
+/// The largest exponent for which [BinaryOp::NumLog] tries to find an exact integer result.
+const MAX_EXACT_LOG: f64 = 4096.0;
+
+/// The characters escaped by percent-encoding: everything but the unreserved characters of RFC
+/// 3986.
+const PERCENT_ENCODE_SET: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
 /// Result of the equality of two terms.
 ///
 /// The equality of two terms can either be computed directly for base types (`Number`, `String`, etc.),
@@ -111,7 +128,14 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
     /// Depending on the content of the stack, it either starts the evaluation of the first argument,
     /// starts the evaluation of the second argument, or finally process with the operation if both
     /// arguments are evaluated (for binary operators).
-    pub fn continuate_operation(&mut self, mut clos: Closure) -> Result<Closure, EvalError> {
+    ///
+    /// `initial_env` is the environment containing the stdlib items, which is needed by the few
+    /// primops that run a nested evaluation (see [`BinaryOp::ContractTry`]).
+    pub fn continuate_operation(
+        &mut self,
+        mut clos: Closure,
+        initial_env: &Environment,
+    ) -> Result<Closure, EvalError> {
         let (cont, cs_len, pos) = self.stack.pop_op_cont().expect("Condition already checked");
         self.call_stack.truncate(cs_len);
         match cont {
@@ -127,9 +151,8 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                 );
                 Ok(clos)
             }
-            OperationCont::Op2Second(b_op, fst_clos, fst_pos, snd_pos) => {
-                self.process_binary_operation(b_op, fst_clos, fst_pos, clos, snd_pos, pos)
-            }
+            OperationCont::Op2Second(b_op, fst_clos, fst_pos, snd_pos) => self
+                .process_binary_operation(b_op, fst_clos, fst_pos, clos, snd_pos, pos, initial_env),
             OperationCont::OpN {
                 op,
                 mut evaluated,
@@ -584,6 +607,8 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                   ));
                 };
 
+                self.check_size(n_int as usize, pos_op)?;
+
                 let mut shared_env = Environment::new();
                 let f_as_var = f.body.closurize(&mut self.cache, &mut env, f.env);
 
@@ -646,8 +671,13 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                                 })
                                 .map_err(|missing_field_err| missing_field_err.into_eval_err(pos, pos_op))?;
 
+                            // The result is a new record, built from the mapped values: the
+                            // invariants of the original record don't apply to it.
                             Ok(Closure {
-                                body: RichTerm::new(Term::Record(RecordData { fields, ..record }), pos_op_inh),
+                                body: RichTerm::new(
+                                    Term::Record(RecordData { fields, invariants: Vec::new(), ..record }),
+                                    pos_op_inh
+                                ),
                                 env: shared_env,
                             })
                         }
@@ -683,7 +713,29 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                 }
 
                 match t.into_owned() {
-                    Term::Record(record) if !record.fields.is_empty() => {
+                    Term::Record(mut record)
+                        if !record.fields.is_empty() || !record.invariants.is_empty() =>
+                    {
+                        let mut env = env;
+
+                        // As for `Force`, record-level invariants are checked against the original
+                        // record once all the fields have been evaluated. The values returned by
+                        // the invariants are only evaluated to a weak head normal form.
+                        let invariants = std::mem::take(&mut record.invariants);
+                        let checks: Vec<_> = if invariants.is_empty() {
+                            Vec::new()
+                        } else {
+                            let local_env = env.clone();
+                            let checked = RichTerm::new(Term::Record(record.clone()), pos)
+                                .closurize(&mut self.cache, &mut env, local_env.clone());
+
+                            invariants
+                                .closurize(&mut self.cache, &mut env, local_env)
+                                .into_iter()
+                                .map(|invariant| invariant.apply(checked.clone(), pos_op_inh))
+                                .collect()
+                        };
+
                         let defined = record
                             // into_iter_without_opts applies pending contracts as well
                             .into_iter_without_opts()
@@ -694,10 +746,26 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
 
                         let terms = defined.into_iter().map(|(_, field)| field);
 
-                        Ok(Closure {
-                            body: seq_terms(terms, pos_op),
-                            env,
-                        })
+                        let body = if checks.is_empty() {
+                            seq_terms(terms, pos_op)
+                        } else {
+                            // Deep-sequencing `null` resumes the evaluation of the argument on the
+                            // top of the stack once the invariants have been checked.
+                            let cont = checks.into_iter().fold(
+                                mk_term::op1(UnaryOp::DeepSeq(), Term::Null).with_pos(pos_op_inh),
+                                |acc, check| {
+                                    mk_app!(mk_term::op1(UnaryOp::Seq(), check), acc)
+                                        .with_pos(pos_op_inh)
+                                },
+                            );
+
+                            terms.fold(cont, |acc, t| {
+                                mk_app!(mk_term::op1(UnaryOp::DeepSeq(), t), acc)
+                                    .with_pos(pos_op_inh)
+                            })
+                        };
+
+                        Ok(Closure { body, env })
                     }
                     Term::Array(ts, attrs) if !ts.is_empty() => {
                         let mut shared_env = Environment::new();
@@ -753,6 +821,15 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                 } = self.stack.pop_str_acc().unwrap();
 
                 if let Term::Str(s) = &*t {
+                    // Each line break is followed by the indentation.
+                    self.check_size_with(
+                        || {
+                            let newlines = s.as_str().matches('\n').count();
+                            acc.len() + s.len() + newlines * indent
+                        },
+                        pos_op,
+                    )?;
+
                     let s = if indent != 0 {
                         let indent_str: String = std::iter::once('\n')
                             .chain((0..indent).map(|_| ' '))
@@ -762,12 +839,13 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                         s.clone()
                     };
 
-                    acc.push_str(&s);
+                    acc.append(&s);
 
                     let mut next_opt = self.stack.pop_str_chunk();
 
                     // Pop consecutive string literals to find the next expression to evaluate
                     while let Some(StrChunk::Literal(s)) = next_opt {
+                        self.check_size(acc.len() + s.len(), pos_op)?;
                         acc.push_str(&s);
                         next_opt = self.stack.pop_str_chunk();
                     }
@@ -786,7 +864,7 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                         })
                     } else {
                         Ok(Closure::atomic_closure(RichTerm::new(
-                            Term::Str(acc.into()),
+                            Term::Str(acc),
                             pos_op_inh,
                         )))
                     }
@@ -815,6 +893,7 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
             }
             UnaryOp::StrChars() => {
                 if let Term::Str(s) = &*t {
+                    self.check_size_with(|| s.length(), pos_op)?;
                     let ts = s.characters();
                     Ok(Closure::atomic_closure(RichTerm::new(
                         Term::Array(ts, ArrayAttrs::new().closurized()),
@@ -844,6 +923,91 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                     Err(mk_type_error!("string_lowercase", "String"))
                 }
             }
+            UnaryOp::StrEncode(encoding) => {
+                if let Term::Str(s) = &*t {
+                    Ok(Closure::atomic_closure(RichTerm::new(
+                        Term::Str(encode(encoding, s.as_str()).into()),
+                        pos_op_inh,
+                    )))
+                } else {
+                    Err(mk_type_error!(format!("{encoding}_encode"), "String"))
+                }
+            }
+            UnaryOp::StrDecode(encoding) => {
+                if let Term::Str(s) = &*t {
+                    let decoded = decode(encoding, s.as_str()).map_err(|msg| {
+                        EvalError::DecodingError(encoding.to_string(), msg, pos_op)
+                    })?;
+
+                    Ok(Closure::atomic_closure(RichTerm::new(
+                        Term::Str(decoded.into()),
+                        pos_op_inh,
+                    )))
+                } else {
+                    Err(mk_type_error!(format!("{encoding}_decode"), "String"))
+                }
+            }
+            UnaryOp::StrCheckFormat(format) => {
+                let Term::Str(ref s) = *t else {
+                    return Err(mk_type_error!(format!("{format}_check"), "String"));
+                };
+
+                let message = check_format(format, s).err().unwrap_or_default();
+                let result = mk_record!(
+                    ("valid", Term::Bool(message.is_empty())),
+                    ("message", Term::Str(message.into()))
+                );
+
+                Ok(Closure::atomic_closure(result.with_pos(pos_op_inh)))
+            }
+            UnaryOp::SemVerParse() => {
+                let Term::Str(ref s) = *t else {
+                    return Err(mk_type_error!("semver_parse", "String"));
+                };
+
+                let c = semver::components(s).map_err(|msg| {
+                    EvalError::Other(
+                        format!("semver_parse: invalid semantic version `{s}`: {msg}"),
+                        pos_op,
+                    )
+                })?;
+
+                let result = mk_record!(
+                    ("major", Term::Num(c.major.into())),
+                    ("minor", Term::Num(c.minor.into())),
+                    ("patch", Term::Num(c.patch.into())),
+                    ("pre", Term::Str(c.pre.into())),
+                    ("build", Term::Str(c.build.into()))
+                );
+
+                Ok(Closure::atomic_closure(result.with_pos(pos_op_inh)))
+            }
+            UnaryOp::UrlParse() => {
+                let Term::Str(ref s) = *t else {
+                    return Err(mk_type_error!("url_parse", "String"));
+                };
+
+                let c = net::url_components(s).map_err(|msg| {
+                    EvalError::Other(format!("url_parse: invalid URL `{s}`: {msg}"), pos_op)
+                })?;
+
+                // There's no optional value in Nickel yet: a missing port is represented as `-1`,
+                // similarly to a missing match in `str_find`.
+                let port = c.port.map_or(Number::from(-1), Number::from);
+
+                let result = mk_record!(
+                    ("scheme", Term::Str(c.scheme.into())),
+                    ("username", Term::Str(c.username.into())),
+                    ("password", Term::Str(c.password.into())),
+                    ("host", Term::Str(c.host.into())),
+                    ("port", Term::Num(port)),
+                    ("path", Term::Str(c.path.into())),
+                    ("query", Term::Str(c.query.into())),
+                    ("fragment", Term::Str(c.fragment.into()))
+                );
+
+                Ok(Closure::atomic_closure(result.with_pos(pos_op_inh)))
+            }
             UnaryOp::StrLength() => {
                 if let Term::Str(s) = &*t {
                     let length = s.graphemes(true).count();
@@ -890,6 +1054,171 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                     Err(mk_type_error!("num_from_str", "String"))
                 }
             }
+            UnaryOp::NumFloor() => {
+                let Term::Num(ref n) = *t else {
+                    return Err(mk_type_error!("floor", "Number"));
+                };
+
+                Ok(Closure::atomic_closure(RichTerm::new(
+                    Term::Num(Number::from(Integer::rounding_from(
+                        n.clone(),
+                        RoundingMode::Floor,
+                    ))),
+                    pos_op_inh,
+                )))
+            }
+            UnaryOp::NumCeil() => {
+                let Term::Num(ref n) = *t else {
+                    return Err(mk_type_error!("ceil", "Number"));
+                };
+
+                Ok(Closure::atomic_closure(RichTerm::new(
+                    Term::Num(Number::from(Integer::rounding_from(
+                        n.clone(),
+                        RoundingMode::Ceiling,
+                    ))),
+                    pos_op_inh,
+                )))
+            }
+            UnaryOp::NumRound() => {
+                let Term::Num(ref n) = *t else {
+                    return Err(mk_type_error!("round", "Number"));
+                };
+
+                // Malachite's `RoundingMode::Nearest` rounds half to even, but we round half away
+                // from zero, which is what most users expect.
+                let half = Number::from_signeds(1, 2);
+                let rounded = if *n >= Number::ZERO {
+                    Integer::rounding_from(n + half, RoundingMode::Floor)
+                } else {
+                    Integer::rounding_from(n - half, RoundingMode::Ceiling)
+                };
+
+                Ok(Closure::atomic_closure(RichTerm::new(
+                    Term::Num(Number::from(rounded)),
+                    pos_op_inh,
+                )))
+            }
+            UnaryOp::NumSqrt() => {
+                let Term::Num(ref n) = *t else {
+                    return Err(mk_type_error!("sqrt", "Number"));
+                };
+
+                if *n < Number::ZERO {
+                    return Err(EvalError::Other(
+                        format!(
+                            "sqrt: the square root of a negative number ({}) isn't defined",
+                            n.to_sci()
+                        ),
+                        pos_op,
+                    ));
+                }
+
+                // The square root of a rational is rational exactly when both the numerator and
+                // the denominator (which are coprime) are perfect squares.
+                let exact = n
+                    .numerator_ref()
+                    .clone()
+                    .checked_sqrt()
+                    .zip(n.denominator_ref().clone().checked_sqrt());
+
+                let result = if let Some((num, den)) = exact {
+                    Number::from_naturals(num, den)
+                } else {
+                    // The number might be outside of the range of floats, as `2e400`. We scale
+                    // it by an even power of ten to bring it between `1` and `100` before
+                    // converting it, and scale the square root back afterwards.
+                    let exponent = (ln(n) / std::f64::consts::LN_10 / 2.0).floor() as i64;
+                    let scale = Number::from(Integer::from(10)).pow(exponent);
+                    let scaled = n / &scale / &scale;
+
+                    let result_as_f64 = f64::rounding_from(&scaled, RoundingMode::Nearest).sqrt();
+                    Number::try_from_float_simplest(result_as_f64)
+                        .map(|root| root * scale)
+                        .map_err(|_| {
+                            EvalError::Other(
+                                format!(
+                                    "sqrt: the square root of {} returned {result_as_f64}, which \
+                                    isn't representable in Nickel",
+                                    n.to_sci()
+                                ),
+                                pos_op,
+                            )
+                        })?
+                };
+
+                Ok(Closure::atomic_closure(RichTerm::new(
+                    Term::Num(result),
+                    pos_op_inh,
+                )))
+            }
+            UnaryOp::DateTimeParse() => {
+                let Term::Str(ref s) = *t else {
+                    return Err(mk_type_error!("datetime_parse", "String"));
+                };
+
+                let c = datetime::components(s)
+                    .map_err(|msg| EvalError::Other(format!("datetime_parse: {msg}"), pos_op))?;
+
+                let result = mk_record!(
+                    ("timestamp", Term::Num(c.timestamp)),
+                    ("offset", Term::Num(c.offset.into())),
+                    ("year", Term::Num(c.year.into())),
+                    ("month", Term::Num(c.month.into())),
+                    ("day", Term::Num(c.day.into())),
+                    ("hour", Term::Num(c.hour.into())),
+                    ("minute", Term::Num(c.minute.into())),
+                    ("second", Term::Num(c.second.into())),
+                    ("nanosecond", Term::Num(c.nanosecond.into())),
+                    ("weekday", Term::Num(c.weekday.into())),
+                    ("day_of_year", Term::Num(c.day_of_year.into()))
+                );
+
+                Ok(Closure::atomic_closure(result.with_pos(pos_op_inh)))
+            }
+            UnaryOp::DateTimeIsValid() => {
+                let Term::Str(ref s) = *t else {
+                    return Err(mk_type_error!("datetime_is_valid", "String"));
+                };
+
+                Ok(Closure::atomic_closure(RichTerm::new(
+                    Term::Bool(datetime::parse(s).is_ok()),
+                    pos_op_inh,
+                )))
+            }
+            UnaryOp::DurationParse() => {
+                let Term::Str(ref s) = *t else {
+                    return Err(mk_type_error!("duration_parse", "String"));
+                };
+
+                let seconds = datetime::parse_duration(s)
+                    .map_err(|msg| EvalError::Other(format!("duration_parse: {msg}"), pos_op))?;
+
+                Ok(Closure::atomic_closure(RichTerm::new(
+                    Term::Num(seconds),
+                    pos_op_inh,
+                )))
+            }
+            UnaryOp::DurationIsValid() => {
+                let Term::Str(ref s) = *t else {
+                    return Err(mk_type_error!("duration_is_valid", "String"));
+                };
+
+                Ok(Closure::atomic_closure(RichTerm::new(
+                    Term::Bool(datetime::parse_duration(s).is_ok()),
+                    pos_op_inh,
+                )))
+            }
+            UnaryOp::DurationFormat() => {
+                let Term::Num(ref n) = *t else {
+                    return Err(mk_type_error!("duration_format", "Number"));
+                };
+
+                Ok(Closure::atomic_closure(RichTerm::new(
+                    Term::Str(datetime::format_duration(n).into()),
+                    pos_op_inh,
+                )))
+            }
             UnaryOp::EnumFromStr() => {
                 if let Term::Str(s) = &*t {
                     Ok(Closure::atomic_closure(RichTerm::new(
@@ -900,48 +1229,36 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                     Err(mk_type_error!("enum_from_str", "String"))
                 }
             }
-            UnaryOp::StrIsMatch() => {
+            UnaryOp::StrIsMatch()
+            | UnaryOp::StrFind()
+            | UnaryOp::StrFindAll()
+            | UnaryOp::StrCaptures() => {
                 if let Term::Str(s) = &*t {
-                    let re = regex::Regex::new(s)
+                    let re = self
+                        .regex_cache
+                        .get_or_compile(s)
                         .map_err(|err| EvalError::Other(err.to_string(), pos_op))?;
 
-                    let param = LocIdent::fresh();
-                    let matcher = Term::Fun(
-                        param,
-                        RichTerm::new(
-                            Term::Op1(
-                                UnaryOp::StrIsMatchCompiled(re.into()),
-                                RichTerm::new(Term::Var(param), pos_op_inh),
-                            ),
-                            pos_op_inh,
-                        ),
-                    );
-
-                    Ok(Closure::atomic_closure(RichTerm::new(matcher, pos)))
-                } else {
-                    Err(mk_type_error!("str_is_match", "String", 1))
-                }
-            }
-            UnaryOp::StrFind() => {
-                if let Term::Str(s) = &*t {
-                    let re = regex::Regex::new(s)
-                        .map_err(|err| EvalError::Other(err.to_string(), pos_op))?;
+                    let compiled_op = match u_op {
+                        UnaryOp::StrIsMatch() => UnaryOp::StrIsMatchCompiled(re),
+                        UnaryOp::StrFind() => UnaryOp::StrFindCompiled(re),
+                        UnaryOp::StrFindAll() => UnaryOp::StrFindAllCompiled(re),
+                        UnaryOp::StrCaptures() => UnaryOp::StrCapturesCompiled(re),
+                        _ => unreachable!(),
+                    };
 
                     let param = LocIdent::fresh();
                     let matcher = Term::Fun(
                         param,
                         RichTerm::new(
-                            Term::Op1(
-                                UnaryOp::StrFindCompiled(re.into()),
-                                RichTerm::new(Term::Var(param), pos_op_inh),
-                            ),
+                            Term::Op1(compiled_op, RichTerm::new(Term::Var(param), pos_op_inh)),
                             pos_op_inh,
                         ),
                     );
 
                     Ok(Closure::atomic_closure(RichTerm::new(matcher, pos)))
                 } else {
-                    Err(mk_type_error!("str_find", "String", 1))
+                    Err(mk_type_error!(u_op.to_string(), "String", 1))
                 }
             }
             UnaryOp::StrIsMatchCompiled(regex) => {
@@ -998,6 +1315,54 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                     ))
                 }
             }
+            UnaryOp::StrFindAllCompiled(regex) => {
+                if let Term::Str(s) = &*t {
+                    let matches = s.find_all_regex(&regex).map(|m| {
+                        mk_record!(
+                            ("matched", RichTerm::from(Term::Str(m.mtch))),
+                            ("index", RichTerm::from(Term::Num(m.index))),
+                            (
+                                "groups",
+                                RichTerm::from(Term::Array(
+                                    Array::from_iter(
+                                        m.groups.into_iter().map(|s| Term::Str(s).into())
+                                    ),
+                                    ArrayAttrs::new().closurized()
+                                ))
+                            )
+                        )
+                    });
+
+                    Ok(Closure::atomic_closure(RichTerm::new(
+                        Term::Array(Array::from_iter(matches), ArrayAttrs::new().closurized()),
+                        pos_op_inh,
+                    )))
+                } else {
+                    Err(mk_type_error!(
+                        "a compiled regular expression match",
+                        "String"
+                    ))
+                }
+            }
+            UnaryOp::StrCapturesCompiled(regex) => {
+                if let Term::Str(s) = &*t {
+                    let fields = s
+                        .captures_regex(&regex)
+                        .unwrap_or_default()
+                        .into_iter()
+                        .map(|(name, value)| (LocIdent::from(name), Term::Str(value).into()));
+
+                    Ok(Closure::atomic_closure(RichTerm::new(
+                        Term::Record(RecordData::with_field_values(fields)),
+                        pos_op_inh,
+                    )))
+                } else {
+                    Err(mk_type_error!(
+                        "a compiled regular expression match",
+                        "String"
+                    ))
+                }
+            }
             UnaryOp::Force {
                 ignore_not_exported,
             } => {
@@ -1013,9 +1378,28 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
 
                 match_sharedterm! {t,
                     with {
-                        Term::Record(record) if !record.fields.is_empty() => {
+                        Term::Record(record) if !record.fields.is_empty() || !record.invariants.is_empty() => {
+                            // due to a limitation of `match_sharedterm`: see the macro's
+                            // documentation
+                            let mut record = record;
                             let mut shared_env = Environment::new();
 
+                            // Record-level invariants are checked against the original record,
+                            // which includes the fields that are not exported.
+                            let invariants = std::mem::take(&mut record.invariants);
+                            let checks: Vec<_> = if invariants.is_empty() {
+                                Vec::new()
+                            } else {
+                                let checked = RichTerm::new(Term::Record(record.clone()), pos)
+                                    .closurize(&mut self.cache, &mut shared_env, env.clone());
+
+                                invariants
+                                    .closurize(&mut self.cache, &mut shared_env, env.clone())
+                                    .into_iter()
+                                    .map(|invariant| invariant.apply(checked.clone(), pos_op_inh))
+                                    .collect()
+                            };
+
                             let fields = record.fields
                                 .into_iter()
                                 .filter(|(_, field)| {
@@ -1040,8 +1424,11 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                                 pos.into_inherited(),
                             );
 
+                            // The invariants are checked once all the fields have been forced.
+                            // `seq_terms` evaluates the last terms first, hence the invariants
+                            // come first.
                             Ok(Closure {
-                                body: seq_terms(terms, pos_op, cont),
+                                body: seq_terms(checks.into_iter().chain(terms), pos_op, cont),
                                 env: shared_env,
                             })
                         },
@@ -1145,6 +1532,7 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
     ///
     /// Both arguments are expected to be evaluated (in WHNF). `pos_op` corresponds to the whole
     /// operation position, that may be needed for error reporting.
+    #[allow(clippy::too_many_arguments)]
     fn process_binary_operation(
         &mut self,
         b_op: BinaryOp,
@@ -1153,6 +1541,7 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
         clos: Closure,
         snd_pos: TermPos,
         pos_op: TermPos,
+        initial_env: &Environment,
     ) -> Result<Closure, EvalError> {
         let Closure {
             body: RichTerm {
@@ -1325,36 +1714,303 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                     Err(mk_type_error!("pow", "Number", 1, t1, pos1))
                 }
             }
-            BinaryOp::StrConcat() => {
-                if let Term::Str(s1) = &*t1 {
-                    if let Term::Str(s2) = &*t2 {
-                        let ss: [&str; 2] = [s1, s2];
-                        Ok(Closure::atomic_closure(RichTerm::new(
-                            Term::Str(ss.concat().into()),
-                            pos_op_inh,
-                        )))
-                    } else {
-                        Err(mk_type_error!("(++)", "String", 2, t2, pos2))
-                    }
-                } else {
-                    Err(mk_type_error!("(++)", "String", 1, t1, pos1))
+            BinaryOp::NumLog() => {
+                let Term::Num(ref n) = *t1 else {
+                    return Err(mk_type_error!("log", "Number", 1, t1, pos1));
+                };
+
+                let Term::Num(ref base) = *t2 else {
+                    return Err(mk_type_error!("log", "Number", 2, t2, pos2));
+                };
+
+                if *n <= Number::ZERO {
+                    return Err(EvalError::Other(
+                        format!(
+                            "log: the logarithm of a non-positive number ({}) isn't defined",
+                            n.to_sci()
+                        ),
+                        pos1,
+                    ));
+                }
+
+                if *base <= Number::ZERO || *base == Number::ONE {
+                    return Err(EvalError::Other(
+                        format!(
+                            "log: invalid base {}. The base of a logarithm must be positive and \
+                            different from 1",
+                            base.to_sci()
+                        ),
+                        pos2,
+                    ));
                 }
+
+                let result_as_f64 = ln(n) / ln(base);
+
+                // The floating-point computation might be slightly off even if the logarithm is an
+                // integer, as in `log 1000 10`. If the result is close to a reasonably small
+                // integer `k`, we check if `base^k == n` exactly, and return `k` in that case.
+                let candidate = result_as_f64.round();
+                let exact = (candidate.abs() <= MAX_EXACT_LOG
+                    && (result_as_f64 - candidate).abs() < 1e-9)
+                    .then_some(candidate as i64)
+                    .filter(|k| &base.pow(*k) == n);
+
+                let result = if let Some(k) = exact {
+                    Number::from(k)
+                } else {
+                    Number::try_from_float_simplest(result_as_f64).map_err(|_| {
+                        EvalError::Other(
+                            format!(
+                                "log: the logarithm of {} in base {} returned {result_as_f64}, \
+                                which isn't representable in Nickel",
+                                n.to_sci(),
+                                base.to_sci()
+                            ),
+                            pos_op,
+                        )
+                    })?
+                };
+
+                Ok(Closure::atomic_closure(RichTerm::new(
+                    Term::Num(result),
+                    pos_op_inh,
+                )))
             }
-            BinaryOp::Assume() => {
-                if let Term::Lbl(l) = &*t2 {
-                    // Track the contract argument for better error reporting, and push back the label
-                    // on the stack, so that it becomes the first argument of the contract.
-                    let idx = self.stack.track_arg(&mut self.cache).ok_or_else(|| {
-                        EvalError::NotEnoughArgs(3, String::from("assume"), pos_op)
-                    })?;
-                    let mut l = l.clone();
-                    l.arg_pos = self.cache.get_then(idx.clone(), |c| c.body.pos);
-                    l.arg_idx = Some(idx);
+            BinaryOp::IntDiv() | BinaryOp::IntRem() => {
+                let primop = b_op.to_string();
 
-                    self.stack.push_arg(
-                        Closure::atomic_closure(RichTerm::new(Term::Lbl(l), pos2.into_inherited())),
-                        pos2.into_inherited(),
-                    );
+                let Term::Num(ref n1) = *t1 else {
+                    return Err(mk_type_error!(primop, "Number", 1, t1, pos1));
+                };
+
+                let Term::Num(ref n2) = *t2 else {
+                    return Err(mk_type_error!(primop, "Number", 2, t2, pos2));
+                };
+
+                let (Ok(n1), Ok(n2)) = (Integer::try_from(n1), Integer::try_from(n2)) else {
+                    return Err(EvalError::Other(
+                        format!(
+                            "{primop}: expected integer operands, got {} and {}",
+                            n1.to_sci(),
+                            n2.to_sci()
+                        ),
+                        pos_op,
+                    ));
+                };
+
+                if n2 == Integer::ZERO {
+                    return Err(EvalError::Other(
+                        format!("division by zero ({primop})"),
+                        pos2,
+                    ));
+                }
+
+                // Malachite's integer division rounds towards zero, and the remainder has the sign
+                // of the dividend.
+                let result = if matches!(b_op, BinaryOp::IntDiv()) {
+                    n1 / n2
+                } else {
+                    n1 % n2
+                };
+
+                Ok(Closure::atomic_closure(RichTerm::new(
+                    Term::Num(Number::from(result)),
+                    pos_op_inh,
+                )))
+            }
+            BinaryOp::Gcd() => {
+                let Term::Num(ref n1) = *t1 else {
+                    return Err(mk_type_error!("gcd", "Number", 1, t1, pos1));
+                };
+
+                let Term::Num(ref n2) = *t2 else {
+                    return Err(mk_type_error!("gcd", "Number", 2, t2, pos2));
+                };
+
+                let (Ok(n1), Ok(n2)) = (Integer::try_from(n1), Integer::try_from(n2)) else {
+                    return Err(EvalError::Other(
+                        format!(
+                            "gcd: expected integer operands, got {} and {}",
+                            n1.to_sci(),
+                            n2.to_sci()
+                        ),
+                        pos_op,
+                    ));
+                };
+
+                Ok(Closure::atomic_closure(RichTerm::new(
+                    Term::Num(Number::from(n1.unsigned_abs().gcd(n2.unsigned_abs()))),
+                    pos_op_inh,
+                )))
+            }
+            BinaryOp::NumToStrFixed() => {
+                let Term::Num(ref digits) = *t1 else {
+                    return Err(mk_type_error!("num_to_str_fixed", "Number", 1, t1, pos1));
+                };
+
+                let Term::Num(ref n) = *t2 else {
+                    return Err(mk_type_error!("num_to_str_fixed", "Number", 2, t2, pos2));
+                };
+
+                let Ok(digits) = u64::try_from(digits) else {
+                    return Err(EvalError::Other(
+                        format!(
+                            "num_to_str_fixed: expected the number of decimal places to be a \
+                            natural number, got {digits}"
+                        ),
+                        pos1,
+                    ));
+                };
+
+                if digits > MAX_FIXED_DIGITS {
+                    return Err(EvalError::Other(
+                        format!(
+                            "num_to_str_fixed: the number of decimal places must be at most \
+                            {MAX_FIXED_DIGITS}, got {digits}"
+                        ),
+                        pos1,
+                    ));
+                }
+
+                Ok(Closure::atomic_closure(RichTerm::new(
+                    Term::Str(to_str_fixed(n, digits).into()),
+                    pos_op_inh,
+                )))
+            }
+            BinaryOp::DateTimeFromTimestamp() => {
+                let Term::Num(ref offset) = *t1 else {
+                    return Err(mk_type_error!(
+                        "datetime_from_timestamp",
+                        "Number",
+                        1,
+                        t1,
+                        pos1
+                    ));
+                };
+
+                let Term::Num(ref timestamp) = *t2 else {
+                    return Err(mk_type_error!(
+                        "datetime_from_timestamp",
+                        "Number",
+                        2,
+                        t2,
+                        pos2
+                    ));
+                };
+
+                let result = datetime::from_timestamp(timestamp, offset).map_err(|msg| {
+                    EvalError::Other(format!("datetime_from_timestamp: {msg}"), pos_op)
+                })?;
+
+                Ok(Closure::atomic_closure(RichTerm::new(
+                    Term::Str(result.into()),
+                    pos_op_inh,
+                )))
+            }
+            BinaryOp::DateTimeFormat() => {
+                let Term::Str(ref fmt) = *t1 else {
+                    return Err(mk_type_error!("datetime_format", "String", 1, t1, pos1));
+                };
+
+                let Term::Str(ref s) = *t2 else {
+                    return Err(mk_type_error!("datetime_format", "String", 2, t2, pos2));
+                };
+
+                let result = datetime::format(fmt, s)
+                    .map_err(|msg| EvalError::Other(format!("datetime_format: {msg}"), pos_op))?;
+
+                Ok(Closure::atomic_closure(RichTerm::new(
+                    Term::Str(result.into()),
+                    pos_op_inh,
+                )))
+            }
+            BinaryOp::SemVerCompare() => {
+                let Term::Str(ref s1) = *t1 else {
+                    return Err(mk_type_error!("semver_compare", "String", 1, t1, pos1));
+                };
+
+                let Term::Str(ref s2) = *t2 else {
+                    return Err(mk_type_error!("semver_compare", "String", 2, t2, pos2));
+                };
+
+                let ordering = semver::compare(s1, s2)
+                    .map_err(|msg| EvalError::Other(format!("semver_compare: {msg}"), pos_op))?;
+
+                let tag = match ordering {
+                    Ordering::Less => "Lesser",
+                    Ordering::Equal => "Equal",
+                    Ordering::Greater => "Greater",
+                };
+
+                Ok(Closure::atomic_closure(RichTerm::new(
+                    Term::Enum(LocIdent::from(tag)),
+                    pos_op_inh,
+                )))
+            }
+            BinaryOp::SemVerMatches() => {
+                let Term::Str(ref req) = *t1 else {
+                    return Err(mk_type_error!("semver_matches", "String", 1, t1, pos1));
+                };
+
+                let Term::Str(ref s) = *t2 else {
+                    return Err(mk_type_error!("semver_matches", "String", 2, t2, pos2));
+                };
+
+                let matches = semver::matches(req, s)
+                    .map_err(|msg| EvalError::Other(format!("semver_matches: {msg}"), pos_op))?;
+
+                Ok(Closure::atomic_closure(RichTerm::new(
+                    Term::Bool(matches),
+                    pos_op_inh,
+                )))
+            }
+            BinaryOp::CidrContains() => {
+                let Term::Str(ref cidr) = *t1 else {
+                    return Err(mk_type_error!("cidr_contains", "String", 1, t1, pos1));
+                };
+
+                let Term::Str(ref ip) = *t2 else {
+                    return Err(mk_type_error!("cidr_contains", "String", 2, t2, pos2));
+                };
+
+                let contains = net::cidr_contains(cidr, ip)
+                    .map_err(|msg| EvalError::Other(format!("cidr_contains: {msg}"), pos_op))?;
+
+                Ok(Closure::atomic_closure(RichTerm::new(
+                    Term::Bool(contains),
+                    pos_op_inh,
+                )))
+            }
+            BinaryOp::StrConcat() => {
+                if let Term::Str(s1) = &*t1 {
+                    if let Term::Str(s2) = &*t2 {
+                        self.check_size(s1.len() + s2.len(), pos_op)?;
+                        Ok(Closure::atomic_closure(RichTerm::new(
+                            Term::Str(s1.concat(s2)),
+                            pos_op_inh,
+                        )))
+                    } else {
+                        Err(mk_type_error!("(++)", "String", 2, t2, pos2))
+                    }
+                } else {
+                    Err(mk_type_error!("(++)", "String", 1, t1, pos1))
+                }
+            }
+            BinaryOp::Assume() => {
+                if let Term::Lbl(l) = &*t2 {
+                    // Track the contract argument for better error reporting, and push back the label
+                    // on the stack, so that it becomes the first argument of the contract.
+                    let idx = self.stack.track_arg(&mut self.cache).ok_or_else(|| {
+                        EvalError::NotEnoughArgs(3, String::from("assume"), pos_op)
+                    })?;
+                    let mut l = l.clone();
+                    l.arg_pos = self.cache.get_then(idx.clone(), |c| c.body.pos);
+                    l.arg_idx = Some(idx);
+
+                    self.stack.push_arg(
+                        Closure::atomic_closure(RichTerm::new(Term::Lbl(l), pos2.into_inherited())),
+                        pos2.into_inherited(),
+                    );
 
                     match *t1 {
                         Term::Fun(..) | Term::Match { .. } => Ok(Closure {
@@ -1398,6 +2054,92 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                     Err(mk_type_error!("assume", "Label", 2, t2, pos2))
                 }
             }
+            BinaryOp::ContractTry() => {
+                let label = if let Term::Lbl(label) = &*t2 {
+                    label.clone()
+                } else {
+                    return Err(mk_type_error!("contract_try", "Label", 2, t2, pos2));
+                };
+
+                let (value_idx, value_pos) =
+                    self.stack.pop_arg_as_idx(&mut self.cache).ok_or_else(|| {
+                        EvalError::NotEnoughArgs(3, String::from("contract_try"), pos_op)
+                    })?;
+
+                let mut env = Environment::new();
+                let contract = RichTerm {
+                    term: t1,
+                    pos: pos1,
+                }
+                .closurize(&mut self.cache, &mut env, env1);
+                let body = mk_term::op2(BinaryOp::Assume(), contract, Term::Lbl(label))
+                    .with_pos(pos_op_inh);
+
+                // The contract is applied in a nested evaluation with a fresh stack, so that a
+                // failure can't leave the current stack in an inconsistent state.
+                let outer_stack = std::mem::replace(&mut self.stack, Stack::new());
+                let cs_len = self.call_stack.len();
+                self.stack.push_tracked_arg(value_idx, value_pos);
+
+                let result = self.eval_closure(Closure { body, env }, initial_env);
+
+                if result.is_err() {
+                    self.stack.reset(&mut self.cache);
+                }
+                self.stack = outer_stack;
+                self.call_stack.truncate(cs_len);
+
+                match result {
+                    Ok((value, value_env)) => {
+                        let mut env = Environment::new();
+                        let value = value.closurize(&mut self.cache, &mut env, value_env);
+
+                        Ok(Closure {
+                            body: mk_record!(
+                                ("success", Term::Bool(true)),
+                                ("value", value),
+                                ("message", Term::Str(NickelString::new())),
+                                (
+                                    "notes",
+                                    Term::Array(Array::default(), ArrayAttrs::new().closurized())
+                                )
+                            )
+                            .with_pos(pos_op_inh),
+                            env,
+                        })
+                    }
+                    Err(EvalError::BlameError { label: blamed, .. }) => {
+                        let diagnostic = blamed
+                            .diagnostics
+                            .iter()
+                            .rev()
+                            .find(|diag| !diag.is_empty())
+                            .cloned()
+                            .unwrap_or_default();
+
+                        let notes = Array::from_iter(
+                            diagnostic
+                                .notes
+                                .into_iter()
+                                .map(|note| Term::Str(note.into()).into()),
+                        );
+
+                        Ok(Closure::atomic_closure(
+                            mk_record!(
+                                ("success", Term::Bool(false)),
+                                ("value", Term::Null),
+                                (
+                                    "message",
+                                    Term::Str(diagnostic.message.unwrap_or_default().into())
+                                ),
+                                ("notes", Term::Array(notes, ArrayAttrs::new().closurized()))
+                            )
+                            .with_pos(pos_op_inh),
+                        ))
+                    }
+                    Err(err) => Err(err),
+                }
+            }
             BinaryOp::Unseal() => {
                 if let Term::SealingKey(s1) = &*t1 {
                     // Return a function that either behaves like the identity or
@@ -1716,18 +2458,7 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                                 debug_assert!(attrs1.closurized, "the left-hand side of ArrayConcat (@) is not closurized.");
                                 debug_assert!(attrs2.closurized, "the right-hand side of ArrayConcat (@) is not closurized.");
 
-                                // NOTE: To avoid the extra Vec allocation, we could use Rc<[T]>::new_uninit_slice()
-                                // and fill up the slice manually, but that's a nightly-only experimental API.
-                                // Note that collecting into an Rc<[T]> will also allocate a intermediate vector,
-                                // unless the input iterator implements the nightly-only API TrustedLen, and Array's iterator currently doesn't.
-                                // Even if we could implement TrustedLen we would have to contend with the fact that .chain(..) tends to be slow.
-                                // - Rc<[T]>::from_iter docs: https://doc.rust-lang.org/std/rc/struct.Rc.html#impl-FromIterator%3CT%3E
-                                // - chain issue: https://github.com/rust-lang/rust/issues/63340
-                                let mut ts: Vec<RichTerm> = Vec::with_capacity(ts1.len() + ts2.len());
-
-                                let mut env = env1.clone();
-                                // TODO: Is there a cheaper way to "merge" two environements?
-                                env.extend(env2.iter_elems().map(|(k, v)| (*k, v.clone())));
+                                self.check_size(ts1.len() + ts2.len(), pos_op)?;
 
                                 // We have two sets of contracts from the LHS and RHS arrays.
                                 // - Common contracts between the two sides can be put into
@@ -1740,22 +2471,53 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                                 let (ctrs_left, ctrs_common) : (Vec<_>, Vec<_>) = attrs1
                                     .pending_contracts
                                     .into_iter()
-                                    .partition(|ctr| !attrs2.pending_contracts.contains(ctr));
+                                    .partition(|ctr1| !attrs2.pending_contracts.iter().any(|ctr2| {
+                                        contract_eq::contract_eq(ctr1, &env1, ctr2, &env2)
+                                    }));
 
-                                let ctrs_right = attrs2
+                                let ctrs_right: Vec<_> = attrs2
                                     .pending_contracts
                                     .into_iter()
-                                    .filter(|ctr| !ctrs_left.contains(ctr) && !ctrs_common.contains(ctr));
+                                    .filter(|ctr2| !ctrs_common.iter().any(|ctr1| {
+                                        contract_eq::contract_eq(ctr1, &env1, ctr2, &env2)
+                                    }))
+                                    .collect();
+
+                                // The elements of a side without specific contracts are kept as
+                                // they are, which only requires their bindings (and the ones of
+                                // the common contracts) to be in the environment of the result.
+                                // We start from the environment of the longest array and merge
+                                // the other one into it, such that building an array by repeated
+                                // concatenations doesn't copy the whole environment each time.
+                                let mut env = if ts1.len() >= ts2.len() {
+                                    let mut env = env1.clone();
+                                    env.extend(env2.iter_elems().map(|(k, v)| (*k, v.clone())));
+                                    env
+                                } else {
+                                    let mut env = env2.clone();
+                                    env.extend(env1.iter_elems().map(|(k, v)| (*k, v.clone())));
+                                    env
+                                };
 
-                                ts.extend(ts1.into_iter().map(|t|
-                                    RuntimeContract::apply_all(t, ctrs_left.iter().cloned(), pos1)
-                                    .closurize(&mut self.cache, &mut env, env1.clone())
-                                ));
+                                let mut ts = if ctrs_left.is_empty() {
+                                    ts1
+                                } else {
+                                    ts1.into_iter().map(|t|
+                                        RuntimeContract::apply_all(t, ctrs_left.iter().cloned(), pos1)
+                                        .closurize(&mut self.cache, &mut env, env1.clone())
+                                    ).collect()
+                                };
 
-                                ts.extend(ts2.into_iter().map(|t|
-                                    RuntimeContract::apply_all(t, ctrs_right.clone(), pos2)
-                                    .closurize(&mut self.cache, &mut env, env2.clone())
-                                ));
+                                if ctrs_right.is_empty() {
+                                    ts.append(ts2);
+                                } else {
+                                    ts.append(ts2.into_iter().map(|t|
+                                        RuntimeContract::apply_all(t, ctrs_right.iter().cloned(), pos2)
+                                        .closurize(&mut self.cache, &mut env, env2.clone())
+                                    ).collect());
+                                }
+
+                                env.compact();
 
                                 let attrs = ArrayAttrs {
                                     closurized: true,
@@ -1763,7 +2525,7 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                                 };
 
                                 Ok(Closure {
-                                    body: RichTerm::new(Term::Array(Array::new(Rc::from(ts)), attrs), pos_op_inh),
+                                    body: RichTerm::new(Term::Array(ts, attrs), pos_op_inh),
                                     env,
                                 })
                             }
@@ -1800,6 +2562,69 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                 (Term::Array(..), _) => Err(mk_type_error!("elem_at", "Number", 2, t2, pos2)),
                 (_, _) => Err(mk_type_error!("elem_at", "Array", 1, t1, pos1)),
             },
+            BinaryOp::ArrayChunks() | BinaryOp::ArrayWindows() => {
+                let windows = matches!(b_op, BinaryOp::ArrayWindows());
+                let op_name = if windows {
+                    "array_windows"
+                } else {
+                    "array_chunks"
+                };
+
+                let Term::Num(ref n) = *t1 else {
+                    return Err(mk_type_error!(op_name, "Number", 1, t1, pos1));
+                };
+
+                let Term::Array(ref ts, ref attrs) = *t2 else {
+                    return Err(mk_type_error!(op_name, "Array", 2, t2, pos2));
+                };
+
+                let size = match usize::try_from(n) {
+                    Ok(size) if size > 0 => size,
+                    _ => {
+                        return Err(EvalError::Other(
+                            format!(
+                                "{op_name} expects its first argument to be a strictly positive integer smaller than {}, got {n}",
+                                usize::MAX
+                            ),
+                            pos_op,
+                        ))
+                    }
+                };
+
+                // The sub-arrays are views over the original storage, and thus share its
+                // environment and its pending contracts. They still need to be closurized to
+                // maintain the invariant that array elements are only variables.
+                self.check_size(
+                    if windows {
+                        (ts.len() + 1).saturating_sub(size)
+                    } else {
+                        ts.len().div_ceil(size)
+                    },
+                    pos_op,
+                )?;
+
+                let mut shared_env = Environment::new();
+                let parts: Box<dyn Iterator<Item = Array> + '_> = if windows {
+                    Box::new(ts.windows(size))
+                } else {
+                    Box::new(ts.chunks(size))
+                };
+
+                let parts: Array = parts
+                    .map(|part| {
+                        RichTerm::new(Term::Array(part, attrs.clone()), pos2.into_inherited())
+                            .closurize(&mut self.cache, &mut shared_env, env2.clone())
+                    })
+                    .collect();
+
+                Ok(Closure {
+                    body: RichTerm::new(
+                        Term::Array(parts, ArrayAttrs::new().closurized()),
+                        pos_op_inh,
+                    ),
+                    env: shared_env,
+                })
+            }
             BinaryOp::Merge(merge_label) => merge::merge(
                 &mut self.cache,
                 RichTerm {
@@ -1817,52 +2642,61 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                 &mut self.call_stack,
             ),
             BinaryOp::Hash() => {
-                let mk_err_fst = |t1| {
-                    Err(mk_type_error!(
-                        "hash",
-                        "[| 'Md5, 'Sha1, 'Sha256, 'Sha512 |]",
+                let mk_err_fst = |t1| Err(mk_type_error!("hash", HashAlgorithm::TAGS, 1, t1, pos1));
+
+                let algorithm = match &*t1 {
+                    Term::Enum(id) => HashAlgorithm::from_tag(id.label()),
+                    _ => None,
+                };
+
+                let Some(algorithm) = algorithm else {
+                    return mk_err_fst(t1);
+                };
+
+                if let Term::Str(s) = &*t2 {
+                    Ok(Closure::atomic_closure(RichTerm::new(
+                        Term::Str(algorithm.digest(s.as_bytes()).into()),
+                        pos_op_inh,
+                    )))
+                } else {
+                    Err(mk_type_error!("hash", "String", 2, t2, pos2))
+                }
+            }
+            BinaryOp::HashValue() => {
+                let algorithm = match &*t1 {
+                    Term::Enum(id) => HashAlgorithm::from_tag(id.label()),
+                    _ => None,
+                };
+
+                let Some(algorithm) = algorithm else {
+                    return Err(mk_type_error!(
+                        "hash_value",
+                        HashAlgorithm::TAGS,
                         1,
                         t1,
                         pos1
-                    ))
+                    ));
                 };
 
-                if let Term::Enum(id) = &*t1 {
-                    if let Term::Str(s) = &*t2 {
-                        let result = match id.as_ref() {
-                            "Md5" => {
-                                let mut hasher = md5::Md5::new();
-                                hasher.update(s.as_ref());
-                                format!("{:x}", hasher.finalize())
-                            }
-                            "Sha1" => {
-                                let mut hasher = sha1::Sha1::new();
-                                hasher.update(s.as_ref());
-                                format!("{:x}", hasher.finalize())
-                            }
-                            "Sha256" => {
-                                let mut hasher = sha2::Sha256::new();
-                                hasher.update(s.as_ref());
-                                format!("{:x}", hasher.finalize())
-                            }
-                            "Sha512" => {
-                                let mut hasher = sha2::Sha512::new();
-                                hasher.update(s.as_ref());
-                                format!("{:x}", hasher.finalize())
-                            }
-                            _ => return mk_err_fst(t1),
-                        };
+                // Serialization needs all variables term to be fully substituted
+                let initial_env = Environment::new();
+                let rt2 = subst(
+                    &self.cache,
+                    RichTerm {
+                        term: t2,
+                        pos: pos2,
+                    },
+                    &initial_env,
+                    &env2,
+                );
 
-                        Ok(Closure::atomic_closure(RichTerm::new(
-                            Term::Str(result.into()),
-                            pos_op_inh,
-                        )))
-                    } else {
-                        Err(mk_type_error!("hash", "String", 2, t2, pos2))
-                    }
-                } else {
-                    mk_err_fst(t1)
-                }
+                serialize::validate(ExportFormat::Json, &rt2)?;
+                let json = serialize::to_canonical_json(&rt2)?;
+
+                Ok(Closure::atomic_closure(RichTerm::new(
+                    Term::Str(algorithm.digest(json.as_bytes()).into()),
+                    pos_op_inh,
+                )))
             }
             BinaryOp::Serialize() => {
                 let mk_err_fst = |t1| {
@@ -1896,8 +2730,21 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                     };
 
                     serialize::validate(format, &rt2)?;
+
+                    // The size of the output is checked as it's written. TOML is an exception, as
+                    // it's serialized to a string before being written.
+                    let mut output = BoundedWriter::new(self.limits.max_size);
+                    if let Err(err) = serialize::to_writer(&mut output, format, &rt2) {
+                        return Err(match self.limits.max_size {
+                            Some(max) if output.exceeded() => {
+                                self.limit_exceeded(Limit::Size(max), pos_op)
+                            }
+                            _ => err.into(),
+                        });
+                    }
+
                     Ok(Closure::atomic_closure(RichTerm::new(
-                        Term::Str(serialize::to_string(format, &rt2)?.into()),
+                        Term::Str(String::from_utf8_lossy(&output.into_inner()).into()),
                         pos_op_inh,
                     )))
                 } else {
@@ -1972,6 +2819,24 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                 (Term::Str(_), _) => Err(mk_type_error!("str_contains", "String", 2, t2, pos2)),
                 (_, _) => Err(mk_type_error!("str_contains", "String", 1, t1, pos1)),
             },
+            BinaryOp::StrCompare() => match (&*t1, &*t2) {
+                (Term::Str(s1), Term::Str(s2)) => {
+                    // Comparing the UTF-8 encodings byte by byte is the same as comparing the
+                    // strings code point by code point.
+                    let tag = match s1.as_str().cmp(s2.as_str()) {
+                        Ordering::Less => "Lesser",
+                        Ordering::Equal => "Equal",
+                        Ordering::Greater => "Greater",
+                    };
+
+                    Ok(Closure::atomic_closure(RichTerm::new(
+                        Term::Enum(LocIdent::from(tag)),
+                        pos_op_inh,
+                    )))
+                }
+                (Term::Str(_), _) => Err(mk_type_error!("str_compare", "String", 2, t2, pos2)),
+                (_, _) => Err(mk_type_error!("str_compare", "String", 1, t1, pos1)),
+            },
             BinaryOp::ArrayLazyAssume() => {
                 let (ctr, _) = self.stack.pop_arg(&self.cache).ok_or_else(|| {
                     EvalError::NotEnoughArgs(3, String::from("array_lazy_assume"), pos_op)
@@ -1993,12 +2858,15 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                         Term::Array(ts, attrs) => {
                             // Preserve the environment of the contract in the resulting array.
                             let rt3 = rt3.closurize(&mut self.cache, &mut env2, env3);
+                            let mut attrs = attrs;
+                            contract_eq::extend_pending(
+                                &mut attrs.pending_contracts,
+                                [RuntimeContract::new(rt3, lbl)],
+                                &env2,
+                            );
 
                             let array_with_ctr = Closure {
-                                body: RichTerm::new(
-                                    Term::Array(ts, attrs.with_extra_contracts([RuntimeContract::new(rt3, lbl)])),
-                                    pos2,
-                                ),
+                                body: RichTerm::new(Term::Array(ts, attrs), pos2),
                                 env: env2,
                             };
 
@@ -2032,21 +2900,23 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                             // documentation
                             let mut record_data = record_data;
 
-                            let mut contract_at_field = |id: LocIdent| {
-                                let pos = contract_term.pos;
-                                mk_app!(
-                                    contract_term.clone(),
-                                    RichTerm::new(Term::Str(id.into()), id.pos))
-                                        .with_pos(pos)
-                                        .closurize(&mut self.cache, &mut env2, contract_env.clone(),
-                                )
-                            };
-
                             for (id, field) in record_data.fields.iter_mut() {
-                                field.pending_contracts.push(RuntimeContract {
-                                    contract: contract_at_field(*id),
+                                let contract = mk_app!(
+                                    contract_term.clone(),
+                                    RichTerm::new(Term::Str((*id).into()), id.pos))
+                                        .with_pos(contract_term.pos)
+                                        .closurize(&mut self.cache, &mut env2, contract_env.clone());
+                                let ctr = RuntimeContract {
+                                    contract,
                                     label: label.clone(),
-                                });
+                                };
+
+                                // The same contract might already have been applied to the
+                                // record, for example by merging two records with the same
+                                // schema.
+                                if !contract_eq::is_pending(&field.pending_contracts, &ctr, &env2) {
+                                    field.pending_contracts.push(ctr);
+                                }
                             }
 
                             // IMPORTANT: here, we revert the record back to a `RecRecord`. The
@@ -2251,12 +3121,19 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                 match (&*fst, &*snd, &*thd) {
                     (Term::Str(s), Term::Str(from), Term::Str(to)) => {
                         let result = if let NAryOp::StrReplace() = n_op {
+                            self.check_size_with(
+                                || s.replaced_len(from.as_str(), to.as_str()),
+                                pos_op,
+                            )?;
                             s.replace(from.as_str(), to.as_str())
                         } else {
-                            let re = regex::Regex::new(from)
+                            let re = self
+                                .regex_cache
+                                .get_or_compile(from)
                                 .map_err(|err| EvalError::Other(err.to_string(), pos_op))?;
 
-                            s.replace_regex(&CompiledRegex(re), to)
+                            self.check_size_with(|| s.regex_replaced_len(&re, to), pos_op)?;
+                            s.replace_regex(&re, to)
                         };
 
                         Ok(Closure::atomic_closure(RichTerm::new(
@@ -2805,6 +3682,249 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                     env: env3,
                 })
             }
+            NAryOp::RecordAddInvariant() => {
+                let mut args = args.into_iter();
+
+                let (
+                    Closure {
+                        body: contract,
+                        env: contract_env,
+                    },
+                    _,
+                ) = args.next().unwrap();
+
+                let (
+                    Closure {
+                        body:
+                            RichTerm {
+                                term: label,
+                                pos: pos2,
+                            },
+                        ..
+                    },
+                    label_pos,
+                ) = args.next().unwrap();
+
+                let (
+                    Closure {
+                        body:
+                            RichTerm {
+                                term: record,
+                                pos: pos3,
+                            },
+                        env: mut env3,
+                    },
+                    record_pos,
+                ) = args.next().unwrap();
+                debug_assert!(args.next().is_none());
+
+                let Term::Lbl(ref label) = &*label else {
+                    return Err(EvalError::NAryPrimopTypeError {
+                        primop: String::from("record_add_invariant"),
+                        expected: String::from("Label"),
+                        arg_number: 2,
+                        arg_pos: label_pos,
+                        arg_evaluated: RichTerm {
+                            term: label,
+                            pos: pos2,
+                        },
+                    });
+                };
+
+                let Term::Record(ref data) = &*record else {
+                    return Err(EvalError::NAryPrimopTypeError {
+                        primop: String::from("record_add_invariant"),
+                        expected: String::from("Record"),
+                        arg_number: 3,
+                        arg_pos: record_pos,
+                        arg_evaluated: RichTerm {
+                            term: record,
+                            pos: pos3,
+                        },
+                    });
+                };
+
+                let mut data = data.clone();
+                let contract = contract.closurize(&mut self.cache, &mut env3, contract_env);
+                data.invariants
+                    .push(RuntimeContract::new(contract, label.clone()));
+
+                Ok(Closure {
+                    body: RichTerm::new(Term::Record(data), pos3),
+                    env: env3,
+                })
+            }
+            NAryOp::Hmac() => {
+                let mut args_wo_env = args
+                    .into_iter()
+                    .map(|(clos, pos)| (clos.body.term, clos.body.pos, pos));
+                let (fst, pos1, fst_pos) = args_wo_env.next().unwrap();
+                let (snd, pos2, snd_pos) = args_wo_env.next().unwrap();
+                let (thd, pos3, thd_pos) = args_wo_env.next().unwrap();
+                debug_assert!(args_wo_env.next().is_none());
+
+                let algorithm = match &*fst {
+                    Term::Enum(id) => HmacAlgorithm::from_tag(id.label()),
+                    _ => None,
+                };
+
+                let Some(algorithm) = algorithm else {
+                    return Err(EvalError::NAryPrimopTypeError {
+                        primop: String::from("hmac"),
+                        expected: String::from(HmacAlgorithm::TAGS),
+                        arg_number: 1,
+                        arg_pos: fst_pos,
+                        arg_evaluated: RichTerm {
+                            term: fst,
+                            pos: pos1,
+                        },
+                    });
+                };
+
+                let Term::Str(key) = &*snd else {
+                    return Err(EvalError::NAryPrimopTypeError {
+                        primop: String::from("hmac"),
+                        expected: String::from("String"),
+                        arg_number: 2,
+                        arg_pos: snd_pos,
+                        arg_evaluated: RichTerm {
+                            term: snd,
+                            pos: pos2,
+                        },
+                    });
+                };
+
+                let Term::Str(message) = &*thd else {
+                    return Err(EvalError::NAryPrimopTypeError {
+                        primop: String::from("hmac"),
+                        expected: String::from("String"),
+                        arg_number: 3,
+                        arg_pos: thd_pos,
+                        arg_evaluated: RichTerm {
+                            term: thd,
+                            pos: pos3,
+                        },
+                    });
+                };
+
+                Ok(Closure::atomic_closure(RichTerm::new(
+                    Term::Str(algorithm.mac(key.as_bytes(), message.as_bytes()).into()),
+                    pos_op_inh,
+                )))
+            }
+            NAryOp::LabelWithRelatedField() => {
+                let mut args = args.into_iter();
+
+                let (
+                    Closure {
+                        body:
+                            RichTerm {
+                                term: field,
+                                pos: pos1,
+                            },
+                        ..
+                    },
+                    field_pos,
+                ) = args.next().unwrap();
+
+                let (
+                    Closure {
+                        body:
+                            RichTerm {
+                                term: record,
+                                pos: pos2,
+                            },
+                        ..
+                    },
+                    record_pos,
+                ) = args.next().unwrap();
+
+                let (
+                    Closure {
+                        body:
+                            RichTerm {
+                                term: label,
+                                pos: pos3,
+                            },
+                        ..
+                    },
+                    label_pos,
+                ) = args.next().unwrap();
+                debug_assert!(args.next().is_none());
+
+                let Term::Str(ref field) = &*field else {
+                    return Err(EvalError::NAryPrimopTypeError {
+                        primop: String::from("label_with_related_field"),
+                        expected: String::from("String"),
+                        arg_number: 1,
+                        arg_pos: field_pos,
+                        arg_evaluated: RichTerm {
+                            term: field,
+                            pos: pos1,
+                        },
+                    });
+                };
+
+                let Term::Record(ref data) = &*record else {
+                    return Err(EvalError::NAryPrimopTypeError {
+                        primop: String::from("label_with_related_field"),
+                        expected: String::from("Record"),
+                        arg_number: 2,
+                        arg_pos: record_pos,
+                        arg_evaluated: RichTerm {
+                            term: record,
+                            pos: pos2,
+                        },
+                    });
+                };
+
+                let Term::Lbl(ref label) = &*label else {
+                    return Err(EvalError::NAryPrimopTypeError {
+                        primop: String::from("label_with_related_field"),
+                        expected: String::from("Label"),
+                        arg_number: 3,
+                        arg_pos: label_pos,
+                        arg_evaluated: RichTerm {
+                            term: label,
+                            pos: pos3,
+                        },
+                    });
+                };
+
+                let Some((id, field_def)) = data.fields.get_key_value(&LocIdent::from(field))
+                else {
+                    return Err(EvalError::FieldMissing(
+                        field.to_string(),
+                        String::from("label_with_related_field"),
+                        RichTerm {
+                            term: record.clone(),
+                            pos: pos2,
+                        },
+                        pos_op,
+                    ));
+                };
+
+                // We prefer to point to the name of the field in its definition, and fall back to
+                // its value otherwise.
+                let span = id.pos.into_opt().or_else(|| {
+                    field_def
+                        .value
+                        .as_ref()
+                        .and_then(|value| value.pos.into_opt())
+                });
+
+                let label = match span {
+                    Some(span) => label
+                        .clone()
+                        .append_diagnostic_related(span, format!("`{field}` is defined here")),
+                    None => label.clone(),
+                };
+
+                Ok(Closure::atomic_closure(RichTerm::new(
+                    Term::Lbl(label),
+                    pos3.into_inherited(),
+                )))
+            }
         }
     }
 }
@@ -2856,6 +3976,9 @@ impl RecPriority {
             }
         };
 
+        record.invariants =
+            std::mem::take(&mut record.invariants).closurize(cache, &mut new_env, env.clone());
+
         record.fields = record
             .fields
             .into_iter()
@@ -2948,6 +4071,115 @@ impl RecPriority {
     }
 }
 
+/// Compute the natural logarithm of a positive number as a 64 bit float. Unlike converting the
+/// number to a float first, this works for numbers whose magnitude is outside of the range of
+/// floats, such as `1e400` or `1e-400`.
+fn ln(n: &Number) -> f64 {
+    fn ln_natural(n: &Natural) -> f64 {
+        // We only keep the 64 most significant bits, which is more than the precision of a float,
+        // and account for the dropped bits separately.
+        let shift = n.significant_bits().saturating_sub(64);
+        f64::rounding_from(&(n >> shift), RoundingMode::Nearest).ln()
+            + (shift as f64) * std::f64::consts::LN_2
+    }
+
+    ln_natural(n.numerator_ref()) - ln_natural(n.denominator_ref())
+}
+
+/// The maximum number of decimal places accepted by [BinaryOp::NumToStrFixed]. The conversion
+/// computes `10^digits`, which must stay reasonably small.
+const MAX_FIXED_DIGITS: u64 = 100;
+
+/// Render a number in decimal notation with exactly `digits` decimal places. The number is first
+/// rounded to the nearest multiple of `10^-digits`, rounding half away from zero. This is done on
+/// the exact rational representation, so the result doesn't suffer from floating-point errors:
+/// for example, `1.005` is rendered as `1.01` with two decimal places.
+fn to_str_fixed(n: &Number, digits: u64) -> String {
+    let scale = Number::from(Integer::from(10).pow(digits));
+    let scaled = (n * scale).abs() + Number::from_signeds(1, 2);
+    let rounded = Integer::rounding_from(scaled, RoundingMode::Floor).to_string();
+
+    // Left pad with zeros so that there's at least one digit before the decimal point.
+    let digits = usize::try_from(digits).unwrap_or(usize::MAX);
+    let padded = format!("{rounded:0>width$}", width = digits.saturating_add(1));
+    let (int_part, frac_part) = padded.split_at(padded.len() - digits);
+    // We don't print a minus sign when the number is rounded to zero.
+    let sign = if *n < Number::ZERO && rounded.chars().any(|c| c != '0') {
+        "-"
+    } else {
+        ""
+    };
+
+    if digits == 0 {
+        format!("{sign}{int_part}")
+    } else {
+        format!("{sign}{int_part}.{frac_part}")
+    }
+}
+
+/// Encode the UTF-8 bytes of a string. Used by [UnaryOp::StrEncode].
+fn encode(encoding: Encoding, s: &str) -> String {
+    use base64::engine::general_purpose;
+
+    match encoding {
+        Encoding::Base64 => general_purpose::STANDARD.encode(s),
+        Encoding::Base64Url => general_purpose::URL_SAFE.encode(s),
+        Encoding::Hex => s.bytes().map(|b| format!("{b:02x}")).collect(),
+        Encoding::Percent => {
+            percent_encoding::utf8_percent_encode(s, PERCENT_ENCODE_SET).to_string()
+        }
+    }
+}
+
+/// Decode a string, the inverse of [encode]. Used by [UnaryOp::StrDecode]. Return an error
+/// message if the input isn't valid for the given encoding or if the decoded bytes aren't valid
+/// UTF-8.
+fn decode(encoding: Encoding, s: &str) -> Result<String, String> {
+    use base64::engine::general_purpose;
+
+    let bytes = match encoding {
+        Encoding::Base64 => general_purpose::STANDARD
+            .decode(s)
+            .map_err(|err| err.to_string())?,
+        Encoding::Base64Url => general_purpose::URL_SAFE
+            .decode(s)
+            .map_err(|err| err.to_string())?,
+        Encoding::Hex => {
+            if s.len() % 2 != 0 {
+                return Err(String::from("odd number of hexadecimal digits"));
+            }
+
+            (0..s.len())
+                .step_by(2)
+                .map(|i| {
+                    // `from_str_radix` accepts a leading sign, so we check the digits first.
+                    s.get(i..i + 2)
+                        .filter(|digits| digits.bytes().all(|b| b.is_ascii_hexdigit()))
+                        .and_then(|digits| u8::from_str_radix(digits, 16).ok())
+                        .ok_or_else(|| format!("invalid hexadecimal digits at offset {i}"))
+                })
+                .collect::<Result<Vec<u8>, _>>()?
+        }
+        Encoding::Percent => percent_encoding::percent_decode_str(s).collect(),
+    };
+
+    String::from_utf8(bytes).map_err(|err| format!("the decoded bytes aren't valid UTF-8 ({err})"))
+}
+
+/// Check that a string is valid for the given format. Used by [UnaryOp::StrCheckFormat]. Return
+/// an error explaining what part of the string is invalid otherwise.
+fn check_format(format: StringFormat, s: &str) -> Result<(), String> {
+    match format {
+        StringFormat::SemVer => semver::parse(s).map(|_| ()),
+        StringFormat::SemVerReq => semver::parse_req(s).map(|_| ()),
+        StringFormat::Ipv4 => net::parse_ipv4(s).map(|_| ()),
+        StringFormat::Ipv6 => net::parse_ipv6(s).map(|_| ()),
+        StringFormat::Cidr => net::parse_cidr(s).map(|_| ()),
+        StringFormat::Hostname => net::check_hostname(s),
+        StringFormat::Url => net::parse_url(s).map(|_| ()),
+    }
+}
+
 /// Compute the equality of two terms, represented as closures.
 ///
 /// # Parameters
@@ -3266,7 +4498,7 @@ mod tests {
 
         vm.stack.push_op_cont(cont, 0, TermPos::None);
 
-        clos = vm.continuate_operation(clos).unwrap();
+        clos = vm.continuate_operation(clos, &Environment::new()).unwrap();
 
         assert_eq!(
             clos,
@@ -3296,7 +4528,7 @@ mod tests {
         let mut vm = VirtualMachine::new(DummyResolver {}, std::io::sink());
         vm.stack.push_op_cont(cont, 0, TermPos::None);
 
-        clos = vm.continuate_operation(clos).unwrap();
+        clos = vm.continuate_operation(clos, &Environment::new()).unwrap();
 
         assert_eq!(
             clos,
@@ -3345,7 +4577,7 @@ mod tests {
         };
         vm.stack.push_op_cont(cont, 0, TermPos::None);
 
-        clos = vm.continuate_operation(clos).unwrap();
+        clos = vm.continuate_operation(clos, &Environment::new()).unwrap();
 
         assert_eq!(
             clos,
@@ -0,0 +1,80 @@
+//! Cache of compiled regular expressions.
+//!
+//! Regex primops such as `%str_is_match%` need a compiled pattern each time they are evaluated.
+//! When a pattern is used inside a function which is called many times, for example in the body
+//! of an `std.array.map`, compiling it at each call would dominate the evaluation time. The
+//! virtual machine thus keeps the compiled regexes around, keyed by their source pattern.
+use std::collections::HashMap;
+
+use crate::term::CompiledRegex;
+
+/// The maximum number of patterns kept in the cache. When the limit is reached, the cache is
+/// cleared. Programs rarely use more than a handful of distinct patterns, so there's no need for
+/// a fancier eviction strategy.
+const MAX_ENTRIES: usize = 256;
+
+#[derive(Debug, Default)]
+pub struct RegexCache {
+    regexes: HashMap<String, CompiledRegex>,
+}
+
+impl RegexCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return the compiled regex for `pattern`, compiling it first if it isn't in the cache.
+    /// Compiled regexes are reference-counted, so the returned value is cheap to clone.
+    pub fn get_or_compile(&mut self, pattern: &str) -> Result<CompiledRegex, regex::Error> {
+        if let Some(regex) = self.regexes.get(pattern) {
+            return Ok(regex.clone());
+        }
+
+        let regex = CompiledRegex(regex::Regex::new(pattern)?);
+
+        if self.regexes.len() >= MAX_ENTRIES {
+            self.regexes.clear();
+        }
+
+        self.regexes.insert(pattern.to_owned(), regex.clone());
+        Ok(regex)
+    }
+
+    /// The number of compiled regexes currently in the cache.
+    pub fn len(&self) -> usize {
+        self.regexes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.regexes.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reuses_compiled_regexes() {
+        let mut cache = RegexCache::new();
+
+        cache.get_or_compile("a+").unwrap();
+        cache.get_or_compile("a+").unwrap();
+        cache.get_or_compile("b+").unwrap();
+        assert_eq!(cache.len(), 2);
+
+        assert!(cache.get_or_compile("(").is_err());
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn bounded_size() {
+        let mut cache = RegexCache::new();
+
+        for i in 0..=MAX_ENTRIES {
+            cache.get_or_compile(&format!("a{{{i}}}")).unwrap();
+        }
+
+        assert!(cache.len() <= MAX_ENTRIES);
+    }
+}
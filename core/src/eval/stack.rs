@@ -5,11 +5,11 @@ use super::cache::{Cache, CacheIndex};
 use super::operation::OperationCont;
 use crate::eval::{Closure, Environment, IdentKind};
 use crate::position::TermPos;
-use crate::term::{BindingType, RichTerm, StrChunk};
+use crate::term::{string::NickelString, BindingType, RichTerm, StrChunk};
 
 pub struct StrAccData {
     /// The accumulator.
-    pub acc: String,
+    pub acc: NickelString,
     /// The common environment of chunks.
     pub env: Environment,
     /// The indentation level of the chunk currently being evaluated.
@@ -139,6 +139,11 @@ impl<C: Cache> Stack<C> {
         }
     }
 
+    /// The total number of items on the stack.
+    pub fn depth(&self) -> usize {
+        self.0.len()
+    }
+
     /// Count the number of arguments at the top of the stack.
     pub fn count_args(&self) -> usize {
         Stack::count(self, Marker::is_arg)
@@ -341,3 +341,112 @@ fn substitution() {
         parse("match {'x => [1, 1], 'y => (if false then 1 else \"Glob2\"), 'z => {id = true, other = false}} true").unwrap()
     );
 }
+
+/// Evaluate the source `s` without import support, with the given resource limits.
+fn eval_with_limits(s: &str, limits: Limits) -> Result<Term, EvalError> {
+    let mut vm = VirtualMachine::<_, CacheImpl>::new(DummyResolver {}, std::io::sink());
+    vm.set_limits(limits);
+    vm.eval(parse(s).unwrap(), &Environment::new())
+        .map(Term::from)
+}
+
+#[test]
+fn resource_limits() {
+    use assert_matches::assert_matches;
+    use std::time::Duration;
+
+    let looping = "let rec f = fun x => f x in f 0";
+    let recursive = "let rec f = fun x => 1 + f x in f 0";
+    let growing = r#"let rec f = fun s => f (s ++ s) in f "ab""#;
+
+    assert_matches!(
+        eval_with_limits(
+            looping,
+            Limits {
+                max_steps: Some(1000),
+                ..Default::default()
+            }
+        ),
+        Err(EvalError::ResourceLimitExceeded {
+            limit: Limit::Steps(1000),
+            ..
+        })
+    );
+    assert_matches!(
+        eval_with_limits(
+            looping,
+            Limits {
+                timeout: Some(Duration::from_millis(10)),
+                ..Default::default()
+            }
+        ),
+        Err(EvalError::ResourceLimitExceeded {
+            limit: Limit::Timeout(_),
+            ..
+        })
+    );
+    assert_matches!(
+        eval_with_limits(
+            recursive,
+            Limits {
+                max_stack_depth: Some(100),
+                ..Default::default()
+            }
+        ),
+        Err(EvalError::ResourceLimitExceeded {
+            limit: Limit::StackDepth(100),
+            ..
+        })
+    );
+    assert_matches!(
+        eval_with_limits(
+            growing,
+            Limits {
+                max_size: Some(1000),
+                ..Default::default()
+            }
+        ),
+        Err(EvalError::ResourceLimitExceeded {
+            limit: Limit::Size(1000),
+            ..
+        })
+    );
+
+    let limits = Limits {
+        max_steps: Some(1000),
+        max_stack_depth: Some(100),
+        max_size: Some(1000),
+        timeout: Some(Duration::from_secs(60)),
+    };
+    assert_eq!(
+        eval_with_limits(r#""a" ++ "b""#, limits),
+        Ok(Term::Str("ab".into()))
+    );
+}
+
+#[test]
+fn size_limit_is_checked_before_allocating() {
+    use assert_matches::assert_matches;
+
+    let long = "a".repeat(600);
+    let limits = Limits {
+        max_size: Some(1000),
+        ..Default::default()
+    };
+
+    for src in [
+        format!(r#"%str_replace% "{long}" "a" "aa""#),
+        format!(r#"%str_replace_regex% "{long}" "a+" "{long}{long}""#),
+        format!(r#"let s = "{long}" in "%{{s}}%{{s}}""#),
+        format!(r#"%serialize% 'Json ["{long}", "{long}"]"#),
+    ] {
+        assert_matches!(
+            eval_with_limits(&src, limits.clone()),
+            Err(EvalError::ResourceLimitExceeded {
+                limit: Limit::Size(1000),
+                ..
+            }),
+            "{src}"
+        );
+    }
+}
@@ -0,0 +1,126 @@
+//! Hash functions for the `std.digest` module and the deprecated `std.hash` function.
+//!
+//! Digests are rendered as lowercase hexadecimal strings, which is what most tools expect (e.g.
+//! `sha256sum`, or checksum annotations in Kubernetes manifests).
+use hmac::{Hmac, Mac};
+use md5::digest::Digest;
+
+/// The hash algorithms supported by `%hash%` and `%hash_value%`, identified by an enum tag.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HashAlgorithm {
+    Md5,
+    Sha1,
+    Sha256,
+    Sha512,
+    Sha3_256,
+    Sha3_512,
+    Blake3,
+    Crc32,
+}
+
+impl HashAlgorithm {
+    /// The type of the enum tags accepted by [HashAlgorithm::from_tag], used in error messages.
+    pub const TAGS: &'static str =
+        "[| 'Md5, 'Sha1, 'Sha256, 'Sha512, 'Sha3_256, 'Sha3_512, 'Blake3, 'Crc32 |]";
+
+    pub fn from_tag(tag: &str) -> Option<Self> {
+        match tag {
+            "Md5" => Some(HashAlgorithm::Md5),
+            "Sha1" => Some(HashAlgorithm::Sha1),
+            "Sha256" => Some(HashAlgorithm::Sha256),
+            "Sha512" => Some(HashAlgorithm::Sha512),
+            "Sha3_256" => Some(HashAlgorithm::Sha3_256),
+            "Sha3_512" => Some(HashAlgorithm::Sha3_512),
+            "Blake3" => Some(HashAlgorithm::Blake3),
+            "Crc32" => Some(HashAlgorithm::Crc32),
+            _ => None,
+        }
+    }
+
+    /// Hash `data` and return the digest as a hexadecimal string.
+    pub fn digest(self, data: &[u8]) -> String {
+        match self {
+            HashAlgorithm::Md5 => format!("{:x}", md5::Md5::digest(data)),
+            HashAlgorithm::Sha1 => format!("{:x}", sha1::Sha1::digest(data)),
+            HashAlgorithm::Sha256 => format!("{:x}", sha2::Sha256::digest(data)),
+            HashAlgorithm::Sha512 => format!("{:x}", sha2::Sha512::digest(data)),
+            HashAlgorithm::Sha3_256 => format!("{:x}", sha3::Sha3_256::digest(data)),
+            HashAlgorithm::Sha3_512 => format!("{:x}", sha3::Sha3_512::digest(data)),
+            HashAlgorithm::Blake3 => blake3::hash(data).to_hex().to_string(),
+            HashAlgorithm::Crc32 => format!("{:08x}", crc32fast::hash(data)),
+        }
+    }
+}
+
+/// The hash algorithms supported by `%hmac%`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HmacAlgorithm {
+    Sha256,
+    Sha512,
+}
+
+impl HmacAlgorithm {
+    /// The type of the enum tags accepted by [HmacAlgorithm::from_tag], used in error messages.
+    pub const TAGS: &'static str = "[| 'Sha256, 'Sha512 |]";
+
+    pub fn from_tag(tag: &str) -> Option<Self> {
+        match tag {
+            "Sha256" => Some(HmacAlgorithm::Sha256),
+            "Sha512" => Some(HmacAlgorithm::Sha512),
+            _ => None,
+        }
+    }
+
+    /// Compute the HMAC of `message` with `key`, and return it as a hexadecimal string.
+    pub fn mac(self, key: &[u8], message: &[u8]) -> String {
+        // unwrap(): HMAC accepts keys of any length, so `new_from_slice` can't fail.
+        match self {
+            HmacAlgorithm::Sha256 => {
+                let mut mac = Hmac::<sha2::Sha256>::new_from_slice(key).unwrap();
+                mac.update(message);
+                format!("{:x}", mac.finalize().into_bytes())
+            }
+            HmacAlgorithm::Sha512 => {
+                let mut mac = Hmac::<sha2::Sha512>::new_from_slice(key).unwrap();
+                mac.update(message);
+                format!("{:x}", mac.finalize().into_bytes())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn digests() {
+        assert_eq!(
+            HashAlgorithm::Md5.digest(b"hunter2"),
+            "2ab96390c7dbe3439de74d0c9b0b1767"
+        );
+        assert_eq!(
+            HashAlgorithm::Sha3_256.digest(b""),
+            "a7ffc6f8bf1ed76651c14756a061d662f580ff4de43b49fa82d80a4b80f8434a"
+        );
+        assert_eq!(
+            HashAlgorithm::Blake3.digest(b""),
+            "af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262"
+        );
+        assert_eq!(HashAlgorithm::Crc32.digest(b"123456789"), "cbf43926");
+        assert_eq!(HashAlgorithm::Crc32.digest(b""), "00000000");
+    }
+
+    #[test]
+    fn hmac() {
+        // Test case 2 of RFC 4231.
+        assert_eq!(
+            HmacAlgorithm::Sha256.mac(b"Jefe", b"what do ya want for nothing?"),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+        assert_eq!(
+            HmacAlgorithm::Sha512.mac(b"Jefe", b"what do ya want for nothing?"),
+            "164b7a7bfcf819e2e395fbe73b56e0a387bd64222e831fd610270cd7ea2505549758bf75c05a994a6d034f65f8f0e6fdcaeab1a34d4a6b4b636e070a38bce737"
+        );
+    }
+}
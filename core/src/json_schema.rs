@@ -0,0 +1,773 @@
+//! Conversion between JSON Schema documents and Nickel record contracts.
+//!
+//! [import] compiles a JSON Schema (as a [serde_json::Value]) to the source of a Nickel contract
+//! module: a Nickel expression evaluating to a contract that can then be imported and applied to
+//! a configuration. [export] goes the other way around, and converts a record contract, as
+//! returned by [crate::program::Program::eval_record_spine], to a JSON Schema.
+//!
+//! Both conversions are best effort. JSON Schema and Nickel contracts don't have the same
+//! expressive power: `oneOf` is compiled to `std.contract.any_of`, which doesn't enforce that
+//! exactly one alternative matches, and a custom contract that isn't a record contract or a known
+//! standard library contract can't be exported to JSON Schema. Keywords that aren't understood
+//! are ignored.
+use std::fmt;
+
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+use crate::{
+    identifier::LocIdent,
+    pretty::{escape, ident_quoted},
+    term::{
+        record::{Field, RecordData},
+        MergePriority, RichTerm, Term,
+    },
+    typ::{EnumRowsIteratorItem, RecordRowsIteratorItem, Type, TypeF},
+};
+
+/// The indentation used for each nesting level of the generated Nickel source.
+const INDENT: &str = "  ";
+
+/// An error occurring while converting from or to JSON Schema.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum JsonSchemaError {
+    /// The JSON document isn't a valid schema, or uses a keyword with an unexpected value.
+    InvalidSchema(String),
+    /// A `$ref` pointing somewhere else than to the `definitions` or `$defs` of the root schema.
+    UnsupportedRef(String),
+    /// The value to export isn't a record contract.
+    NotARecordContract,
+    /// The record contract uses a type which has no JSON Schema counterpart.
+    UnsupportedType(String),
+}
+
+impl fmt::Display for JsonSchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            JsonSchemaError::InvalidSchema(msg) => write!(f, "invalid JSON schema: {msg}"),
+            JsonSchemaError::UnsupportedRef(reference) => write!(
+                f,
+                "unsupported reference `{reference}`: only references to the `definitions` or \
+                `$defs` of the root schema are supported"
+            ),
+            JsonSchemaError::NotARecordContract => {
+                write!(f, "only record contracts can be exported to JSON Schema")
+            }
+            JsonSchemaError::UnsupportedType(typ) => {
+                write!(f, "the type `{typ}` can't be converted to JSON Schema")
+            }
+        }
+    }
+}
+
+impl std::error::Error for JsonSchemaError {}
+
+/// Compile a JSON Schema to the source of a Nickel expression evaluating to the corresponding
+/// contract.
+///
+/// Definitions of the root schema (under `definitions` or `$defs`) are bound by a recursive
+/// `let`, such that references to them - including recursive ones - are supported.
+pub fn import(schema: &Value) -> Result<String, JsonSchemaError> {
+    let definitions: Vec<(&String, &Value)> = ["definitions", "$defs"]
+        .into_iter()
+        .filter_map(|key| schema.get(key).and_then(Value::as_object))
+        .flat_map(Map::iter)
+        .collect();
+
+    if definitions.is_empty() {
+        return Ok(format!("{}\n", contract(schema, 0)?));
+    }
+
+    let mut out = String::from("let rec defs = {\n");
+
+    for (name, def) in definitions {
+        out.push_str(&format!(
+            "{INDENT}{} = {},\n",
+            quote_ident(name),
+            contract(def, 1)?
+        ));
+    }
+
+    out.push_str(&format!("}}\nin\n{}\n", contract(schema, 0)?));
+    Ok(out)
+}
+
+/// Compile a schema to a single contract expression.
+fn contract(schema: &Value, depth: usize) -> Result<String, JsonSchemaError> {
+    let mut contracts = contracts(schema, depth)?;
+
+    Ok(match contracts.len() {
+        0 => String::from("Dyn"),
+        1 => contracts.pop().unwrap(),
+        _ => list("std.contract.all_of", &contracts, depth),
+    })
+}
+
+/// Compile a schema to a list of contract expressions which must all be satisfied. The list is
+/// empty if the schema doesn't impose any constraint.
+fn contracts(schema: &Value, depth: usize) -> Result<Vec<String>, JsonSchemaError> {
+    let obj = match schema {
+        Value::Bool(true) => return Ok(Vec::new()),
+        Value::Bool(false) => {
+            return Ok(vec![String::from(
+                "std.contract.from_predicate (fun _value => false)",
+            )])
+        }
+        Value::Object(obj) => obj,
+        _ => {
+            return Err(JsonSchemaError::InvalidSchema(format!(
+                "expected an object or a boolean, got `{schema}`"
+            )))
+        }
+    };
+
+    // Following draft 7, the other keywords are ignored when `$ref` is present.
+    if let Some(reference) = obj.get("$ref") {
+        return Ok(vec![reference_contract(reference)?]);
+    }
+
+    // A string enumeration is compiled to an enum type, which fully determines the set of valid
+    // values. We thus skip the other keywords, which would otherwise constrain the value to be a
+    // string and reject the enum tags.
+    if let Some(values) = obj.get("enum") {
+        let values = values.as_array().ok_or_else(|| {
+            JsonSchemaError::InvalidSchema(String::from("`enum` must be an array"))
+        })?;
+
+        if let Some(tags) = values
+            .iter()
+            .map(|v| v.as_str().map(|tag| format!("'{}", quote_ident(tag))))
+            .collect::<Option<Vec<_>>>()
+        {
+            return Ok(vec![format!("[| {} |]", tags.join(", "))]);
+        }
+
+        let alternatives = values
+            .iter()
+            .map(|v| Ok(format!("std.contract.Equal {}", atom(literal(v)?))))
+            .collect::<Result<Vec<_>, _>>()?;
+        return Ok(vec![list("std.contract.any_of", &alternatives, depth)]);
+    }
+
+    let mut result = Vec::new();
+
+    if let Some(value) = obj.get("const") {
+        result.push(format!("std.contract.Equal {}", atom(literal(value)?)));
+    }
+
+    match obj.get("type") {
+        Some(Value::String(typ)) => result.push(typed_contract(typ, obj, depth)?),
+        Some(Value::Array(types)) => {
+            let alternatives = types
+                .iter()
+                .map(|typ| match typ {
+                    Value::String(typ) => typed_contract(typ, obj, depth + 1),
+                    _ => Err(JsonSchemaError::InvalidSchema(format!(
+                        "expected a type name, got `{typ}`"
+                    ))),
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            result.push(list("std.contract.any_of", &alternatives, depth));
+        }
+        Some(typ) => {
+            return Err(JsonSchemaError::InvalidSchema(format!(
+                "expected a type name or an array of type names, got `{typ}`"
+            )))
+        }
+        None if obj.contains_key("properties") || obj.contains_key("additionalProperties") => {
+            result.push(object_contract(obj, depth)?)
+        }
+        None => (),
+    }
+
+    result.extend(constraints(obj)?);
+
+    for (keyword, combinator) in [
+        ("allOf", "std.contract.all_of"),
+        ("anyOf", "std.contract.any_of"),
+        ("oneOf", "std.contract.any_of"),
+    ] {
+        if let Some(schemas) = obj.get(keyword) {
+            let schemas = schemas.as_array().ok_or_else(|| {
+                JsonSchemaError::InvalidSchema(format!("`{keyword}` must be an array"))
+            })?;
+            let alternatives = schemas
+                .iter()
+                .map(|schema| contract(schema, depth + 1))
+                .collect::<Result<Vec<_>, _>>()?;
+            result.push(list(combinator, &alternatives, depth));
+        }
+    }
+
+    if let Some(schema) = obj.get("not") {
+        result.push(format!(
+            "std.contract.not {}",
+            atom(contract(schema, depth)?)
+        ));
+    }
+
+    Ok(result)
+}
+
+/// Compile the contract corresponding to the `type` keyword.
+fn typed_contract(
+    typ: &str,
+    obj: &Map<String, Value>,
+    depth: usize,
+) -> Result<String, JsonSchemaError> {
+    match typ {
+        "string" => Ok(String::from("String")),
+        "number" => Ok(String::from("Number")),
+        "integer" => Ok(String::from("std.number.Integer")),
+        "boolean" => Ok(String::from("Bool")),
+        "null" => Ok(String::from("std.contract.Equal null")),
+        "array" => match obj.get("items") {
+            Some(items) => Ok(format!("Array {}", atom(contract(items, depth)?))),
+            None => Ok(String::from("Array Dyn")),
+        },
+        "object" => object_contract(obj, depth),
+        _ => Err(JsonSchemaError::InvalidSchema(format!(
+            "unknown type `{typ}`"
+        ))),
+    }
+}
+
+/// Compile an object schema to a record contract, or to a dictionary contract if there are no
+/// `properties` but `additionalProperties` is a schema.
+fn object_contract(obj: &Map<String, Value>, depth: usize) -> Result<String, JsonSchemaError> {
+    let properties = match obj.get("properties") {
+        Some(Value::Object(properties)) => properties,
+        Some(_) => {
+            return Err(JsonSchemaError::InvalidSchema(String::from(
+                "`properties` must be an object",
+            )))
+        }
+        None => {
+            return match obj.get("additionalProperties") {
+                Some(Value::Bool(false)) => Ok(String::from("{}")),
+                Some(schema @ Value::Object(_)) => {
+                    Ok(format!("{{ _ | {} }}", contract(schema, depth)?))
+                }
+                _ => Ok(String::from("{ _ | Dyn }")),
+            }
+        }
+    };
+
+    let required: Vec<&str> = match obj.get("required") {
+        Some(Value::Array(required)) => required.iter().filter_map(Value::as_str).collect(),
+        Some(_) => {
+            return Err(JsonSchemaError::InvalidSchema(String::from(
+                "`required` must be an array",
+            )))
+        }
+        None => Vec::new(),
+    };
+
+    let field_indent = INDENT.repeat(depth + 1);
+    let mut fields = Vec::new();
+
+    for (name, schema) in properties {
+        let mut field = format!("{field_indent}{}", quote_ident(name));
+
+        // A field with a default value is always defined, so it doesn't need to be optional.
+        if !required.contains(&name.as_str()) && schema.get("default").is_none() {
+            field.push_str(" | optional");
+        }
+
+        for ctr in contracts(schema, depth + 1)? {
+            field.push_str(&format!(" | {ctr}"));
+        }
+
+        if let Some(doc) = documentation(schema) {
+            field.push_str(&format!(" | doc \"{}\"", escape(&doc)));
+        }
+
+        if let Some(default) = schema.get("default") {
+            field.push_str(&format!(" | default = {}", literal(default)?));
+        }
+
+        fields.push(field);
+    }
+
+    // JSON Schema allows additional properties by default.
+    if !matches!(obj.get("additionalProperties"), Some(Value::Bool(false))) {
+        fields.push(format!("{field_indent}.."));
+    }
+
+    Ok(format!(
+        "{{\n{}\n{}}}",
+        fields.join(",\n"),
+        INDENT.repeat(depth)
+    ))
+}
+
+/// Compile the validation keywords which don't depend on the type of the value to predicates.
+///
+/// As in JSON Schema, each keyword only constrains the values of the type it applies to: for
+/// example, `minLength` accepts any value which isn't a string.
+fn constraints(obj: &Map<String, Value>) -> Result<Vec<String>, JsonSchemaError> {
+    let mut result = Vec::new();
+
+    if let Some(pattern) = obj.get("pattern") {
+        let pattern = pattern.as_str().ok_or_else(|| {
+            JsonSchemaError::InvalidSchema(String::from("`pattern` must be a string"))
+        })?;
+        result.push(format!(
+            "std.contract.from_predicate (fun x => !(std.is_string x) || std.string.is_match \"{}\" x)",
+            escape(pattern)
+        ));
+    }
+
+    for (keyword, guard, measure, op) in [
+        ("minLength", "std.is_string", "std.string.length", ">="),
+        ("maxLength", "std.is_string", "std.string.length", "<="),
+        ("minItems", "std.is_array", "std.array.length", ">="),
+        ("maxItems", "std.is_array", "std.array.length", "<="),
+        ("minimum", "std.is_number", "", ">="),
+        ("maximum", "std.is_number", "", "<="),
+        ("exclusiveMinimum", "std.is_number", "", ">"),
+        ("exclusiveMaximum", "std.is_number", "", "<"),
+    ] {
+        if let Some(bound) = obj.get(keyword) {
+            if !bound.is_number() {
+                return Err(JsonSchemaError::InvalidSchema(format!(
+                    "`{keyword}` must be a number"
+                )));
+            }
+
+            let measured = if measure.is_empty() {
+                String::from("x")
+            } else {
+                format!("{measure} x")
+            };
+
+            result.push(format!(
+                "std.contract.from_predicate (fun x => !({guard} x) || {measured} {op} {})",
+                atom(bound.to_string())
+            ));
+        }
+    }
+
+    Ok(result)
+}
+
+/// Compile a `$ref` to a reference to the corresponding definition.
+fn reference_contract(reference: &Value) -> Result<String, JsonSchemaError> {
+    let reference = reference
+        .as_str()
+        .ok_or_else(|| JsonSchemaError::InvalidSchema(String::from("`$ref` must be a string")))?;
+
+    let name = reference
+        .strip_prefix("#/definitions/")
+        .or_else(|| reference.strip_prefix("#/$defs/"))
+        .filter(|name| !name.contains('/'))
+        .ok_or_else(|| JsonSchemaError::UnsupportedRef(reference.to_owned()))?;
+
+    // Unescape the JSON pointer syntax (RFC 6901).
+    let name = name.replace("~1", "/").replace("~0", "~");
+    Ok(format!("defs.{}", quote_ident(&name)))
+}
+
+/// Build the documentation of a field from the `title` and the `description` of its schema.
+fn documentation(schema: &Value) -> Option<String> {
+    let parts: Vec<&str> = ["title", "description"]
+        .into_iter()
+        .filter_map(|key| schema.get(key).and_then(Value::as_str))
+        .collect();
+
+    (!parts.is_empty()).then(|| parts.join("\n\n"))
+}
+
+/// Render a JSON value as a Nickel expression.
+fn literal(value: &Value) -> Result<String, JsonSchemaError> {
+    RichTerm::deserialize(value.clone())
+        .map(|rt| rt.to_string())
+        .map_err(|err| JsonSchemaError::InvalidSchema(err.to_string()))
+}
+
+/// Render an application of `combinator` to a list of contracts, with one contract per line.
+fn list(combinator: &str, contracts: &[String], depth: usize) -> String {
+    let indent = INDENT.repeat(depth + 1);
+    let elts: Vec<String> = contracts.iter().map(|c| format!("{indent}{c}")).collect();
+
+    format!(
+        "{combinator} [\n{},\n{}]",
+        elts.join(",\n"),
+        INDENT.repeat(depth)
+    )
+}
+
+/// Wrap an expression in parentheses if it can't be used as is in argument position.
+fn atom(expr: String) -> String {
+    let is_atom = expr.starts_with('{')
+        || expr.starts_with('[')
+        || !expr.contains(|c: char| c.is_whitespace() || c == '-');
+
+    if is_atom {
+        expr
+    } else {
+        format!("({expr})")
+    }
+}
+
+fn quote_ident(name: &str) -> String {
+    ident_quoted(&LocIdent::from(name))
+}
+
+/// Convert a record contract to a JSON Schema.
+///
+/// `term` is expected to have been evaluated by [crate::program::Program::eval_record_spine], so
+/// that pending contracts which are record contracts themselves are evaluated and can be
+/// converted recursively.
+pub fn export(term: &RichTerm) -> Result<Value, JsonSchemaError> {
+    match term.as_ref() {
+        Term::Record(data) => {
+            let mut schema = Map::new();
+            schema.insert(
+                String::from("$schema"),
+                Value::from("http://json-schema.org/draft-07/schema#"),
+            );
+            schema.extend(record_schema(data)?);
+            Ok(Value::Object(schema))
+        }
+        _ => Err(JsonSchemaError::NotARecordContract),
+    }
+}
+
+fn record_schema(data: &RecordData) -> Result<Map<String, Value>, JsonSchemaError> {
+    let mut properties = Map::new();
+    let mut required = Vec::new();
+
+    for (id, field) in data.fields.iter() {
+        if field.metadata.not_exported {
+            continue;
+        }
+
+        if !field.metadata.opt && !is_default(field) {
+            required.push(Value::from(id.label()));
+        }
+
+        properties.insert(id.label().to_owned(), Value::Object(field_schema(field)?));
+    }
+
+    let mut schema = Map::new();
+    schema.insert(String::from("type"), Value::from("object"));
+    schema.insert(String::from("properties"), Value::Object(properties));
+
+    if !required.is_empty() {
+        schema.insert(String::from("required"), Value::Array(required));
+    }
+
+    if !data.attrs.open {
+        schema.insert(String::from("additionalProperties"), Value::Bool(false));
+    }
+
+    Ok(schema)
+}
+
+fn field_schema(field: &Field) -> Result<Map<String, Value>, JsonSchemaError> {
+    let mut schemas = field
+        .pending_contracts
+        .iter()
+        .map(|ctr| match &ctr.label.typ.typ {
+            // For a contract which isn't a type, the evaluated contract might be a record
+            // contract, which we can convert.
+            TypeF::Flat(_) => match ctr.contract.as_ref() {
+                Term::Record(data) => record_schema(data),
+                _ => Ok(flat_schema(&ctr.label.typ)),
+            },
+            _ => type_schema(&ctr.label.typ),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // A field whose value is a record (and not a default value) is a nested record contract.
+    if let Some(value) = &field.value {
+        if let (Term::Record(data), false) = (value.as_ref(), is_default(field)) {
+            schemas.push(record_schema(data)?);
+        }
+    }
+
+    let mut schema = match schemas.len() {
+        0 => Map::new(),
+        1 => schemas.pop().unwrap(),
+        _ => {
+            let mut all_of = Map::new();
+            all_of.insert(
+                String::from("allOf"),
+                Value::Array(schemas.into_iter().map(Value::Object).collect()),
+            );
+            all_of
+        }
+    };
+
+    if let Some(doc) = &field.metadata.doc {
+        schema.insert(String::from("description"), Value::from(doc.as_str()));
+    }
+
+    if let (true, Some(value)) = (is_default(field), &field.value) {
+        if let Ok(default) = serde_json::to_value(value) {
+            schema.insert(String::from("default"), default);
+        }
+    }
+
+    Ok(schema)
+}
+
+fn is_default(field: &Field) -> bool {
+    field.value.is_some() && matches!(field.metadata.priority, MergePriority::Bottom)
+}
+
+/// Convert a static type to a JSON Schema.
+fn type_schema(typ: &Type) -> Result<Map<String, Value>, JsonSchemaError> {
+    let mut schema = Map::new();
+
+    match &typ.typ {
+        TypeF::Dyn => (),
+        TypeF::Number => {
+            schema.insert(String::from("type"), Value::from("number"));
+        }
+        TypeF::Bool => {
+            schema.insert(String::from("type"), Value::from("boolean"));
+        }
+        TypeF::String => {
+            schema.insert(String::from("type"), Value::from("string"));
+        }
+        TypeF::Array(elts) => {
+            schema.insert(String::from("type"), Value::from("array"));
+            schema.insert(String::from("items"), Value::Object(type_schema(elts)?));
+        }
+        TypeF::Dict { type_fields, .. } => {
+            schema.insert(String::from("type"), Value::from("object"));
+            schema.insert(
+                String::from("additionalProperties"),
+                Value::Object(type_schema(type_fields)?),
+            );
+        }
+        TypeF::Enum(erows) => {
+            let tags = erows
+                .iter()
+                .map(|item| match item {
+                    EnumRowsIteratorItem::Row(id) => Ok(Value::from(id.label())),
+                    EnumRowsIteratorItem::TailVar(_) => {
+                        Err(JsonSchemaError::UnsupportedType(typ.to_string()))
+                    }
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            schema.insert(String::from("type"), Value::from("string"));
+            schema.insert(String::from("enum"), Value::Array(tags));
+        }
+        TypeF::Record(rrows) => {
+            let mut properties = Map::new();
+            let mut required = Vec::new();
+            let mut open = false;
+
+            for item in rrows.iter() {
+                match item {
+                    RecordRowsIteratorItem::Row(row) => {
+                        required.push(Value::from(row.id.label()));
+                        properties.insert(
+                            row.id.label().to_owned(),
+                            Value::Object(type_schema(row.typ)?),
+                        );
+                    }
+                    RecordRowsIteratorItem::TailDyn => open = true,
+                    RecordRowsIteratorItem::TailVar(_) => {
+                        return Err(JsonSchemaError::UnsupportedType(typ.to_string()))
+                    }
+                }
+            }
+
+            schema.insert(String::from("type"), Value::from("object"));
+            schema.insert(String::from("properties"), Value::Object(properties));
+            schema.insert(String::from("required"), Value::Array(required));
+
+            if !open {
+                schema.insert(String::from("additionalProperties"), Value::Bool(false));
+            }
+        }
+        TypeF::Flat(_) => return Ok(flat_schema(typ)),
+        TypeF::Symbol
+        | TypeF::Arrow(..)
+        | TypeF::Var(_)
+        | TypeF::Forall { .. }
+        | TypeF::Wildcard(_) => return Err(JsonSchemaError::UnsupportedType(typ.to_string())),
+    }
+
+    Ok(schema)
+}
+
+/// Convert a custom contract to a JSON Schema. Only a few contracts of the standard library have
+/// a JSON Schema counterpart. Other contracts are exported as an unconstrained schema with a
+/// `$comment` mentioning the original contract.
+fn flat_schema(typ: &Type) -> Map<String, Value> {
+    let contract = typ.to_string();
+    let mut schema = Map::new();
+
+    match contract.as_str() {
+        "std.number.Integer" => {
+            schema.insert(String::from("type"), Value::from("integer"));
+        }
+        "std.number.Nat" => {
+            schema.insert(String::from("type"), Value::from("integer"));
+            schema.insert(String::from("minimum"), Value::from(0));
+        }
+        "std.number.PosNat" => {
+            schema.insert(String::from("type"), Value::from("integer"));
+            schema.insert(String::from("minimum"), Value::from(1));
+        }
+        "std.string.NonEmpty" => {
+            schema.insert(String::from("type"), Value::from("string"));
+            schema.insert(String::from("minLength"), Value::from(1));
+        }
+        _ => {
+            schema.insert(
+                String::from("$comment"),
+                Value::from(format!("unsupported contract: {contract}")),
+            );
+        }
+    }
+
+    schema
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{eval::cache::CacheImpl, program::Program};
+    use serde_json::json;
+    use std::io::Cursor;
+
+    fn program(source: &str) -> Program<CacheImpl> {
+        Program::new_from_source(Cursor::new(source), "<test>", std::io::sink()).unwrap()
+    }
+
+    #[cfg(feature = "doc")]
+    fn export_source(source: &str) -> Result<Value, JsonSchemaError> {
+        let term = program(source).eval_record_spine().unwrap();
+        export(&term)
+    }
+
+    #[test]
+    fn import_object() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "name": { "type": "string", "description": "The name" },
+                "kind": { "enum": ["a", "b c"] },
+                "port": { "type": "integer", "default": 80 },
+            },
+            "required": ["name"],
+            "additionalProperties": false,
+        });
+
+        assert_eq!(
+            import(&schema).unwrap(),
+            "{\n  \
+              kind | optional | [| 'a, '\"b c\" |],\n  \
+              name | String | doc \"The name\",\n  \
+              port | std.number.Integer | default = 80\n\
+            }\n"
+        );
+    }
+
+    #[test]
+    fn import_refs() {
+        let schema = json!({
+            "$defs": { "Node": { "type": "array", "items": { "$ref": "#/$defs/Node" } } },
+            "$ref": "#/$defs/Node",
+        });
+
+        assert_eq!(
+            import(&schema).unwrap(),
+            "let rec defs = {\n  Node = Array defs.Node,\n}\nin\ndefs.Node\n"
+        );
+
+        assert_eq!(
+            import(&json!({ "$ref": "other.json" })),
+            Err(JsonSchemaError::UnsupportedRef(String::from("other.json")))
+        );
+    }
+
+    #[test]
+    fn import_constraints_only_apply_to_their_type() {
+        let contract = import(&json!({
+            "type": ["string", "null"],
+            "minLength": 1,
+        }))
+        .unwrap();
+
+        let check = |value: &str| {
+            program(&format!("{value} | ({contract})"))
+                .eval_full()
+                .is_ok()
+        };
+
+        assert!(check("null"));
+        assert!(check("\"a\""));
+        assert!(!check("\"\""));
+        assert!(!check("1"));
+
+        let contract = import(&json!({ "minimum": 0, "maxItems": 1, "pattern": "^a" })).unwrap();
+        let check = |value: &str| {
+            program(&format!("{value} | ({contract})"))
+                .eval_full()
+                .is_ok()
+        };
+
+        assert!(check("\"abc\""));
+        assert!(check("[1]"));
+        assert!(check("{ b = 1 }"));
+        assert!(!check("\"b\""));
+        assert!(!check("[1, 2]"));
+        assert!(!check("-1"));
+    }
+
+    #[cfg(feature = "doc")]
+    #[test]
+    fn export_record() {
+        assert_eq!(
+            export_source(
+                "{ name | String, tags | Array String | optional, kind | [| 'a, 'b |] | optional, .. }"
+            ),
+            Ok(json!({
+                "$schema": "http://json-schema.org/draft-07/schema#",
+                "type": "object",
+                "properties": {
+                    "name": { "type": "string" },
+                    "tags": { "type": "array", "items": { "type": "string" } },
+                    "kind": { "type": "string", "enum": ["a", "b"] },
+                },
+                "required": ["name"],
+            }))
+        );
+
+        assert_eq!(export_source("1"), Err(JsonSchemaError::NotARecordContract));
+        assert_eq!(
+            export_source("{ f | Number -> Number }"),
+            Err(JsonSchemaError::UnsupportedType(String::from(
+                "Number -> Number"
+            )))
+        );
+    }
+
+    #[cfg(feature = "doc")]
+    #[test]
+    fn round_trip() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "name": { "type": "string", "description": "The name" },
+                "port": { "type": "integer", "default": 80 },
+            },
+            "required": ["name"],
+            "additionalProperties": false,
+        });
+
+        let mut expected = json!({ "$schema": "http://json-schema.org/draft-07/schema#" });
+        expected
+            .as_object_mut()
+            .unwrap()
+            .extend(schema.as_object().unwrap().clone());
+        assert_eq!(export_source(&import(&schema).unwrap()), Ok(expected));
+    }
+}
@@ -321,6 +321,7 @@ impl From<&TypeVarData> for Term {
             .into(),
             attrs: Default::default(),
             sealed_tail: None,
+            invariants: Vec::new(),
         })
     }
 }
@@ -381,6 +382,17 @@ pub struct ContractDiagnostic {
     pub message: Option<String>,
     /// Additional notes printed at the end of the message.
     pub notes: Vec<String>,
+    /// Secondary locations related to the contract violation, such as other fields involved in a
+    /// cross-field validation. They are shown in addition to the location of the blamed value.
+    pub related: Vec<RelatedSpan>,
+}
+
+/// A secondary location attached to a [ContractDiagnostic], together with a short message
+/// explaining its relation to the contract violation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RelatedSpan {
+    pub span: RawSpan,
+    pub message: String,
 }
 
 impl ContractDiagnostic {
@@ -407,10 +419,20 @@ impl ContractDiagnostic {
         self.notes.push(note.into());
     }
 
+    /// Attach a related location to this diagnostic.
+    pub fn append_related(&mut self, span: RawSpan, message: impl Into<String>) {
+        self.related.push(RelatedSpan {
+            span,
+            message: message.into(),
+        });
+    }
+
     /// Return `true` if this diagnostic is empty, that is if `message` is either not set (`None`)
-    /// or is set but empty, AND notes are empty.
+    /// or is set but empty, AND notes and related locations are empty.
     pub fn is_empty(&self) -> bool {
-        self.message.as_ref().map(String::is_empty).unwrap_or(true) && self.notes.is_empty()
+        self.message.as_ref().map(String::is_empty).unwrap_or(true)
+            && self.notes.is_empty()
+            && self.related.is_empty()
     }
 }
 
@@ -479,6 +501,22 @@ impl Label {
         self
     }
 
+    /// Attach a related location to the current diagnostic (the last diagnostic of the stack).
+    ///
+    /// If the diagnostic stack is empty, this method pushes a new diagnostic with the given
+    /// location.
+    pub fn append_diagnostic_related(mut self, span: RawSpan, message: impl Into<String>) -> Self {
+        if let Some(current) = self.diagnostics.last_mut() {
+            current.append_related(span, message);
+        } else {
+            let mut diagnostic = ContractDiagnostic::new();
+            diagnostic.append_related(span, message);
+            self.diagnostics.push(diagnostic);
+        };
+
+        self
+    }
+
     /// Return a reference to the current contract diagnostic, which is the last element of the
     /// stack, if any.
     pub fn current_diagnostic(&self) -> Option<&ContractDiagnostic> {
@@ -1,23 +1,30 @@
 pub mod cache;
 pub mod combine;
+pub mod datetime;
 pub mod deserialize;
 pub mod destructuring;
 pub mod environment;
 pub mod error;
 pub mod eval;
+pub mod hash;
 pub mod identifier;
+pub mod json_schema;
 pub mod label;
+pub mod net;
 pub mod parser;
+pub mod persistent_cache;
 pub mod position;
 pub mod pretty;
 pub mod program;
 pub mod repl;
+pub mod semver;
 pub mod serialize;
 pub mod stdlib;
 pub mod term;
 pub mod transform;
 pub mod typ;
 pub mod typecheck;
+pub mod vfs;
 
 #[cfg(feature = "format")]
 pub mod format;
@@ -190,6 +190,8 @@ pub enum NormalToken<'input> {
     ArrayLazyAssume,
     #[token("%record_lazy_assume%")]
     RecordLazyAssume,
+    #[token("%contract_try%")]
+    ContractTry,
     #[token("%blame%")]
     Blame,
     #[token("%chng_pol%")]
@@ -245,6 +247,26 @@ pub enum NormalToken<'input> {
     ValuesOf,
     #[token("%pow%")]
     Pow,
+    #[token("%num_to_str_fixed%")]
+    NumToStrFixed,
+    #[token("%datetime_from_timestamp%")]
+    DateTimeFromTimestamp,
+    #[token("%datetime_format%")]
+    DateTimeFormat,
+    #[token("%semver_compare%")]
+    SemVerCompare,
+    #[token("%semver_matches%")]
+    SemVerMatches,
+    #[token("%cidr_contains%")]
+    CidrContains,
+    #[token("%log%")]
+    Log,
+    #[token("%int_div%")]
+    IntDiv,
+    #[token("%int_rem%")]
+    IntRem,
+    #[token("%gcd%")]
+    Gcd,
     #[token("%trace%")]
     Trace,
 
@@ -254,6 +276,10 @@ pub enum NormalToken<'input> {
     Map,
     #[token("%elem_at%")]
     ElemAt,
+    #[token("%array_chunks%")]
+    ArrayChunks,
+    #[token("%array_windows%")]
+    ArrayWindows,
     #[token("%generate%")]
     ArrayGen,
     #[token("%rec_force%")]
@@ -278,6 +304,10 @@ pub enum NormalToken<'input> {
 
     #[token("%hash%")]
     OpHash,
+    #[token("%hash_value%")]
+    HashValue,
+    #[token("%hmac%")]
+    Hmac,
     #[token("%serialize%")]
     Serialize,
     #[token("%deserialize%")]
@@ -292,8 +322,44 @@ pub enum NormalToken<'input> {
     StrUppercase,
     #[token("%str_lowercase%")]
     StrLowercase,
+    #[token("%base64_encode%")]
+    Base64Encode,
+    #[token("%base64_decode%")]
+    Base64Decode,
+    #[token("%base64_url_encode%")]
+    Base64UrlEncode,
+    #[token("%base64_url_decode%")]
+    Base64UrlDecode,
+    #[token("%hex_encode%")]
+    HexEncode,
+    #[token("%hex_decode%")]
+    HexDecode,
+    #[token("%percent_encode%")]
+    PercentEncode,
+    #[token("%percent_decode%")]
+    PercentDecode,
+    #[token("%semver_check%")]
+    SemVerCheck,
+    #[token("%semver_req_check%")]
+    SemVerReqCheck,
+    #[token("%ipv4_check%")]
+    Ipv4Check,
+    #[token("%ipv6_check%")]
+    Ipv6Check,
+    #[token("%cidr_check%")]
+    CidrCheck,
+    #[token("%hostname_check%")]
+    HostnameCheck,
+    #[token("%url_check%")]
+    UrlCheck,
+    #[token("%semver_parse%")]
+    SemVerParse,
+    #[token("%url_parse%")]
+    UrlParse,
     #[token("%str_contains%")]
     StrContains,
+    #[token("%str_compare%")]
+    StrCompare,
     #[token("%str_replace%")]
     StrReplace,
     #[token("%str_replace_regex%")]
@@ -302,6 +368,10 @@ pub enum NormalToken<'input> {
     StrIsMatch,
     #[token("%str_find%")]
     StrFind,
+    #[token("%str_find_all%")]
+    StrFindAll,
+    #[token("%str_captures%")]
+    StrCaptures,
     #[token("%str_length%")]
     StrLength,
     #[token("%str_substr%")]
@@ -310,6 +380,24 @@ pub enum NormalToken<'input> {
     ToStr,
     #[token("%num_from_str%")]
     NumFromStr,
+    #[token("%floor%")]
+    Floor,
+    #[token("%ceil%")]
+    Ceil,
+    #[token("%round%")]
+    Round,
+    #[token("%sqrt%")]
+    Sqrt,
+    #[token("%datetime_parse%")]
+    DateTimeParse,
+    #[token("%datetime_is_valid%")]
+    DateTimeIsValid,
+    #[token("%duration_parse%")]
+    DurationParse,
+    #[token("%duration_is_valid%")]
+    DurationIsValid,
+    #[token("%duration_format%")]
+    DurationFormat,
     #[token("%enum_from_str%")]
     EnumFromStr,
     #[token("%label_with_message%")]
@@ -322,6 +410,10 @@ pub enum NormalToken<'input> {
     LabelPushDiag,
     #[token("%array_slice%")]
     ArraySlice,
+    #[token("%label_with_related_field%")]
+    LabelWithRelatedField,
+    #[token("%record_add_invariant%")]
+    RecordAddInvariant,
 
     #[token("{")]
     LBrace,
@@ -1,5 +1,3 @@
-use std::rc::Rc;
-
 use super::lexer::{Lexer, MultiStringToken, NormalToken, StringToken, SymbolicStringStart, Token};
 use super::utils::{build_record, FieldPathElem};
 use crate::error::ParseError;
@@ -56,7 +54,7 @@ fn mk_symbolic_single_chunk(prefix: &str, s: &str) -> RichTerm {
             (
                 FieldPathElem::Ident("fragments".into()),
                 Field::from(RichTerm::from(Array(
-                    Array::new(Rc::new([mk_single_chunk(s)])),
+                    Array::from_iter([mk_single_chunk(s)]),
                     Default::default(),
                 ))),
             ),
@@ -0,0 +1,1856 @@
+//! Binary encoding of terms for the [persistent cache](super).
+//!
+//! The format is a plain pre-order serialization of the AST: each node starts with a tag
+//! identifying its variant, followed by its children. Integers are encoded as LEB128 varints and
+//! strings as their length followed by their UTF-8 bytes. Primitive operators are identified by
+//! the name of their variant rather than by a number, so that reordering the operator enums
+//! doesn't silently change the meaning of existing entries.
+//!
+//! This format isn't meant to be stable nor exchanged between versions: any change to the AST or
+//! to this module must bump [FORMAT_VERSION], which is part of the keys of the persistent cache.
+//!
+//! A few pieces of data need special care:
+//!
+//! - Positions are only kept when they point inside the file being encoded. They are remapped to
+//!   the `FileId` the file has been given in the current session when decoding. Positions pointing
+//!   elsewhere (for example, types inferred from another file) are dropped.
+//! - Resolved imports are encoded as an index into the list of the direct imports of the file,
+//!   which is returned by the encoder and stored alongside the term.
+//! - Generated identifiers (see [crate::identifier::GEN_PREFIX]) come from a global counter.
+//!   They are replaced by fresh ones when decoding, so that they can't clash with the ones
+//!   generated in the current session.
+//! - Nodes which only exist at runtime, such as sealed terms, can't be encoded. Neither can parse
+//!   errors. In that case, encoding fails and the term is simply not persisted.
+use crate::{
+    destructuring::{FieldPattern, Match, RecordPattern},
+    identifier::{Ident, LocIdent, GEN_PREFIX},
+    label::{ty_path, ContractDiagnostic, Label, MergeKind, MergeLabel, Polarity, RelatedSpan},
+    position::{RawSpan, TermPos},
+    term::{
+        array::{Array, ArrayAttrs},
+        record::{Field, FieldDeps, FieldMetadata, RecordAttrs, RecordData, RecordDeps},
+        BinaryOp, BindingType, CompiledRegex, Encoding, IndexMap, LabeledType, LetAttrs,
+        MergePriority, NAryOp, Number, RecordExtKind, RichTerm, RuntimeContract, StrChunk,
+        StringFormat, Term, TypeAnnotation, UnaryOp, Zero,
+    },
+    typ::{
+        DictTypeFlavour, EnumRows, EnumRowsF, RecordRowF, RecordRows, RecordRowsF, Type, TypeF,
+        VarKind,
+    },
+};
+use codespan::{ByteIndex, FileId};
+use malachite::Natural;
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::OsString,
+    hash::Hash,
+    rc::Rc,
+    str::FromStr,
+};
+
+/// The version of the encoding. Must be bumped whenever the format or the AST changes.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// An error raised when encoding or decoding a term.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CodecError {
+    /// The term contains a node which can't be persisted. The payload describes the node.
+    Unsupported(&'static str),
+    /// The data is truncated or doesn't follow the format.
+    Malformed,
+}
+
+type Result<T> = std::result::Result<T, CodecError>;
+
+/// Encode the term of the file `file_id`. Return the encoded term together with the list of the
+/// files it imports, in the order expected by [decode].
+pub fn encode(rt: &RichTerm, file_id: FileId) -> Result<(Vec<u8>, Vec<FileId>)> {
+    let mut enc = Encoder {
+        buf: Vec::new(),
+        file_id,
+        imports: Vec::new(),
+    };
+    rt.encode(&mut enc)?;
+    Ok((enc.buf, enc.imports))
+}
+
+/// Decode a term previously encoded by [encode], as the content of the file `file_id`. `imports`
+/// must hold the ids of the files corresponding to the imports returned by [encode], in the same
+/// order.
+pub fn decode(data: &[u8], file_id: FileId, imports: &[FileId]) -> Result<RichTerm> {
+    let mut dec = Decoder {
+        data,
+        file_id,
+        imports,
+        generated: HashMap::new(),
+    };
+    let rt = RichTerm::decode(&mut dec)?;
+
+    if dec.data.is_empty() {
+        Ok(rt)
+    } else {
+        Err(CodecError::Malformed)
+    }
+}
+
+pub struct Encoder {
+    buf: Vec<u8>,
+    file_id: FileId,
+    imports: Vec<FileId>,
+}
+
+impl Encoder {
+    fn tag(&mut self, tag: u8) {
+        self.buf.push(tag);
+    }
+
+    fn varint(&mut self, mut n: u64) {
+        loop {
+            let byte = (n & 0x7f) as u8;
+            n >>= 7;
+
+            if n == 0 {
+                self.buf.push(byte);
+                break;
+            } else {
+                self.buf.push(byte | 0x80);
+            }
+        }
+    }
+
+    fn str(&mut self, s: &str) {
+        self.varint(s.len() as u64);
+        self.buf.extend_from_slice(s.as_bytes());
+    }
+
+    fn import(&mut self, file_id: FileId) {
+        let index = match self.imports.iter().position(|id| *id == file_id) {
+            Some(index) => index,
+            None => {
+                self.imports.push(file_id);
+                self.imports.len() - 1
+            }
+        };
+        self.varint(index as u64);
+    }
+}
+
+pub struct Decoder<'a> {
+    data: &'a [u8],
+    file_id: FileId,
+    imports: &'a [FileId],
+    /// The fresh identifiers substituted for the generated identifiers of the encoded term.
+    generated: HashMap<Ident, Ident>,
+}
+
+impl<'a> Decoder<'a> {
+    fn tag(&mut self) -> Result<u8> {
+        let (byte, rest) = self.data.split_first().ok_or(CodecError::Malformed)?;
+        self.data = rest;
+        Ok(*byte)
+    }
+
+    fn varint(&mut self) -> Result<u64> {
+        let mut result = 0u64;
+
+        for shift in (0..64).step_by(7) {
+            let byte = self.tag()?;
+            result |= u64::from(byte & 0x7f) << shift;
+
+            if byte & 0x80 == 0 {
+                return Ok(result);
+            }
+        }
+
+        Err(CodecError::Malformed)
+    }
+
+    /// Decode the length of a sequence. Each element takes at least one byte, which bounds the
+    /// length by the size of the remaining data and avoids allocating absurd amounts of memory
+    /// on corrupted input.
+    fn len(&mut self) -> Result<usize> {
+        let len = usize::try_from(self.varint()?).map_err(|_| CodecError::Malformed)?;
+
+        if len <= self.data.len() {
+            Ok(len)
+        } else {
+            Err(CodecError::Malformed)
+        }
+    }
+
+    fn str(&mut self) -> Result<&'a str> {
+        let len = self.len()?;
+        let (bytes, rest) = self.data.split_at(len);
+        self.data = rest;
+        std::str::from_utf8(bytes).map_err(|_| CodecError::Malformed)
+    }
+
+    fn import(&mut self) -> Result<FileId> {
+        let index = usize::try_from(self.varint()?).map_err(|_| CodecError::Malformed)?;
+        self.imports
+            .get(index)
+            .copied()
+            .ok_or(CodecError::Malformed)
+    }
+
+    fn ident(&mut self) -> Result<Ident> {
+        let label = self.str()?;
+
+        if label.starts_with(GEN_PREFIX) {
+            Ok(*self
+                .generated
+                .entry(Ident::new(label))
+                .or_insert_with(|| LocIdent::fresh().ident()))
+        } else {
+            Ok(Ident::new(label))
+        }
+    }
+}
+
+pub trait Encode {
+    fn encode(&self, enc: &mut Encoder) -> Result<()>;
+}
+
+pub trait Decode: Sized {
+    fn decode(dec: &mut Decoder<'_>) -> Result<Self>;
+}
+
+impl Encode for bool {
+    fn encode(&self, enc: &mut Encoder) -> Result<()> {
+        enc.tag(u8::from(*self));
+        Ok(())
+    }
+}
+
+impl Decode for bool {
+    fn decode(dec: &mut Decoder<'_>) -> Result<Self> {
+        match dec.tag()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(CodecError::Malformed),
+        }
+    }
+}
+
+impl Encode for usize {
+    fn encode(&self, enc: &mut Encoder) -> Result<()> {
+        enc.varint(*self as u64);
+        Ok(())
+    }
+}
+
+impl Decode for usize {
+    fn decode(dec: &mut Decoder<'_>) -> Result<Self> {
+        usize::try_from(dec.varint()?).map_err(|_| CodecError::Malformed)
+    }
+}
+
+impl Encode for String {
+    fn encode(&self, enc: &mut Encoder) -> Result<()> {
+        enc.str(self);
+        Ok(())
+    }
+}
+
+impl Decode for String {
+    fn decode(dec: &mut Decoder<'_>) -> Result<Self> {
+        dec.str().map(String::from)
+    }
+}
+
+impl<T: Encode> Encode for Option<T> {
+    fn encode(&self, enc: &mut Encoder) -> Result<()> {
+        match self {
+            None => {
+                enc.tag(0);
+                Ok(())
+            }
+            Some(t) => {
+                enc.tag(1);
+                t.encode(enc)
+            }
+        }
+    }
+}
+
+impl<T: Decode> Decode for Option<T> {
+    fn decode(dec: &mut Decoder<'_>) -> Result<Self> {
+        match dec.tag()? {
+            0 => Ok(None),
+            1 => T::decode(dec).map(Some),
+            _ => Err(CodecError::Malformed),
+        }
+    }
+}
+
+impl<T: Encode> Encode for [T] {
+    fn encode(&self, enc: &mut Encoder) -> Result<()> {
+        enc.varint(self.len() as u64);
+        self.iter().try_for_each(|t| t.encode(enc))
+    }
+}
+
+impl<T: Encode> Encode for Vec<T> {
+    fn encode(&self, enc: &mut Encoder) -> Result<()> {
+        self.as_slice().encode(enc)
+    }
+}
+
+impl<T: Decode> Decode for Vec<T> {
+    fn decode(dec: &mut Decoder<'_>) -> Result<Self> {
+        let len = dec.len()?;
+        (0..len).map(|_| T::decode(dec)).collect()
+    }
+}
+
+impl<T: Encode + Eq + Hash> Encode for HashSet<T> {
+    fn encode(&self, enc: &mut Encoder) -> Result<()> {
+        enc.varint(self.len() as u64);
+        self.iter().try_for_each(|t| t.encode(enc))
+    }
+}
+
+impl<T: Decode + Eq + Hash> Decode for HashSet<T> {
+    fn decode(dec: &mut Decoder<'_>) -> Result<Self> {
+        let len = dec.len()?;
+        (0..len).map(|_| T::decode(dec)).collect()
+    }
+}
+
+impl<K: Encode, V: Encode> Encode for IndexMap<K, V> {
+    fn encode(&self, enc: &mut Encoder) -> Result<()> {
+        enc.varint(self.len() as u64);
+        self.iter().try_for_each(|(k, v)| {
+            k.encode(enc)?;
+            v.encode(enc)
+        })
+    }
+}
+
+impl<K: Decode + Eq + Hash, V: Decode> Decode for IndexMap<K, V> {
+    fn decode(dec: &mut Decoder<'_>) -> Result<Self> {
+        let len = dec.len()?;
+        (0..len)
+            .map(|_| -> Result<(K, V)> { Ok((K::decode(dec)?, V::decode(dec)?)) })
+            .collect()
+    }
+}
+
+impl<A: Encode, B: Encode> Encode for (A, B) {
+    fn encode(&self, enc: &mut Encoder) -> Result<()> {
+        self.0.encode(enc)?;
+        self.1.encode(enc)
+    }
+}
+
+impl<A: Decode, B: Decode> Decode for (A, B) {
+    fn decode(dec: &mut Decoder<'_>) -> Result<Self> {
+        Ok((A::decode(dec)?, B::decode(dec)?))
+    }
+}
+
+impl<T: Encode> Encode for Box<T> {
+    fn encode(&self, enc: &mut Encoder) -> Result<()> {
+        self.as_ref().encode(enc)
+    }
+}
+
+impl<T: Decode> Decode for Box<T> {
+    fn decode(dec: &mut Decoder<'_>) -> Result<Self> {
+        T::decode(dec).map(Box::new)
+    }
+}
+
+impl<T: Encode> Encode for Rc<T> {
+    fn encode(&self, enc: &mut Encoder) -> Result<()> {
+        self.as_ref().encode(enc)
+    }
+}
+
+impl<T: Decode> Decode for Rc<T> {
+    fn decode(dec: &mut Decoder<'_>) -> Result<Self> {
+        T::decode(dec).map(Rc::new)
+    }
+}
+
+impl Encode for Number {
+    fn encode(&self, enc: &mut Encoder) -> Result<()> {
+        (*self >= Number::ZERO).encode(enc)?;
+        enc.str(&self.to_numerator().to_string());
+        enc.str(&self.to_denominator().to_string());
+        Ok(())
+    }
+}
+
+impl Decode for Number {
+    fn decode(dec: &mut Decoder<'_>) -> Result<Self> {
+        let sign = bool::decode(dec)?;
+        let numerator = Natural::from_str(dec.str()?).map_err(|_| CodecError::Malformed)?;
+        let denominator = Natural::from_str(dec.str()?).map_err(|_| CodecError::Malformed)?;
+
+        if denominator == Natural::ZERO {
+            return Err(CodecError::Malformed);
+        }
+
+        Ok(Number::from_sign_and_naturals(sign, numerator, denominator))
+    }
+}
+
+impl Encode for Ident {
+    fn encode(&self, enc: &mut Encoder) -> Result<()> {
+        enc.str(self.label());
+        Ok(())
+    }
+}
+
+impl Decode for Ident {
+    fn decode(dec: &mut Decoder<'_>) -> Result<Self> {
+        dec.ident()
+    }
+}
+
+impl Encode for LocIdent {
+    fn encode(&self, enc: &mut Encoder) -> Result<()> {
+        self.ident().encode(enc)?;
+        self.pos.encode(enc)
+    }
+}
+
+impl Decode for LocIdent {
+    fn decode(dec: &mut Decoder<'_>) -> Result<Self> {
+        let ident = dec.ident()?;
+        let pos = TermPos::decode(dec)?;
+        Ok(LocIdent::from(ident).with_pos(pos))
+    }
+}
+
+impl Encode for RawSpan {
+    fn encode(&self, enc: &mut Encoder) -> Result<()> {
+        if self.src_id != enc.file_id {
+            return Err(CodecError::Unsupported("span pointing to another file"));
+        }
+
+        enc.varint(u64::from(self.start.0));
+        enc.varint(u64::from(self.end.0));
+        Ok(())
+    }
+}
+
+impl Decode for RawSpan {
+    fn decode(dec: &mut Decoder<'_>) -> Result<Self> {
+        let mut index = || -> Result<ByteIndex> {
+            let raw = u32::try_from(dec.varint()?).map_err(|_| CodecError::Malformed)?;
+            Ok(ByteIndex(raw))
+        };
+        let start = index()?;
+        let end = index()?;
+
+        Ok(RawSpan {
+            src_id: dec.file_id,
+            start,
+            end,
+        })
+    }
+}
+
+impl Encode for TermPos {
+    fn encode(&self, enc: &mut Encoder) -> Result<()> {
+        match self {
+            TermPos::Original(span) if span.src_id == enc.file_id => {
+                enc.tag(1);
+                span.encode(enc)
+            }
+            TermPos::Inherited(span) if span.src_id == enc.file_id => {
+                enc.tag(2);
+                span.encode(enc)
+            }
+            _ => {
+                enc.tag(0);
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Decode for TermPos {
+    fn decode(dec: &mut Decoder<'_>) -> Result<Self> {
+        match dec.tag()? {
+            0 => Ok(TermPos::None),
+            1 => RawSpan::decode(dec).map(TermPos::Original),
+            2 => RawSpan::decode(dec).map(TermPos::Inherited),
+            _ => Err(CodecError::Malformed),
+        }
+    }
+}
+
+impl Encode for RichTerm {
+    fn encode(&self, enc: &mut Encoder) -> Result<()> {
+        self.as_ref().encode(enc)?;
+        self.pos.encode(enc)
+    }
+}
+
+impl Decode for RichTerm {
+    fn decode(dec: &mut Decoder<'_>) -> Result<Self> {
+        let term = Term::decode(dec)?;
+        let pos = TermPos::decode(dec)?;
+        Ok(RichTerm::new(term, pos))
+    }
+}
+
+impl Encode for Term {
+    fn encode(&self, enc: &mut Encoder) -> Result<()> {
+        match self {
+            Term::Null => enc.tag(0),
+            Term::Bool(b) => {
+                enc.tag(1);
+                b.encode(enc)?;
+            }
+            Term::Num(n) => {
+                enc.tag(2);
+                n.encode(enc)?;
+            }
+            Term::Str(s) => {
+                enc.tag(3);
+                enc.str(s);
+            }
+            Term::StrChunks(chunks) => {
+                enc.tag(4);
+                chunks.encode(enc)?;
+            }
+            Term::Fun(id, body) => {
+                enc.tag(5);
+                id.encode(enc)?;
+                body.encode(enc)?;
+            }
+            Term::FunPattern(id, pat, body) => {
+                enc.tag(6);
+                id.encode(enc)?;
+                pat.encode(enc)?;
+                body.encode(enc)?;
+            }
+            Term::Let(id, bound, body, attrs) => {
+                enc.tag(7);
+                id.encode(enc)?;
+                bound.encode(enc)?;
+                body.encode(enc)?;
+                attrs.encode(enc)?;
+            }
+            Term::LetPattern(id, pat, bound, body) => {
+                enc.tag(8);
+                id.encode(enc)?;
+                pat.encode(enc)?;
+                bound.encode(enc)?;
+                body.encode(enc)?;
+            }
+            Term::App(head, arg) => {
+                enc.tag(9);
+                head.encode(enc)?;
+                arg.encode(enc)?;
+            }
+            Term::Var(id) => {
+                enc.tag(10);
+                id.encode(enc)?;
+            }
+            Term::Enum(id) => {
+                enc.tag(11);
+                id.encode(enc)?;
+            }
+            Term::Record(record) => {
+                enc.tag(12);
+                record.encode(enc)?;
+            }
+            Term::RecRecord(record, dyn_fields, deps) => {
+                enc.tag(13);
+                record.encode(enc)?;
+                dyn_fields.encode(enc)?;
+                deps.encode(enc)?;
+            }
+            Term::Match { cases, default } => {
+                enc.tag(14);
+                cases.encode(enc)?;
+                default.encode(enc)?;
+            }
+            Term::Array(array, attrs) => {
+                enc.tag(15);
+                enc.varint(array.len() as u64);
+                array.iter().try_for_each(|elt| elt.encode(enc))?;
+                attrs.encode(enc)?;
+            }
+            Term::Op1(op, t) => {
+                enc.tag(16);
+                op.encode(enc)?;
+                t.encode(enc)?;
+            }
+            Term::Op2(op, t1, t2) => {
+                enc.tag(17);
+                op.encode(enc)?;
+                t1.encode(enc)?;
+                t2.encode(enc)?;
+            }
+            Term::OpN(op, args) => {
+                enc.tag(18);
+                op.encode(enc)?;
+                args.encode(enc)?;
+            }
+            Term::Annotated(annot, t) => {
+                enc.tag(19);
+                annot.encode(enc)?;
+                t.encode(enc)?;
+            }
+            Term::Import(path) => {
+                let path = path
+                    .to_str()
+                    .ok_or(CodecError::Unsupported("non UTF-8 import path"))?;
+                enc.tag(20);
+                enc.str(path);
+            }
+            Term::ResolvedImport(file_id) => {
+                enc.tag(21);
+                enc.import(*file_id);
+            }
+            Term::Type(typ) => {
+                enc.tag(22);
+                typ.encode(enc)?;
+            }
+            Term::Lbl(_) => return Err(CodecError::Unsupported("label")),
+            Term::SealingKey(_) | Term::Sealed(..) => {
+                return Err(CodecError::Unsupported("sealed term"))
+            }
+            Term::ParseError(_) => return Err(CodecError::Unsupported("parse error")),
+            Term::RuntimeError(_) => return Err(CodecError::Unsupported("runtime error")),
+        }
+
+        Ok(())
+    }
+}
+
+impl Decode for Term {
+    fn decode(dec: &mut Decoder<'_>) -> Result<Self> {
+        let term = match dec.tag()? {
+            0 => Term::Null,
+            1 => Term::Bool(bool::decode(dec)?),
+            2 => Term::Num(Number::decode(dec)?),
+            3 => Term::Str(dec.str()?.into()),
+            4 => Term::StrChunks(Vec::decode(dec)?),
+            5 => Term::Fun(LocIdent::decode(dec)?, RichTerm::decode(dec)?),
+            6 => Term::FunPattern(
+                Option::decode(dec)?,
+                RecordPattern::decode(dec)?,
+                RichTerm::decode(dec)?,
+            ),
+            7 => Term::Let(
+                LocIdent::decode(dec)?,
+                RichTerm::decode(dec)?,
+                RichTerm::decode(dec)?,
+                LetAttrs::decode(dec)?,
+            ),
+            8 => Term::LetPattern(
+                Option::decode(dec)?,
+                RecordPattern::decode(dec)?,
+                RichTerm::decode(dec)?,
+                RichTerm::decode(dec)?,
+            ),
+            9 => Term::App(RichTerm::decode(dec)?, RichTerm::decode(dec)?),
+            10 => Term::Var(LocIdent::decode(dec)?),
+            11 => Term::Enum(LocIdent::decode(dec)?),
+            12 => Term::Record(RecordData::decode(dec)?),
+            13 => Term::RecRecord(
+                RecordData::decode(dec)?,
+                Vec::decode(dec)?,
+                Option::decode(dec)?,
+            ),
+            14 => Term::Match {
+                cases: IndexMap::decode(dec)?,
+                default: Option::decode(dec)?,
+            },
+            15 => {
+                let elts: Vec<RichTerm> = Vec::decode(dec)?;
+                Term::Array(Array::from_iter(elts), ArrayAttrs::decode(dec)?)
+            }
+            16 => Term::Op1(UnaryOp::decode(dec)?, RichTerm::decode(dec)?),
+            17 => Term::Op2(
+                BinaryOp::decode(dec)?,
+                RichTerm::decode(dec)?,
+                RichTerm::decode(dec)?,
+            ),
+            18 => Term::OpN(NAryOp::decode(dec)?, Vec::decode(dec)?),
+            19 => Term::Annotated(TypeAnnotation::decode(dec)?, RichTerm::decode(dec)?),
+            20 => Term::Import(OsString::from(dec.str()?)),
+            21 => Term::ResolvedImport(dec.import()?),
+            22 => Term::Type(Type::decode(dec)?),
+            _ => return Err(CodecError::Malformed),
+        };
+
+        Ok(term)
+    }
+}
+
+impl Encode for StrChunk<RichTerm> {
+    fn encode(&self, enc: &mut Encoder) -> Result<()> {
+        match self {
+            StrChunk::Literal(s) => {
+                enc.tag(0);
+                enc.str(s);
+                Ok(())
+            }
+            StrChunk::Expr(t, indent) => {
+                enc.tag(1);
+                t.encode(enc)?;
+                indent.encode(enc)
+            }
+        }
+    }
+}
+
+impl Decode for StrChunk<RichTerm> {
+    fn decode(dec: &mut Decoder<'_>) -> Result<Self> {
+        match dec.tag()? {
+            0 => String::decode(dec).map(StrChunk::Literal),
+            1 => Ok(StrChunk::Expr(RichTerm::decode(dec)?, usize::decode(dec)?)),
+            _ => Err(CodecError::Malformed),
+        }
+    }
+}
+
+impl Encode for LetAttrs {
+    fn encode(&self, enc: &mut Encoder) -> Result<()> {
+        match &self.binding_type {
+            BindingType::Normal => enc.tag(0),
+            BindingType::Revertible(deps) => {
+                enc.tag(1);
+                deps.encode(enc)?;
+            }
+        }
+        self.rec.encode(enc)
+    }
+}
+
+impl Decode for LetAttrs {
+    fn decode(dec: &mut Decoder<'_>) -> Result<Self> {
+        let binding_type = match dec.tag()? {
+            0 => BindingType::Normal,
+            1 => BindingType::Revertible(FieldDeps::decode(dec)?),
+            _ => return Err(CodecError::Malformed),
+        };
+
+        Ok(LetAttrs {
+            binding_type,
+            rec: bool::decode(dec)?,
+        })
+    }
+}
+
+impl Encode for FieldDeps {
+    fn encode(&self, enc: &mut Encoder) -> Result<()> {
+        match self {
+            FieldDeps::Unknown => {
+                enc.tag(0);
+                Ok(())
+            }
+            FieldDeps::Known(deps) => {
+                enc.tag(1);
+                deps.encode(enc)
+            }
+        }
+    }
+}
+
+impl Decode for FieldDeps {
+    fn decode(dec: &mut Decoder<'_>) -> Result<Self> {
+        match dec.tag()? {
+            0 => Ok(FieldDeps::Unknown),
+            1 => Rc::decode(dec).map(FieldDeps::Known),
+            _ => Err(CodecError::Malformed),
+        }
+    }
+}
+
+impl Encode for RecordDeps {
+    fn encode(&self, enc: &mut Encoder) -> Result<()> {
+        self.stat_fields.encode(enc)?;
+        self.dyn_fields.encode(enc)
+    }
+}
+
+impl Decode for RecordDeps {
+    fn decode(dec: &mut Decoder<'_>) -> Result<Self> {
+        Ok(RecordDeps {
+            stat_fields: IndexMap::decode(dec)?,
+            dyn_fields: Vec::decode(dec)?,
+        })
+    }
+}
+
+impl Encode for RecordData {
+    fn encode(&self, enc: &mut Encoder) -> Result<()> {
+        if self.sealed_tail.is_some() {
+            return Err(CodecError::Unsupported("sealed record tail"));
+        }
+
+        self.fields.encode(enc)?;
+        self.attrs.open.encode(enc)?;
+        self.invariants.encode(enc)
+    }
+}
+
+impl Decode for RecordData {
+    fn decode(dec: &mut Decoder<'_>) -> Result<Self> {
+        let fields = IndexMap::decode(dec)?;
+        let attrs = RecordAttrs {
+            open: bool::decode(dec)?,
+        };
+
+        Ok(RecordData {
+            invariants: Vec::decode(dec)?,
+            ..RecordData::new(fields, attrs, None)
+        })
+    }
+}
+
+impl Encode for Field {
+    fn encode(&self, enc: &mut Encoder) -> Result<()> {
+        self.value.encode(enc)?;
+        self.metadata.encode(enc)?;
+        self.pending_contracts.encode(enc)
+    }
+}
+
+impl Decode for Field {
+    fn decode(dec: &mut Decoder<'_>) -> Result<Self> {
+        Ok(Field {
+            value: Option::decode(dec)?,
+            metadata: FieldMetadata::decode(dec)?,
+            pending_contracts: Vec::decode(dec)?,
+        })
+    }
+}
+
+impl Encode for FieldMetadata {
+    fn encode(&self, enc: &mut Encoder) -> Result<()> {
+        self.doc.encode(enc)?;
+        self.annotation.encode(enc)?;
+        self.opt.encode(enc)?;
+        self.not_exported.encode(enc)?;
+        self.priority.encode(enc)
+    }
+}
+
+impl Decode for FieldMetadata {
+    fn decode(dec: &mut Decoder<'_>) -> Result<Self> {
+        Ok(FieldMetadata {
+            doc: Option::decode(dec)?,
+            annotation: TypeAnnotation::decode(dec)?,
+            opt: bool::decode(dec)?,
+            not_exported: bool::decode(dec)?,
+            priority: MergePriority::decode(dec)?,
+        })
+    }
+}
+
+impl Encode for MergePriority {
+    fn encode(&self, enc: &mut Encoder) -> Result<()> {
+        match self {
+            MergePriority::Bottom => enc.tag(0),
+            MergePriority::Neutral => enc.tag(1),
+            MergePriority::Numeral(n) => {
+                enc.tag(2);
+                n.encode(enc)?;
+            }
+            MergePriority::Top => enc.tag(3),
+        }
+
+        Ok(())
+    }
+}
+
+impl Decode for MergePriority {
+    fn decode(dec: &mut Decoder<'_>) -> Result<Self> {
+        match dec.tag()? {
+            0 => Ok(MergePriority::Bottom),
+            1 => Ok(MergePriority::Neutral),
+            2 => Number::decode(dec).map(MergePriority::Numeral),
+            3 => Ok(MergePriority::Top),
+            _ => Err(CodecError::Malformed),
+        }
+    }
+}
+
+impl Encode for TypeAnnotation {
+    fn encode(&self, enc: &mut Encoder) -> Result<()> {
+        self.typ.encode(enc)?;
+        self.contracts.encode(enc)
+    }
+}
+
+impl Decode for TypeAnnotation {
+    fn decode(dec: &mut Decoder<'_>) -> Result<Self> {
+        Ok(TypeAnnotation {
+            typ: Option::decode(dec)?,
+            contracts: Vec::decode(dec)?,
+        })
+    }
+}
+
+impl Encode for LabeledType {
+    fn encode(&self, enc: &mut Encoder) -> Result<()> {
+        self.typ.encode(enc)?;
+        self.label.encode(enc)
+    }
+}
+
+impl Decode for LabeledType {
+    fn decode(dec: &mut Decoder<'_>) -> Result<Self> {
+        Ok(LabeledType {
+            typ: Type::decode(dec)?,
+            label: Label::decode(dec)?,
+        })
+    }
+}
+
+impl Encode for RuntimeContract {
+    fn encode(&self, enc: &mut Encoder) -> Result<()> {
+        self.contract.encode(enc)?;
+        self.label.encode(enc)
+    }
+}
+
+impl Decode for RuntimeContract {
+    fn decode(dec: &mut Decoder<'_>) -> Result<Self> {
+        Ok(RuntimeContract {
+            contract: RichTerm::decode(dec)?,
+            label: Label::decode(dec)?,
+        })
+    }
+}
+
+impl Encode for Label {
+    fn encode(&self, enc: &mut Encoder) -> Result<()> {
+        // Those fields are only set during evaluation.
+        if self.arg_idx.is_some() || !self.type_environment.is_empty() {
+            return Err(CodecError::Unsupported("label with runtime data"));
+        }
+
+        self.typ.encode(enc)?;
+        self.diagnostics.encode(enc)?;
+        self.span.encode(enc)?;
+        self.arg_pos.encode(enc)?;
+        self.polarity.encode(enc)?;
+        self.path.encode(enc)?;
+        self.dualize.encode(enc)?;
+        self.field_name.encode(enc)
+    }
+}
+
+impl Decode for Label {
+    fn decode(dec: &mut Decoder<'_>) -> Result<Self> {
+        Ok(Label {
+            typ: Rc::decode(dec)?,
+            diagnostics: Vec::decode(dec)?,
+            span: RawSpan::decode(dec)?,
+            arg_idx: None,
+            arg_pos: TermPos::decode(dec)?,
+            polarity: Polarity::decode(dec)?,
+            path: Vec::decode(dec)?,
+            type_environment: HashMap::new(),
+            dualize: bool::decode(dec)?,
+            field_name: Option::decode(dec)?,
+        })
+    }
+}
+
+impl Encode for Polarity {
+    fn encode(&self, enc: &mut Encoder) -> Result<()> {
+        enc.tag(match self {
+            Polarity::Positive => 0,
+            Polarity::Negative => 1,
+        });
+        Ok(())
+    }
+}
+
+impl Decode for Polarity {
+    fn decode(dec: &mut Decoder<'_>) -> Result<Self> {
+        match dec.tag()? {
+            0 => Ok(Polarity::Positive),
+            1 => Ok(Polarity::Negative),
+            _ => Err(CodecError::Malformed),
+        }
+    }
+}
+
+impl Encode for ty_path::Elem {
+    fn encode(&self, enc: &mut Encoder) -> Result<()> {
+        match self {
+            ty_path::Elem::Domain => enc.tag(0),
+            ty_path::Elem::Codomain => enc.tag(1),
+            ty_path::Elem::Field(id) => {
+                enc.tag(2);
+                id.encode(enc)?;
+            }
+            ty_path::Elem::Array => enc.tag(3),
+            ty_path::Elem::Dict => enc.tag(4),
+        }
+
+        Ok(())
+    }
+}
+
+impl Decode for ty_path::Elem {
+    fn decode(dec: &mut Decoder<'_>) -> Result<Self> {
+        match dec.tag()? {
+            0 => Ok(ty_path::Elem::Domain),
+            1 => Ok(ty_path::Elem::Codomain),
+            2 => LocIdent::decode(dec).map(ty_path::Elem::Field),
+            3 => Ok(ty_path::Elem::Array),
+            4 => Ok(ty_path::Elem::Dict),
+            _ => Err(CodecError::Malformed),
+        }
+    }
+}
+
+impl Encode for ContractDiagnostic {
+    fn encode(&self, enc: &mut Encoder) -> Result<()> {
+        self.message.encode(enc)?;
+        self.notes.encode(enc)?;
+        self.related.encode(enc)
+    }
+}
+
+impl Decode for ContractDiagnostic {
+    fn decode(dec: &mut Decoder<'_>) -> Result<Self> {
+        Ok(ContractDiagnostic {
+            message: Option::decode(dec)?,
+            notes: Vec::decode(dec)?,
+            related: Vec::decode(dec)?,
+        })
+    }
+}
+
+impl Encode for RelatedSpan {
+    fn encode(&self, enc: &mut Encoder) -> Result<()> {
+        self.span.encode(enc)?;
+        self.message.encode(enc)
+    }
+}
+
+impl Decode for RelatedSpan {
+    fn decode(dec: &mut Decoder<'_>) -> Result<Self> {
+        Ok(RelatedSpan {
+            span: RawSpan::decode(dec)?,
+            message: String::decode(dec)?,
+        })
+    }
+}
+
+impl Encode for ArrayAttrs {
+    fn encode(&self, enc: &mut Encoder) -> Result<()> {
+        self.closurized.encode(enc)?;
+        self.pending_contracts.encode(enc)
+    }
+}
+
+impl Decode for ArrayAttrs {
+    fn decode(dec: &mut Decoder<'_>) -> Result<Self> {
+        Ok(ArrayAttrs {
+            closurized: bool::decode(dec)?,
+            pending_contracts: Vec::decode(dec)?,
+        })
+    }
+}
+
+impl Encode for RecordPattern {
+    fn encode(&self, enc: &mut Encoder) -> Result<()> {
+        self.matches.encode(enc)?;
+        self.open.encode(enc)?;
+        self.rest.encode(enc)?;
+        self.span.encode(enc)
+    }
+}
+
+impl Decode for RecordPattern {
+    fn decode(dec: &mut Decoder<'_>) -> Result<Self> {
+        Ok(RecordPattern {
+            matches: Vec::decode(dec)?,
+            open: bool::decode(dec)?,
+            rest: Option::decode(dec)?,
+            span: RawSpan::decode(dec)?,
+        })
+    }
+}
+
+impl Encode for Match {
+    fn encode(&self, enc: &mut Encoder) -> Result<()> {
+        match self {
+            Match::Assign(id, field, pat) => {
+                enc.tag(0);
+                id.encode(enc)?;
+                field.encode(enc)?;
+                pat.encode(enc)
+            }
+            Match::Simple(id, field) => {
+                enc.tag(1);
+                id.encode(enc)?;
+                field.encode(enc)
+            }
+        }
+    }
+}
+
+impl Decode for Match {
+    fn decode(dec: &mut Decoder<'_>) -> Result<Self> {
+        match dec.tag()? {
+            0 => Ok(Match::Assign(
+                LocIdent::decode(dec)?,
+                Field::decode(dec)?,
+                FieldPattern::decode(dec)?,
+            )),
+            1 => Ok(Match::Simple(LocIdent::decode(dec)?, Field::decode(dec)?)),
+            _ => Err(CodecError::Malformed),
+        }
+    }
+}
+
+impl Encode for FieldPattern {
+    fn encode(&self, enc: &mut Encoder) -> Result<()> {
+        match self {
+            FieldPattern::Ident(id) => {
+                enc.tag(0);
+                id.encode(enc)
+            }
+            FieldPattern::RecordPattern(pat) => {
+                enc.tag(1);
+                pat.encode(enc)
+            }
+            FieldPattern::AliasedRecordPattern { alias, pattern } => {
+                enc.tag(2);
+                alias.encode(enc)?;
+                pattern.encode(enc)
+            }
+        }
+    }
+}
+
+impl Decode for FieldPattern {
+    fn decode(dec: &mut Decoder<'_>) -> Result<Self> {
+        match dec.tag()? {
+            0 => LocIdent::decode(dec).map(FieldPattern::Ident),
+            1 => RecordPattern::decode(dec).map(FieldPattern::RecordPattern),
+            2 => Ok(FieldPattern::AliasedRecordPattern {
+                alias: LocIdent::decode(dec)?,
+                pattern: RecordPattern::decode(dec)?,
+            }),
+            _ => Err(CodecError::Malformed),
+        }
+    }
+}
+
+impl Encode for Type {
+    fn encode(&self, enc: &mut Encoder) -> Result<()> {
+        match &self.typ {
+            TypeF::Dyn => enc.tag(0),
+            TypeF::Number => enc.tag(1),
+            TypeF::Bool => enc.tag(2),
+            TypeF::String => enc.tag(3),
+            TypeF::Symbol => enc.tag(4),
+            TypeF::Flat(t) => {
+                enc.tag(5);
+                t.encode(enc)?;
+            }
+            TypeF::Arrow(dom, codom) => {
+                enc.tag(6);
+                dom.encode(enc)?;
+                codom.encode(enc)?;
+            }
+            TypeF::Var(id) => {
+                enc.tag(7);
+                id.encode(enc)?;
+            }
+            TypeF::Forall {
+                var,
+                var_kind,
+                body,
+            } => {
+                enc.tag(8);
+                var.encode(enc)?;
+                var_kind.encode(enc)?;
+                body.encode(enc)?;
+            }
+            TypeF::Enum(erows) => {
+                enc.tag(9);
+                erows.encode(enc)?;
+            }
+            TypeF::Record(rrows) => {
+                enc.tag(10);
+                rrows.encode(enc)?;
+            }
+            TypeF::Dict {
+                type_fields,
+                flavour,
+            } => {
+                enc.tag(11);
+                type_fields.encode(enc)?;
+                enc.tag(match flavour {
+                    DictTypeFlavour::Type => 0,
+                    DictTypeFlavour::Contract => 1,
+                });
+            }
+            TypeF::Array(elts) => {
+                enc.tag(12);
+                elts.encode(enc)?;
+            }
+            TypeF::Wildcard(id) => {
+                enc.tag(13);
+                id.encode(enc)?;
+            }
+        }
+
+        self.pos.encode(enc)
+    }
+}
+
+impl Decode for Type {
+    fn decode(dec: &mut Decoder<'_>) -> Result<Self> {
+        let typ = match dec.tag()? {
+            0 => TypeF::Dyn,
+            1 => TypeF::Number,
+            2 => TypeF::Bool,
+            3 => TypeF::String,
+            4 => TypeF::Symbol,
+            5 => TypeF::Flat(RichTerm::decode(dec)?),
+            6 => TypeF::Arrow(Box::decode(dec)?, Box::decode(dec)?),
+            7 => TypeF::Var(Ident::decode(dec)?),
+            8 => TypeF::Forall {
+                var: LocIdent::decode(dec)?,
+                var_kind: VarKind::decode(dec)?,
+                body: Box::decode(dec)?,
+            },
+            9 => TypeF::Enum(EnumRows::decode(dec)?),
+            10 => TypeF::Record(RecordRows::decode(dec)?),
+            11 => TypeF::Dict {
+                type_fields: Box::decode(dec)?,
+                flavour: match dec.tag()? {
+                    0 => DictTypeFlavour::Type,
+                    1 => DictTypeFlavour::Contract,
+                    _ => return Err(CodecError::Malformed),
+                },
+            },
+            12 => TypeF::Array(Box::decode(dec)?),
+            13 => TypeF::Wildcard(usize::decode(dec)?),
+            _ => return Err(CodecError::Malformed),
+        };
+
+        Ok(Type {
+            typ,
+            pos: TermPos::decode(dec)?,
+        })
+    }
+}
+
+impl Encode for VarKind {
+    fn encode(&self, enc: &mut Encoder) -> Result<()> {
+        match self {
+            VarKind::Type => enc.tag(0),
+            VarKind::EnumRows => enc.tag(1),
+            VarKind::RecordRows { excluded } => {
+                enc.tag(2);
+                excluded.encode(enc)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Decode for VarKind {
+    fn decode(dec: &mut Decoder<'_>) -> Result<Self> {
+        match dec.tag()? {
+            0 => Ok(VarKind::Type),
+            1 => Ok(VarKind::EnumRows),
+            2 => Ok(VarKind::RecordRows {
+                excluded: HashSet::decode(dec)?,
+            }),
+            _ => Err(CodecError::Malformed),
+        }
+    }
+}
+
+impl Encode for RecordRows {
+    fn encode(&self, enc: &mut Encoder) -> Result<()> {
+        match &self.0 {
+            RecordRowsF::Empty => enc.tag(0),
+            RecordRowsF::Extend { row, tail } => {
+                enc.tag(1);
+                row.id.encode(enc)?;
+                row.typ.encode(enc)?;
+                tail.encode(enc)?;
+            }
+            RecordRowsF::TailVar(id) => {
+                enc.tag(2);
+                id.encode(enc)?;
+            }
+            RecordRowsF::TailDyn => enc.tag(3),
+        }
+
+        Ok(())
+    }
+}
+
+impl Decode for RecordRows {
+    fn decode(dec: &mut Decoder<'_>) -> Result<Self> {
+        let rrows = match dec.tag()? {
+            0 => RecordRowsF::Empty,
+            1 => RecordRowsF::Extend {
+                row: RecordRowF {
+                    id: LocIdent::decode(dec)?,
+                    typ: Box::decode(dec)?,
+                },
+                tail: Box::decode(dec)?,
+            },
+            2 => RecordRowsF::TailVar(LocIdent::decode(dec)?),
+            3 => RecordRowsF::TailDyn,
+            _ => return Err(CodecError::Malformed),
+        };
+
+        Ok(RecordRows(rrows))
+    }
+}
+
+impl Encode for EnumRows {
+    fn encode(&self, enc: &mut Encoder) -> Result<()> {
+        match &self.0 {
+            EnumRowsF::Empty => enc.tag(0),
+            EnumRowsF::Extend { row, tail } => {
+                enc.tag(1);
+                row.encode(enc)?;
+                tail.encode(enc)?;
+            }
+            EnumRowsF::TailVar(id) => {
+                enc.tag(2);
+                id.encode(enc)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Decode for EnumRows {
+    fn decode(dec: &mut Decoder<'_>) -> Result<Self> {
+        let erows = match dec.tag()? {
+            0 => EnumRowsF::Empty,
+            1 => EnumRowsF::Extend {
+                row: LocIdent::decode(dec)?,
+                tail: Box::decode(dec)?,
+            },
+            2 => EnumRowsF::TailVar(LocIdent::decode(dec)?),
+            _ => return Err(CodecError::Malformed),
+        };
+
+        Ok(EnumRows(erows))
+    }
+}
+
+/// Generate the encoding of the variants of an operator enum which don't carry any data, as the
+/// name of the variant. The other variants, which carry data, must be listed as well so that the
+/// match stays exhaustive: adding a variant to an operator enum doesn't compile until it's handled
+/// here. The variants carrying data are encoded by hand in the corresponding `Encode` and `Decode`
+/// implementations.
+macro_rules! unit_variants {
+    (
+        $enum:ident,
+        $to_name:ident,
+        $from_name:ident,
+        [$($variant:ident),* $(,)?],
+        with_data: [$($with_data:ident),* $(,)?] $(,)?
+    ) => {
+        fn $to_name(op: &$enum) -> Option<&'static str> {
+            match op {
+                $($enum::$variant() => Some(stringify!($variant)),)*
+                $($enum::$with_data { .. } => None,)*
+            }
+        }
+
+        fn $from_name(name: &str) -> Option<$enum> {
+            match name {
+                $(stringify!($variant) => Some($enum::$variant()),)*
+                _ => None,
+            }
+        }
+    };
+}
+
+unit_variants!(
+    UnaryOp,
+    unary_to_name,
+    unary_from_name,
+    [
+        Ite,
+        Typeof,
+        BoolAnd,
+        BoolOr,
+        BoolNot,
+        Blame,
+        ArrayMap,
+        RecordMap,
+        ChangePolarity,
+        Pol,
+        GoDom,
+        GoCodom,
+        GoArray,
+        GoDict,
+        Seq,
+        DeepSeq,
+        ArrayLength,
+        ArrayGen,
+        ChunksConcat,
+        FieldsOf,
+        ValuesOf,
+        StrTrim,
+        StrChars,
+        StrUppercase,
+        StrLowercase,
+        SemVerParse,
+        UrlParse,
+        StrLength,
+        ToStr,
+        NumFromStr,
+        NumFloor,
+        NumCeil,
+        NumRound,
+        NumSqrt,
+        DateTimeParse,
+        DateTimeIsValid,
+        DurationParse,
+        DurationIsValid,
+        DurationFormat,
+        EnumFromStr,
+        StrIsMatch,
+        StrFind,
+        StrFindAll,
+        StrCaptures,
+        RecDefault,
+        RecForce,
+        RecordEmptyWithTail,
+        Trace,
+        LabelPushDiag,
+        Dualize,
+    ],
+    with_data: [
+        Embed,
+        Match,
+        StaticAccess,
+        StrEncode,
+        StrDecode,
+        StrCheckFormat,
+        StrIsMatchCompiled,
+        StrFindCompiled,
+        StrFindAllCompiled,
+        StrCapturesCompiled,
+        Force,
+    ],
+);
+
+unit_variants!(
+    BinaryOp,
+    binary_to_name,
+    binary_from_name,
+    [
+        Plus,
+        Sub,
+        Mult,
+        Div,
+        Modulo,
+        Pow,
+        NumLog,
+        IntDiv,
+        IntRem,
+        Gcd,
+        NumToStrFixed,
+        DateTimeFromTimestamp,
+        DateTimeFormat,
+        SemVerCompare,
+        SemVerMatches,
+        CidrContains,
+        StrConcat,
+        Eq,
+        LessThan,
+        LessOrEq,
+        GreaterThan,
+        GreaterOrEq,
+        Assume,
+        ContractTry,
+        Unseal,
+        GoField,
+        DynRemove,
+        DynAccess,
+        HasField,
+        ArrayConcat,
+        ArrayElemAt,
+        ArrayChunks,
+        ArrayWindows,
+        Hash,
+        HashValue,
+        Serialize,
+        Deserialize,
+        StrSplit,
+        StrContains,
+        StrCompare,
+        Seal,
+        ArrayLazyAssume,
+        RecordLazyAssume,
+        LabelWithMessage,
+        LabelWithNotes,
+        LabelAppendNote,
+        LookupTypeVar,
+    ],
+    with_data: [DynExtend, Merge],
+);
+
+unit_variants!(
+    NAryOp,
+    nary_to_name,
+    nary_from_name,
+    [
+        StrReplace,
+        StrReplaceRegex,
+        StrSubstr,
+        MergeContract,
+        RecordSealTail,
+        RecordUnsealTail,
+        InsertTypeVar,
+        ArraySlice,
+        LabelWithRelatedField,
+        RecordAddInvariant,
+        Hmac,
+    ],
+    with_data: [],
+);
+
+impl Encode for Encoding {
+    fn encode(&self, enc: &mut Encoder) -> Result<()> {
+        enc.tag(match self {
+            Encoding::Base64 => 0,
+            Encoding::Base64Url => 1,
+            Encoding::Hex => 2,
+            Encoding::Percent => 3,
+        });
+        Ok(())
+    }
+}
+
+impl Decode for Encoding {
+    fn decode(dec: &mut Decoder<'_>) -> Result<Self> {
+        match dec.tag()? {
+            0 => Ok(Encoding::Base64),
+            1 => Ok(Encoding::Base64Url),
+            2 => Ok(Encoding::Hex),
+            3 => Ok(Encoding::Percent),
+            _ => Err(CodecError::Malformed),
+        }
+    }
+}
+
+impl Encode for StringFormat {
+    fn encode(&self, enc: &mut Encoder) -> Result<()> {
+        enc.tag(match self {
+            StringFormat::SemVer => 0,
+            StringFormat::SemVerReq => 1,
+            StringFormat::Ipv4 => 2,
+            StringFormat::Ipv6 => 3,
+            StringFormat::Cidr => 4,
+            StringFormat::Hostname => 5,
+            StringFormat::Url => 6,
+        });
+        Ok(())
+    }
+}
+
+impl Decode for StringFormat {
+    fn decode(dec: &mut Decoder<'_>) -> Result<Self> {
+        match dec.tag()? {
+            0 => Ok(StringFormat::SemVer),
+            1 => Ok(StringFormat::SemVerReq),
+            2 => Ok(StringFormat::Ipv4),
+            3 => Ok(StringFormat::Ipv6),
+            4 => Ok(StringFormat::Cidr),
+            5 => Ok(StringFormat::Hostname),
+            6 => Ok(StringFormat::Url),
+            _ => Err(CodecError::Malformed),
+        }
+    }
+}
+
+impl Encode for CompiledRegex {
+    fn encode(&self, enc: &mut Encoder) -> Result<()> {
+        enc.str(self.as_str());
+        Ok(())
+    }
+}
+
+impl Decode for CompiledRegex {
+    fn decode(dec: &mut Decoder<'_>) -> Result<Self> {
+        regex::Regex::new(dec.str()?)
+            .map(CompiledRegex)
+            .map_err(|_| CodecError::Malformed)
+    }
+}
+
+impl Encode for UnaryOp {
+    fn encode(&self, enc: &mut Encoder) -> Result<()> {
+        if let Some(name) = unary_to_name(self) {
+            enc.str(name);
+            return Ok(());
+        }
+
+        match self {
+            UnaryOp::Embed(id) => {
+                enc.str("Embed");
+                id.encode(enc)
+            }
+            UnaryOp::Match { has_default } => {
+                enc.str("Match");
+                has_default.encode(enc)
+            }
+            UnaryOp::StaticAccess(id) => {
+                enc.str("StaticAccess");
+                id.encode(enc)
+            }
+            UnaryOp::StrEncode(encoding) => {
+                enc.str("StrEncode");
+                encoding.encode(enc)
+            }
+            UnaryOp::StrDecode(encoding) => {
+                enc.str("StrDecode");
+                encoding.encode(enc)
+            }
+            UnaryOp::StrCheckFormat(format) => {
+                enc.str("StrCheckFormat");
+                format.encode(enc)
+            }
+            UnaryOp::StrIsMatchCompiled(regex) => {
+                enc.str("StrIsMatchCompiled");
+                regex.encode(enc)
+            }
+            UnaryOp::StrFindCompiled(regex) => {
+                enc.str("StrFindCompiled");
+                regex.encode(enc)
+            }
+            UnaryOp::StrFindAllCompiled(regex) => {
+                enc.str("StrFindAllCompiled");
+                regex.encode(enc)
+            }
+            UnaryOp::StrCapturesCompiled(regex) => {
+                enc.str("StrCapturesCompiled");
+                regex.encode(enc)
+            }
+            UnaryOp::Force {
+                ignore_not_exported,
+            } => {
+                enc.str("Force");
+                ignore_not_exported.encode(enc)
+            }
+            _ => Err(CodecError::Unsupported("unary operator")),
+        }
+    }
+}
+
+impl Decode for UnaryOp {
+    fn decode(dec: &mut Decoder<'_>) -> Result<Self> {
+        let name = dec.str()?;
+
+        if let Some(op) = unary_from_name(name) {
+            return Ok(op);
+        }
+
+        match name {
+            "Embed" => LocIdent::decode(dec).map(UnaryOp::Embed),
+            "Match" => Ok(UnaryOp::Match {
+                has_default: bool::decode(dec)?,
+            }),
+            "StaticAccess" => LocIdent::decode(dec).map(UnaryOp::StaticAccess),
+            "StrEncode" => Encoding::decode(dec).map(UnaryOp::StrEncode),
+            "StrDecode" => Encoding::decode(dec).map(UnaryOp::StrDecode),
+            "StrCheckFormat" => StringFormat::decode(dec).map(UnaryOp::StrCheckFormat),
+            "StrIsMatchCompiled" => CompiledRegex::decode(dec).map(UnaryOp::StrIsMatchCompiled),
+            "StrFindCompiled" => CompiledRegex::decode(dec).map(UnaryOp::StrFindCompiled),
+            "StrFindAllCompiled" => CompiledRegex::decode(dec).map(UnaryOp::StrFindAllCompiled),
+            "StrCapturesCompiled" => CompiledRegex::decode(dec).map(UnaryOp::StrCapturesCompiled),
+            "Force" => Ok(UnaryOp::Force {
+                ignore_not_exported: bool::decode(dec)?,
+            }),
+            _ => Err(CodecError::Malformed),
+        }
+    }
+}
+
+impl Encode for BinaryOp {
+    fn encode(&self, enc: &mut Encoder) -> Result<()> {
+        if let Some(name) = binary_to_name(self) {
+            enc.str(name);
+            return Ok(());
+        }
+
+        match self {
+            BinaryOp::DynExtend {
+                metadata,
+                pending_contracts,
+                ext_kind,
+            } => {
+                enc.str("DynExtend");
+                metadata.encode(enc)?;
+                pending_contracts.encode(enc)?;
+                (*ext_kind == RecordExtKind::WithValue).encode(enc)
+            }
+            BinaryOp::Merge(label) => {
+                enc.str("Merge");
+                label.span.encode(enc)?;
+                (label.kind == MergeKind::PiecewiseDef).encode(enc)
+            }
+            _ => Err(CodecError::Unsupported("binary operator")),
+        }
+    }
+}
+
+impl Decode for BinaryOp {
+    fn decode(dec: &mut Decoder<'_>) -> Result<Self> {
+        let name = dec.str()?;
+
+        if let Some(op) = binary_from_name(name) {
+            return Ok(op);
+        }
+
+        match name {
+            "DynExtend" => Ok(BinaryOp::DynExtend {
+                metadata: FieldMetadata::decode(dec)?,
+                pending_contracts: Vec::decode(dec)?,
+                ext_kind: if bool::decode(dec)? {
+                    RecordExtKind::WithValue
+                } else {
+                    RecordExtKind::WithoutValue
+                },
+            }),
+            "Merge" => Ok(BinaryOp::Merge(MergeLabel {
+                span: RawSpan::decode(dec)?,
+                kind: if bool::decode(dec)? {
+                    MergeKind::PiecewiseDef
+                } else {
+                    MergeKind::Standard
+                },
+            })),
+            _ => Err(CodecError::Malformed),
+        }
+    }
+}
+
+impl Encode for NAryOp {
+    fn encode(&self, enc: &mut Encoder) -> Result<()> {
+        let name = nary_to_name(self).ok_or(CodecError::Unsupported("n-ary operator"))?;
+        enc.str(name);
+        Ok(())
+    }
+}
+
+impl Decode for NAryOp {
+    fn decode(dec: &mut Decoder<'_>) -> Result<Self> {
+        nary_from_name(dec.str()?).ok_or(CodecError::Malformed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cache::{Cache, ErrorTolerance, SourcePath},
+        transform,
+    };
+
+    fn parse(cache: &mut Cache, source: &str) -> (RichTerm, FileId) {
+        let file_id = cache.add_string(SourcePath::Path("test.ncl".into()), source.to_owned());
+        let (rt, errs) = cache.parse_nocache(file_id).unwrap();
+        assert!(errs.no_errors());
+        (rt, file_id)
+    }
+
+    fn round_trip(rt: &RichTerm, file_id: FileId) -> RichTerm {
+        let (data, imports) = encode(rt, file_id).unwrap();
+        assert!(imports.is_empty());
+        decode(&data, file_id, &imports).unwrap()
+    }
+
+    #[test]
+    fn round_trip_parsed() {
+        let mut cache = Cache::new(ErrorTolerance::Strict);
+        let (rt, file_id) = parse(
+            &mut cache,
+            r#"
+            let f : forall a. { x : a; ..} -> Array a = fun { x, ..} => [x, x] in
+            let { y, z = zz @ { w }, .. } = { z.w = 2, y = "%{"a"}b" } in
+            {
+              a | Number | default = 1.5 / 7,
+              "%{"b"}" = std.string.find "a" "b",
+              c = match { 'Foo => -3, _ => null },
+              d = f { x = 'Bar } @ [1e10],
+              e | { _ : Number } = {},
+            } & { a | priority 10 = 2 }
+            "#,
+        );
+
+        assert_eq!(round_trip(&rt, file_id), rt);
+    }
+
+    #[test]
+    fn round_trip_transformed() {
+        let mut cache = Cache::new(ErrorTolerance::Strict);
+        let (rt, file_id) = parse(
+            &mut cache,
+            "let rec r = { a = 1, b = a + 1, c = [a, b] } in r.c",
+        );
+        let rt = transform::transform(rt, None).unwrap();
+
+        // Generated identifiers are refreshed, so we compare the printed terms with the generated
+        // identifiers erased.
+        let erase = |rt: &RichTerm| {
+            let printed = format!("{rt}");
+            regex::Regex::new("%[0-9]+")
+                .unwrap()
+                .replace_all(&printed, "%")
+                .into_owned()
+        };
+
+        assert_eq!(erase(&round_trip(&rt, file_id)), erase(&rt));
+    }
+
+    #[test]
+    fn foreign_spans() {
+        let mut cache = Cache::new(ErrorTolerance::Strict);
+        let (rt, _) = parse(&mut cache, "1 + 1");
+        let other_id = cache.add_string(SourcePath::Query, String::new());
+
+        // Positions pointing to another file are dropped.
+        let (data, _) = encode(&rt, other_id).unwrap();
+        let decoded = decode(&data, other_id, &[]).unwrap();
+        assert_eq!(decoded.pos, TermPos::None);
+
+        // Labels can't lose their position.
+        let (annotated, _) = parse(&mut cache, "1 | Number");
+        assert_eq!(
+            encode(&annotated, other_id),
+            Err(CodecError::Unsupported("span pointing to another file"))
+        );
+    }
+
+    #[test]
+    fn malformed() {
+        let mut cache = Cache::new(ErrorTolerance::Strict);
+        let (rt, file_id) = parse(&mut cache, "{ foo = [1, 2, 3] }");
+        let (data, _) = encode(&rt, file_id).unwrap();
+
+        for len in 0..data.len() {
+            assert_eq!(
+                decode(&data[..len], file_id, &[]),
+                Err(CodecError::Malformed)
+            );
+        }
+    }
+}
@@ -0,0 +1,410 @@
+//! Opt-in persistent cache of transformed terms.
+//!
+//! Each run of Nickel parses, typechecks and transforms the program, its imports and the standard
+//! library again, even when none of them changed since the previous run. When enabled (see
+//! [crate::cache::Cache::set_persistent_cache]), the persistent cache stores the result of this
+//! front-end on disk, in the [`EntryState::Transformed`](crate::cache::EntryState::Transformed)
+//! state, so that warm runs can skip it entirely.
+//!
+//! # Keys and invalidation
+//!
+//! Entries are stored in a flat directory, one file per entry, named after a SHA-256 hash of:
+//!
+//! - the version of Nickel and the version of the encoding of terms,
+//! - the content of the standard library, on which typechecking depends,
+//! - the stage of the pipeline the entry corresponds to (see [Stage]),
+//! - the name and the content of the source.
+//!
+//! Any change to one of them thus results in a cache miss. The typechecking of a file also
+//! depends on the files it imports. An entry records the path and the content hash of the direct
+//! imports of the file, and is only restored if all of them are unchanged and can be restored as
+//! well.
+//!
+//! The standard library is cached in both its parsed form, which is needed to build the initial
+//! typing context, and its transformed form. Embedding it directly into the binary at build time
+//! would be even faster, but would require running the Nickel front-end from the build script of
+//! the very crate implementing it.
+//!
+//! Failing to read or write the persistent cache is never an error: a missing, outdated or
+//! corrupted entry is simply a cache miss, and the program is processed as usual.
+//!
+//! # In-memory storage
+//!
+//! Entries can also be kept in memory (see [PersistentCache::in_memory]). They are then shared
+//! between the clones of the cache, including across threads, which is how
+//! [`Program::eval_full_for_export_parallel`](crate::program::Program::eval_full_for_export_parallel)
+//! hands the result of the front-end over to its worker threads.
+pub(crate) mod codec;
+
+use crate::{hash::HashAlgorithm, stdlib, term::RichTerm};
+use codespan::FileId;
+use std::{
+    collections::HashMap,
+    ffi::OsStr,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+pub use codec::CodecError;
+
+/// The magic number at the beginning of each entry.
+const MAGIC: &[u8; 4] = b"NCLC";
+
+/// A counter distinguishing the temporary files written by the threads of the same process.
+static TMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// The stage of the pipeline a cache entry corresponds to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    /// The term has just been parsed.
+    Parsed,
+    /// The term has been typechecked and transformed.
+    Transformed,
+}
+
+impl Stage {
+    fn tag(self) -> u8 {
+        match self {
+            Stage::Parsed => 0,
+            Stage::Transformed => 1,
+        }
+    }
+}
+
+/// Return the hash of the content of a source, as recorded for the imports of an entry.
+pub fn content_hash(content: &str) -> String {
+    HashAlgorithm::Sha256.digest(content.as_bytes())
+}
+
+/// A persistent cache, stored in a directory or in memory.
+#[derive(Debug, Clone)]
+pub struct PersistentCache {
+    storage: Storage,
+    /// The hash of the standard library, computed once and for all.
+    stdlib_hash: String,
+}
+
+/// Where the entries of a [PersistentCache] are stored.
+#[derive(Debug, Clone)]
+enum Storage {
+    /// One file per entry in a directory.
+    Dir(PathBuf),
+    /// In memory, shared between the clones of the cache.
+    Memory(Arc<Mutex<HashMap<String, Arc<[u8]>>>>),
+}
+
+/// An entry loaded from the persistent cache.
+#[derive(Debug, Clone)]
+pub struct Entry {
+    /// The direct imports of the file, as their normalized path and the hash of their content.
+    imports: Vec<(PathBuf, String)>,
+    /// The encoded term.
+    term: Vec<u8>,
+}
+
+impl Entry {
+    /// The direct imports of the file, as their normalized path and the hash of their content.
+    pub fn imports(&self) -> &[(PathBuf, String)] {
+        &self.imports
+    }
+
+    /// Decode the term of the entry, as the content of the file `file_id`. `imports` must hold
+    /// the ids of the files corresponding to [Self::imports], in the same order.
+    pub fn decode_term(&self, file_id: FileId, imports: &[FileId]) -> Result<RichTerm, CodecError> {
+        codec::decode(&self.term, file_id, imports)
+    }
+}
+
+impl PersistentCache {
+    /// Create a persistent cache stored in `dir`. The directory is created on the first write if
+    /// it doesn't exist.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self::with_storage(Storage::Dir(dir.into()))
+    }
+
+    /// Create a persistent cache stored in memory. Its entries are shared between its clones,
+    /// which can be sent to other threads, and are dropped with the last of them.
+    pub fn in_memory() -> Self {
+        Self::with_storage(Storage::Memory(Arc::default()))
+    }
+
+    fn with_storage(storage: Storage) -> Self {
+        let stdlib_content: String = stdlib::modules()
+            .into_iter()
+            .map(|module| module.content())
+            .collect();
+
+        PersistentCache {
+            storage,
+            stdlib_hash: content_hash(&stdlib_content),
+        }
+    }
+
+    /// The directory the cache is stored in, or `None` if it's stored in memory.
+    pub fn dir(&self) -> Option<&Path> {
+        match &self.storage {
+            Storage::Dir(dir) => Some(dir),
+            Storage::Memory(_) => None,
+        }
+    }
+
+    /// Compute the key of the entry corresponding to the source `name` with content `content` at
+    /// the given stage.
+    pub fn key(&self, stage: Stage, name: &OsStr, content: &str) -> String {
+        let name = name.to_string_lossy();
+        let parts: [&[u8]; 6] = [
+            env!("CARGO_PKG_VERSION").as_bytes(),
+            &codec::FORMAT_VERSION.to_le_bytes(),
+            self.stdlib_hash.as_bytes(),
+            &[stage.tag()],
+            name.as_bytes(),
+            content.as_bytes(),
+        ];
+
+        // Each part is prefixed with its length, so that different sequences of parts can't be
+        // concatenated to the same data.
+        let mut data = Vec::new();
+        for part in parts {
+            data.extend_from_slice(&(part.len() as u64).to_le_bytes());
+            data.extend_from_slice(part);
+        }
+
+        HashAlgorithm::Sha256.digest(&data)
+    }
+
+    fn entry_path(dir: &Path, key: &str) -> PathBuf {
+        dir.join(format!("{key}.bin"))
+    }
+
+    /// Return `true` if there is an entry for this key.
+    pub fn contains(&self, key: &str) -> bool {
+        match &self.storage {
+            Storage::Dir(dir) => Self::entry_path(dir, key).is_file(),
+            Storage::Memory(entries) => entries
+                .lock()
+                .map_or(false, |entries| entries.contains_key(key)),
+        }
+    }
+
+    /// Load the entry corresponding to a key. Return `None` if there is no such entry, or if it
+    /// can't be read.
+    pub fn load(&self, key: &str) -> Option<Entry> {
+        match &self.storage {
+            Storage::Dir(dir) => parse_entry(&fs::read(Self::entry_path(dir, key)).ok()?),
+            Storage::Memory(entries) => {
+                let data = entries.lock().ok()?.get(key).cloned()?;
+                parse_entry(&data)
+            }
+        }
+    }
+
+    /// Store the term of the file `file_id` under `key`. `import_info` must return the normalized
+    /// path and the content hash of the files imported by the term.
+    ///
+    /// Return `false` if the term couldn't be stored, either because it contains data that can't
+    /// be persisted, because one of its imports isn't a file, or because of an IO error.
+    pub fn store(
+        &self,
+        key: &str,
+        file_id: FileId,
+        term: &RichTerm,
+        import_info: impl Fn(FileId) -> Option<(PathBuf, String)>,
+    ) -> bool {
+        let Ok((term, imports)) = codec::encode(term, file_id) else {
+            return false;
+        };
+        let Some(imports) = imports
+            .into_iter()
+            .map(import_info)
+            .collect::<Option<Vec<_>>>()
+        else {
+            return false;
+        };
+
+        let Some(data) = serialize_entry(&imports, &term) else {
+            return false;
+        };
+
+        match &self.storage {
+            Storage::Dir(dir) => Self::write_atomic(dir, key, &data).is_ok(),
+            Storage::Memory(entries) => entries
+                .lock()
+                .map(|mut entries| entries.insert(String::from(key), data.into()))
+                .is_ok(),
+        }
+    }
+
+    /// Write an entry to a temporary file first and then rename it, so that concurrent runs never
+    /// observe a partially written entry. The name of the temporary file is unique across both
+    /// processes and threads.
+    fn write_atomic(dir: &Path, key: &str, data: &[u8]) -> io::Result<()> {
+        fs::create_dir_all(dir)?;
+        let tmp_path = dir.join(format!(
+            "{key}.{}.{}.tmp",
+            std::process::id(),
+            TMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::write(&tmp_path, data)?;
+        fs::rename(&tmp_path, Self::entry_path(dir, key)).map_err(|err| {
+            let _ = fs::remove_file(&tmp_path);
+            err
+        })
+    }
+}
+
+fn push_bytes(data: &mut Vec<u8>, bytes: &[u8]) {
+    data.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+    data.extend_from_slice(bytes);
+}
+
+fn serialize_entry(imports: &[(PathBuf, String)], term: &[u8]) -> Option<Vec<u8>> {
+    let mut data = Vec::with_capacity(term.len() + 64);
+    data.extend_from_slice(MAGIC);
+    data.extend_from_slice(&(imports.len() as u32).to_le_bytes());
+
+    for (path, hash) in imports {
+        push_bytes(&mut data, path.to_str()?.as_bytes());
+        push_bytes(&mut data, hash.as_bytes());
+    }
+
+    data.extend_from_slice(term);
+    Some(data)
+}
+
+fn parse_entry(data: &[u8]) -> Option<Entry> {
+    fn take<'a>(data: &mut &'a [u8], len: usize) -> Option<&'a [u8]> {
+        if data.len() < len {
+            return None;
+        }
+        let (taken, rest) = data.split_at(len);
+        *data = rest;
+        Some(taken)
+    }
+
+    fn take_u32(data: &mut &[u8]) -> Option<u32> {
+        Some(u32::from_le_bytes(take(data, 4)?.try_into().ok()?))
+    }
+
+    fn take_str<'a>(data: &mut &'a [u8]) -> Option<&'a str> {
+        let len = take_u32(data)? as usize;
+        std::str::from_utf8(take(data, len)?).ok()
+    }
+
+    let mut data = data;
+
+    if take(&mut data, MAGIC.len())? != MAGIC {
+        return None;
+    }
+
+    let imports = (0..take_u32(&mut data)?)
+        .map(|_| {
+            let path = PathBuf::from(take_str(&mut data)?);
+            let hash = String::from(take_str(&mut data)?);
+            Some((path, hash))
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    Some(Entry {
+        imports,
+        term: data.to_vec(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cache::{Cache, ErrorTolerance, SourcePath},
+        term::Term,
+    };
+
+    #[test]
+    fn keys() {
+        let cache = PersistentCache::new("unused");
+        let key = cache.key(Stage::Transformed, OsStr::new("main.ncl"), "1 + 1");
+
+        assert_eq!(
+            key,
+            cache.key(Stage::Transformed, OsStr::new("main.ncl"), "1 + 1")
+        );
+        assert_ne!(
+            key,
+            cache.key(Stage::Parsed, OsStr::new("main.ncl"), "1 + 1")
+        );
+        assert_ne!(
+            key,
+            cache.key(Stage::Transformed, OsStr::new("other.ncl"), "1 + 1")
+        );
+        assert_ne!(
+            key,
+            cache.key(Stage::Transformed, OsStr::new("main.ncl"), "1 + 2")
+        );
+        // The length prefix prevents ambiguous concatenations.
+        assert_ne!(
+            cache.key(Stage::Transformed, OsStr::new("a"), "bc"),
+            cache.key(Stage::Transformed, OsStr::new("ab"), "c")
+        );
+    }
+
+    fn check_store_and_load(persistent: PersistentCache) {
+        let mut cache = Cache::new(ErrorTolerance::Strict);
+        let main_id = cache.add_string(
+            SourcePath::Path("main.ncl".into()),
+            String::from("{ foo = 1 }"),
+        );
+        let lib_id = cache.add_string(SourcePath::Path("lib.ncl".into()), String::from("1"));
+        let (main, _) = cache.parse_nocache(main_id).unwrap();
+        let term = crate::mk_app!(main, Term::ResolvedImport(lib_id));
+
+        let key = persistent.key(Stage::Parsed, OsStr::new("main.ncl"), "{ foo = 1 }");
+        assert!(!persistent.contains(&key));
+        assert!(persistent.store(&key, main_id, &term, |id| {
+            assert_eq!(id, lib_id);
+            Some((PathBuf::from("/lib.ncl"), content_hash("1")))
+        }));
+
+        let entry = persistent.load(&key).unwrap();
+        assert_eq!(
+            entry.imports(),
+            &[(PathBuf::from("/lib.ncl"), content_hash("1"))]
+        );
+        assert_eq!(entry.decode_term(main_id, &[lib_id]), Ok(term.clone()));
+
+        // Terms whose imports can't be described aren't stored.
+        let other_key = persistent.key(Stage::Parsed, OsStr::new("other.ncl"), "");
+        assert!(!persistent.store(&other_key, main_id, &term, |_| None));
+        assert!(!persistent.contains(&other_key));
+    }
+
+    #[test]
+    fn store_and_load() {
+        let dir = tempfile::tempdir().unwrap();
+        check_store_and_load(PersistentCache::new(dir.path()));
+    }
+
+    #[test]
+    fn store_and_load_in_memory() {
+        let persistent = PersistentCache::in_memory();
+        check_store_and_load(persistent.clone());
+
+        // Entries are shared between clones, including across threads.
+        let key = persistent.key(Stage::Parsed, OsStr::new("main.ncl"), "{ foo = 1 }");
+        let shared = persistent.clone();
+        assert!(std::thread::spawn(move || shared.contains(&key))
+            .join()
+            .unwrap());
+    }
+
+    #[test]
+    fn corrupted_entries() {
+        assert!(parse_entry(b"").is_none());
+        assert!(parse_entry(b"NCLD\0\0\0\0").is_none());
+        // The number of imports doesn't match the data.
+        assert!(parse_entry(b"NCLC\x01\0\0\0").is_none());
+        assert!(parse_entry(b"NCLC\0\0\0\0").is_some());
+    }
+}
@@ -50,7 +50,7 @@ fn sorted_map<K: Ord, V>(m: &'_ IndexMap<K, V>) -> Vec<(&'_ K, &'_ V)> {
 }
 
 /// Escape a string to make it suitable for placing between quotes in Nickel
-fn escape(s: &str) -> String {
+pub(crate) fn escape(s: &str) -> String {
     s.replace('\\', "\\\\")
         .replace("%{", "\\%{")
         .replace('\"', "\\\"")
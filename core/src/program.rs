@@ -22,15 +22,22 @@
 //! Each such value is added to the initial environment before the evaluation of the program.
 use crate::{
     cache::*,
-    error::{report, ColorOpt, Error, IntoDiagnostics, ParseError},
+    error::{report, ColorOpt, Error, EvalError, IOError, IntoDiagnostics, ParseError},
     eval,
-    eval::{cache::Cache as EvalCache, VirtualMachine},
-    identifier::LocIdent,
+    eval::{
+        cache::{Cache as EvalCache, CacheIndex},
+        limits::Limits,
+        Closure, IdentKind, VirtualMachine,
+    },
+    identifier::{Ident, LocIdent},
     label::Label,
+    persistent_cache::{codec, PersistentCache},
+    position::TermPos,
     term::{
-        make as mk_term, make::builder, record::Field, record::RecordData, BinaryOp, MergePriority,
-        RichTerm, RuntimeContract, Term,
+        make as mk_term, make::builder, record::Field, record::RecordData, BinaryOp, BindingType,
+        IndexMap, MergePriority, RichTerm, RuntimeContract, Term, UnaryOp,
     },
+    vfs::FileSystem,
 };
 
 use codespan::FileId;
@@ -38,9 +45,14 @@ use codespan_reporting::term::termcolor::Ansi;
 use std::path::PathBuf;
 
 use std::{
-    ffi::OsString,
+    cell::RefCell,
+    collections::HashMap,
+    ffi::{OsStr, OsString},
     io::{self, Cursor, Read, Write},
+    num::NonZeroUsize,
+    rc::Rc,
     result::Result,
+    sync::{atomic::AtomicU64, Arc},
 };
 
 /// Attribute path provided when querying metadata.
@@ -112,6 +124,7 @@ impl QueryPath {
 
 /// Several CLI commands accept additional overrides specified directly on the command line. They
 /// are represented by this structure.
+#[derive(Clone, Debug)]
 pub struct FieldOverride {
     /// The field path identifying the (potentially nested) field to override.
     pub path: Vec<String>,
@@ -121,6 +134,21 @@ pub struct FieldOverride {
     pub priority: MergePriority,
 }
 
+/// The state kept across calls to [Program::eval_incremental].
+#[derive(Default)]
+struct Incremental {
+    /// The overrides set with [Program::set_override], with their priority and the source holding
+    /// their value.
+    overrides: IndexMap<Vec<String>, (MergePriority, FileId)>,
+    /// The program before overriding, together with the initial environment.
+    main: Option<(CacheIndex, eval::Environment)>,
+    /// Whether the files of the program have been reloaded since `main` was built.
+    main_stale: bool,
+    /// The overridden program forced for export, together with the cache elements holding the
+    /// value of each override.
+    result: Option<(CacheIndex, HashMap<Vec<String>, CacheIndex>)>,
+}
+
 /// A Nickel program.
 ///
 /// Manage a file database, which stores the original source code of the program and eventually the
@@ -130,6 +158,8 @@ pub struct Program<EC: EvalCache> {
     main_id: FileId,
     /// The state of the Nickel virtual machine.
     vm: VirtualMachine<Cache, EC>,
+    /// The state of incremental evaluation.
+    incremental: Incremental,
     /// The color option to use when reporting errors.
     pub color_opt: ColorOpt,
 }
@@ -143,14 +173,35 @@ impl<EC: EvalCache> Program<EC> {
     pub fn new_from_file(
         path: impl Into<OsString>,
         trace: impl Write + 'static,
+    ) -> std::io::Result<Self> {
+        Program::new_from_cache_file(Cache::new(ErrorTolerance::Strict), path, trace)
+    }
+
+    /// Create a program by reading it from `file_system`, from which its imports are read as
+    /// well. See [Self::set_file_system].
+    pub fn new_from_file_system(
+        path: impl Into<OsString>,
+        file_system: impl FileSystem + 'static,
+        trace: impl Write + 'static,
     ) -> std::io::Result<Self> {
         let mut cache = Cache::new(ErrorTolerance::Strict);
+        cache.set_file_system(file_system);
+        Program::new_from_cache_file(cache, path, trace)
+    }
+
+    /// Create a program by reading it with `cache`.
+    fn new_from_cache_file(
+        mut cache: Cache,
+        path: impl Into<OsString>,
+        trace: impl Write + 'static,
+    ) -> std::io::Result<Self> {
         let main_id = cache.add_file(path)?;
         let vm = VirtualMachine::new(cache, trace);
 
         Ok(Self {
             main_id,
             vm,
+            incremental: Incremental::default(),
             color_opt: clap::ColorChoice::Auto.into(),
         })
     }
@@ -173,6 +224,7 @@ impl<EC: EvalCache> Program<EC> {
         Ok(Self {
             main_id,
             vm,
+            incremental: Incremental::default(),
             color_opt: clap::ColorChoice::Auto.into(),
         })
     }
@@ -230,12 +282,25 @@ impl<EC: EvalCache> Program<EC> {
         &mut self,
         overrides: impl IntoIterator<Item = FieldOverride>,
     ) -> Result<RichTerm, Error> {
+        let (t, initial_env) = self.prepare_export(overrides)?;
+        self.vm.reset();
+        self.vm
+            .eval_full_for_export(t, &initial_env)
+            .map_err(|e| e.into())
+    }
+
+    /// Prepare the program for export, merging it with `overrides`. See
+    /// [Self::eval_full_for_export].
+    fn prepare_export(
+        &mut self,
+        overrides: impl IntoIterator<Item = FieldOverride>,
+    ) -> Result<(RichTerm, eval::Environment), Error> {
         let mut overrides = overrides.into_iter().peekable();
 
-        let (t, initial_env) = match overrides.peek() {
+        match overrides.peek() {
             // If there are no overrides, we avoid the boilerplate of creating an empty record and
             // merging it with the current program
-            None => self.prepare_eval()?,
+            None => self.prepare_eval(),
             Some(_) => {
                 let mut record = builder::Record::new();
 
@@ -262,15 +327,384 @@ impl<EC: EvalCache> Program<EC> {
                 // without referring to any source position.
                 let wrapper =
                     mk_term::op2(BinaryOp::Merge(Label::default().into()), t, built_record);
-                (wrapper, initial_env)
+                Ok((wrapper, initial_env))
             }
-        };
+        }
+    }
+
+    /// Same as [Self::prepare_export], but store the program in the evaluation cache, such that
+    /// it's evaluated only once when its fields are exported separately. Return a variable bound
+    /// to the program in the returned environment.
+    fn prepare_export_shared(
+        &mut self,
+        overrides: impl IntoIterator<Item = FieldOverride>,
+    ) -> Result<(RichTerm, eval::Environment), Error> {
+        let (t, mut initial_env) = self.prepare_export(overrides)?;
+        let main = self.vm.cache.add(
+            Closure::atomic_closure(t),
+            IdentKind::Let,
+            BindingType::Normal,
+        );
+        let main_id = LocIdent::fresh();
+        initial_env.insert(main_id.ident(), main);
+
+        Ok((Term::Var(main_id).into(), initial_env))
+    }
+
+    /// Fully evaluate the field `id` of a program prepared by [Self::prepare_export_shared].
+    fn export_field(
+        &mut self,
+        main: &RichTerm,
+        initial_env: &eval::Environment,
+        id: Ident,
+    ) -> Result<RichTerm, Error> {
+        let field = mk_term::op1(UnaryOp::StaticAccess(id.into()), main.clone());
         self.vm.reset();
         self.vm
-            .eval_full_for_export(t, &initial_env)
+            .eval_full_for_export(field, initial_env)
             .map_err(|e| e.into())
     }
 
+    /// Same as [Self::eval_full_for_export], but evaluate the top-level fields of the program on
+    /// up to `threads` threads.
+    ///
+    /// Terms can't be shared between threads. Instead, the standard library, the program and its
+    /// imports are processed once on the current thread and stored in the persistent cache (see
+    /// [crate::persistent_cache]), in memory if it isn't enabled otherwise. Each thread restores
+    /// them from there, evaluates its share of the fields, and sends them back encoded, to be put
+    /// together in the final record. This only pays off when evaluating the fields takes
+    /// significantly longer than restoring the program. The trace output of each thread is
+    /// written once all of them are done, in a deterministic order, but the top-level definitions
+    /// of the program might be traced once per thread.
+    ///
+    /// The result is the same as the one of [Self::eval_full_for_export]. Programs that don't
+    /// evaluate to a record, which have record-level invariants, or which read files from a
+    /// [crate::vfs::FileSystem] set by the host, are evaluated sequentially. If the evaluation of
+    /// some fields fails, the error of the first of them in alphabetical order is returned.
+    pub fn eval_full_for_export_parallel(
+        &mut self,
+        overrides: Vec<FieldOverride>,
+        threads: NonZeroUsize,
+    ) -> Result<RichTerm, Error> {
+        // The in-memory cache must be enabled before the standard library is loaded, so that its
+        // parsed form, which the workers need to build their typing context, is stored as well.
+        let cache = self.vm.import_resolver_mut();
+        if threads.get() > 1 && cache.persistent_cache().is_none() && !cache.stdlib_loaded() {
+            cache.set_persistent_cache(PersistentCache::in_memory());
+        }
+
+        let (main, initial_env) = self.prepare_export_shared(overrides.clone())?;
+
+        self.vm.reset();
+        let whnf = self.vm.eval(main.clone(), &initial_env)?;
+
+        let mut fields: Vec<Ident> = match whnf.as_ref() {
+            Term::Record(record)
+                if threads.get() > 1
+                    && record.invariants.is_empty()
+                    && self.vm.import_resolver().uses_os_file_system() =>
+            {
+                record
+                    .fields
+                    .iter()
+                    .filter(|(_, field)| {
+                        !(field.is_empty_optional() || field.metadata.not_exported)
+                    })
+                    .map(|(id, _)| id.ident())
+                    .collect()
+            }
+            _ => Vec::new(),
+        };
+
+        if fields.len() < 2 {
+            self.vm.reset();
+            return self
+                .vm
+                .eval_full_for_export(main, &initial_env)
+                .map_err(|e| e.into());
+        }
+
+        // Each thread evaluates its fields in alphabetical order and stops at the first failure,
+        // so that the first failure of all the threads is the first one in alphabetical order.
+        fields.sort_by(|id1, id2| id1.label().cmp(id2.label()));
+
+        // All the threads account for their steps in the same counter, starting from the steps
+        // taken so far, so that the step limit applies to the export as a whole.
+        let steps = Arc::new(AtomicU64::new(self.vm.steps()));
+        self.vm.share_steps(Some(steps.clone()));
+
+        let cache = self.vm.import_resolver();
+        let config = WorkerConfig {
+            source: cache.source(self.main_id),
+            name: cache.name(self.main_id),
+            overrides,
+            limits: self.vm.limits().clone(),
+            steps,
+            import_policy: cache.import_policy().clone(),
+            persistent: cache.persistent_cache().cloned(),
+        };
+
+        let chunks: Vec<Vec<Ident>> = (0..threads.get().min(fields.len()))
+            .map(|i| {
+                fields
+                    .iter()
+                    .skip(i)
+                    .step_by(threads.get())
+                    .copied()
+                    .collect()
+            })
+            .collect();
+
+        let exported: Vec<WorkerOutput> = std::thread::scope(|scope| {
+            let workers: Vec<_> = chunks
+                .iter()
+                .map(|chunk| {
+                    let config = &config;
+                    scope.spawn(move || export_fields::<EC>(config, chunk))
+                })
+                .collect();
+
+            workers
+                .into_iter()
+                .map(|worker| worker.join().expect("export threads don't panic"))
+                .collect()
+        });
+
+        let record_fields =
+            self.gather_exported_fields(&main, &initial_env, fields, &chunks, exported);
+        self.vm.share_steps(None);
+
+        Ok(RichTerm::new(
+            Term::Record(RecordData::with_field_values(record_fields?)),
+            whnf.pos,
+        ))
+    }
+
+    /// Put together the `fields` exported by the threads of [Self::eval_full_for_export_parallel],
+    /// each of which evaluated the corresponding chunk of `chunks`.
+    fn gather_exported_fields(
+        &mut self,
+        main: &RichTerm,
+        initial_env: &eval::Environment,
+        fields: Vec<Ident>,
+        chunks: &[Vec<Ident>],
+        exported: Vec<WorkerOutput>,
+    ) -> Result<IndexMap<LocIdent, RichTerm>, Error> {
+        let mut values: HashMap<Ident, Option<Vec<u8>>> = HashMap::new();
+        let mut first_failed: Option<Ident> = None;
+
+        for (chunk, (result, trace)) in chunks.iter().zip(exported) {
+            self.vm
+                .trace_mut()
+                .write_all(&trace)
+                .map_err(IOError::from)?;
+
+            match result {
+                Ok(encoded) => values.extend(chunk.iter().copied().zip(encoded)),
+                Err(WorkerError::Field(id)) => {
+                    if first_failed.map_or(true, |first| id.label() < first.label()) {
+                        first_failed = Some(id);
+                    }
+                }
+                Err(WorkerError::Load(report)) => {
+                    return Err(Error::EvalError(EvalError::Other(
+                        format!("a worker thread failed to load the program:\n{report}"),
+                        TermPos::None,
+                    )))
+                }
+            }
+        }
+
+        // Errors can't be sent between threads either: the failed field is evaluated again on the
+        // current thread to report the error.
+        if let Some(id) = first_failed {
+            self.export_field(main, initial_env, id)?;
+        }
+
+        let mut record_fields = IndexMap::new();
+
+        for id in fields {
+            let value = match values.remove(&id).flatten() {
+                Some(data) => codec::decode(&data, self.main_id, &[]).map_err(|err| {
+                    EvalError::InternalError(
+                        format!("can't decode a value exported by a worker thread ({err:?})"),
+                        TermPos::None,
+                    )
+                })?,
+                // The value couldn't be encoded, or the field failed in a thread but not here.
+                None => self.export_field(main, initial_env, id)?,
+            };
+
+            record_fields.insert(LocIdent::from(id), value);
+        }
+
+        Ok(record_fields)
+    }
+
+    /// Override a field for the next calls to [Self::eval_incremental], replacing any previous
+    /// override of the same field.
+    ///
+    /// If the evaluation cache tracks dependencies (see [crate::eval::cache::incremental]) and the
+    /// field was already overridden with the same priority, the next evaluation only recomputes
+    /// what depends on this field. Otherwise, it merges the overrides with the program again,
+    /// reusing only the fields of the program which don't depend recursively on other fields.
+    pub fn set_override(&mut self, ovd: FieldOverride) -> Result<(), Error> {
+        let value_file_id = self
+            .vm
+            .import_resolver_mut()
+            .replace_string(SourcePath::Override(ovd.path.clone()), ovd.value);
+        self.vm.prepare_eval(value_file_id)?;
+
+        let previous = self
+            .incremental
+            .overrides
+            .insert(ovd.path.clone(), (ovd.priority.clone(), value_file_id));
+
+        let cell = self
+            .incremental
+            .result
+            .as_ref()
+            .and_then(|(_, cells)| cells.get(&ovd.path));
+
+        let replaced = match (previous, cell) {
+            (Some((priority, _)), Some(cell)) if priority == ovd.priority => {
+                let value = Closure::atomic_closure(Term::ResolvedImport(value_file_id).into());
+                self.vm.cache.replace(cell, value)
+            }
+            _ => false,
+        };
+
+        if !replaced {
+            self.incremental.result = None;
+        }
+
+        Ok(())
+    }
+
+    /// Same as [Self::eval_full_for_export], with the overrides set by [Self::set_override], but
+    /// keep the evaluated program in the evaluation cache to reuse it in the next call.
+    ///
+    /// The result is always substituted from scratch, but the evaluation itself is skipped for
+    /// the values which are still valid since the previous call. Imported files are evaluated at
+    /// most once (see [VirtualMachine::share_imports]), until they're reloaded by [Self::reload].
+    pub fn eval_incremental(&mut self) -> Result<RichTerm, Error> {
+        self.vm.share_imports();
+
+        if self.incremental.main_stale || self.incremental.main.is_none() {
+            let (t, initial_env) = self.prepare_eval()?;
+            let clos = Closure::atomic_closure(t);
+
+            // If the evaluation cache tracks dependencies, replacing the program only invalidates
+            // the values depending on it, and keeps the cache elements of the overrides.
+            let replaced = matches!(
+                &self.incremental.main,
+                Some((main, _)) if self.vm.cache.replace(main, clos.clone())
+            );
+
+            if !replaced {
+                let main = self.vm.cache.add(clos, IdentKind::Let, BindingType::Normal);
+                self.incremental.main = Some((main, initial_env));
+                self.incremental.result = None;
+            }
+
+            self.incremental.main_stale = false;
+        }
+
+        if self.incremental.result.is_none() {
+            self.incremental.result = Some(self.build_incremental_result());
+        }
+
+        let (Some((_, initial_env)), Some((result, _))) =
+            (&self.incremental.main, &self.incremental.result)
+        else {
+            unreachable!("the incremental state has just been initialized")
+        };
+
+        let result_id = LocIdent::fresh();
+        let mut env = eval::Environment::new();
+        env.insert(result_id.ident(), result.clone());
+
+        self.vm.reset();
+        let (term, env) = self.vm.eval_closure(
+            Closure {
+                body: Term::Var(result_id).into(),
+                env,
+            },
+            initial_env,
+        )?;
+
+        Ok(eval::subst(&self.vm.cache, term, initial_env, &env))
+    }
+
+    /// Build the term forcing the merge of the program with the current overrides. The value of
+    /// each override is stored in its own cache element, such that it can be replaced later.
+    fn build_incremental_result(&mut self) -> (CacheIndex, HashMap<Vec<String>, CacheIndex>) {
+        let (main, _) = self
+            .incremental
+            .main
+            .as_ref()
+            .expect("the program is evaluated before being overridden");
+
+        let main_id = LocIdent::fresh();
+        let mut env = eval::Environment::new();
+        env.insert(main_id.ident(), main.clone());
+
+        let mut record = builder::Record::new();
+        let mut cells = HashMap::new();
+
+        for (path, (priority, value_file_id)) in &self.incremental.overrides {
+            let cell = self.vm.cache.add(
+                Closure::atomic_closure(Term::ResolvedImport(*value_file_id).into()),
+                IdentKind::Record,
+                BindingType::Normal,
+            );
+            let cell_id = LocIdent::fresh();
+            env.insert(cell_id.ident(), cell.clone());
+            cells.insert(path.clone(), cell);
+
+            record = record
+                .path(path)
+                .priority(priority.clone())
+                .value(Term::Var(cell_id));
+        }
+
+        let merged = if cells.is_empty() {
+            Term::Var(main_id).into()
+        } else {
+            // See `eval_full_for_export` regarding the use of `Label::default`.
+            mk_term::op2(
+                BinaryOp::Merge(Label::default().into()),
+                Term::Var(main_id),
+                record.build(),
+            )
+        };
+
+        // The merge and the forcing are done in separate cache elements: the result of the merge
+        // doesn't depend on the values of the overrides, and thus survives their replacement.
+        let merged = self.vm.cache.add(
+            Closure { body: merged, env },
+            IdentKind::Record,
+            BindingType::Normal,
+        );
+        let merged_id = LocIdent::fresh();
+        let mut env = eval::Environment::new();
+        env.insert(merged_id.ident(), merged);
+
+        let forced = mk_term::op1(
+            UnaryOp::Force {
+                ignore_not_exported: true,
+            },
+            Term::Var(merged_id),
+        );
+        let result = self.vm.cache.add(
+            Closure { body: forced, env },
+            IdentKind::Record,
+            BindingType::Normal,
+        );
+
+        (result, cells)
+    }
+
     /// Same as `eval_full`, but does not substitute all variables.
     pub fn eval_deep(&mut self) -> Result<RichTerm, Error> {
         let (t, initial_env) = self.prepare_eval()?;
@@ -305,6 +739,75 @@ impl<EC: EvalCache> Program<EC> {
         Ok(())
     }
 
+    /// Return the paths of the files read from the filesystem that make up this program, that is
+    /// the main file and the files it transitively imports. Imports are only known once they have
+    /// been resolved, for example after a first evaluation or typechecking.
+    pub fn source_files(&self) -> Vec<PathBuf> {
+        let cache = self.vm.import_resolver();
+        let mut files: Vec<PathBuf> = std::iter::once(self.main_id)
+            .chain(cache.get_imports_transitive(self.main_id))
+            .filter_map(|file| cache.get_filesystem_path(file))
+            .map(PathBuf::from)
+            .collect();
+
+        files.sort();
+        files.dedup();
+        files
+    }
+
+    /// Return the paths to watch for changes to this program: the [source files](Self::source_files)
+    /// together with the imports which couldn't be read, so that creating a missing import is
+    /// detected as well. This is only complete up to the point where the last run failed, and
+    /// must thus be computed again after each run.
+    pub fn watched_files(&self) -> Vec<PathBuf> {
+        let cache = self.vm.import_resolver();
+        let mut files = self.source_files();
+        files.extend(
+            std::iter::once(self.main_id)
+                .chain(cache.get_imports_transitive(self.main_id))
+                .flat_map(|file| cache.get_failed_imports(file))
+                .map(PathBuf::from),
+        );
+
+        files.sort();
+        files.dedup();
+        files
+    }
+
+    /// Load again the files of this program that have been modified on disk or that failed to
+    /// import a file which has been created since, together with the files depending on them, so
+    /// that subsequent runs see their new content. Everything else, including the standard
+    /// library, is kept in cache. Return `false` if no file changed.
+    pub fn reload(&mut self) -> Result<bool, Error> {
+        let cache = self.vm.import_resolver_mut();
+        let files = cache.get_imports_transitive(self.main_id);
+        let stale = cache.get_stale_transitive(std::iter::once(self.main_id).chain(files));
+
+        if stale.is_empty() {
+            return Ok(false);
+        }
+
+        for file in stale.iter().filter(|file| **file != self.main_id) {
+            if let Some(path) = cache.get_filesystem_path(*file).map(PathBuf::from) {
+                // If an import can't be read anymore, the error is reported when resolving the
+                // imports of the files depending on it.
+                let _ = cache.add_file(path);
+            }
+        }
+
+        // The main file transitively depends on all the other files, so it's always stale at this
+        // point.
+        if let Some(path) = cache.get_filesystem_path(self.main_id).map(PathBuf::from) {
+            self.main_id = cache.add_file(path).map_err(IOError::from)?;
+        }
+
+        self.vm.forget_imports(stale);
+        self.vm.reset();
+        self.incremental.main_stale = true;
+
+        Ok(true)
+    }
+
     /// Wrapper for [`report`].
     pub fn report<E>(&mut self, error: E)
     where
@@ -477,6 +980,37 @@ impl<EC: EvalCache> Program<EC> {
         ))
     }
 
+    /// Replace the evaluation cache. Must be called before the first evaluation, as the state of
+    /// [Self::eval_incremental] is reset.
+    pub fn set_eval_cache(&mut self, eval_cache: EC) {
+        self.vm.cache = eval_cache;
+        self.incremental = Incremental::default();
+    }
+
+    /// Store the result of parsing, typechecking and transforming the program, its imports and the
+    /// standard library in `dir`, and reuse it on subsequent runs. See [crate::persistent_cache].
+    pub fn enable_persistent_cache(&mut self, dir: impl Into<PathBuf>) {
+        self.vm
+            .import_resolver_mut()
+            .set_persistent_cache(PersistentCache::new(dir));
+    }
+
+    /// Limit the resources used by the evaluation of the program. See [crate::eval::limits].
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.vm.set_limits(limits);
+    }
+
+    /// Read the imports of the program, and the main file when reloading it, from `file_system`
+    /// instead of the filesystem of the operating system. See [crate::vfs].
+    pub fn set_file_system(&mut self, file_system: impl FileSystem + 'static) {
+        self.vm.import_resolver_mut().set_file_system(file_system);
+    }
+
+    /// Restrict the files which can be imported by the program. See [ImportPolicy].
+    pub fn set_import_policy(&mut self, policy: ImportPolicy) {
+        self.vm.import_resolver_mut().set_import_policy(policy);
+    }
+
     #[cfg(debug_assertions)]
     pub fn set_skip_stdlib(&mut self) {
         self.vm.import_resolver_mut().skip_stdlib = true;
@@ -530,6 +1064,91 @@ pub fn query<EC: EvalCache>(
     Ok(vm.query(rt, path, &initial_env.eval_env)?)
 }
 
+/// A trace output which can be read back after having been moved into a program.
+#[derive(Clone, Default)]
+struct SharedTrace(Rc<RefCell<Vec<u8>>>);
+
+impl Write for SharedTrace {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// The configuration of the program loaded by each thread of
+/// [Program::eval_full_for_export_parallel].
+struct WorkerConfig<'a> {
+    source: &'a str,
+    name: &'a OsStr,
+    overrides: Vec<FieldOverride>,
+    limits: Limits,
+    /// The step counter shared by all the threads.
+    steps: Arc<AtomicU64>,
+    import_policy: ImportPolicy,
+    persistent: Option<PersistentCache>,
+}
+
+/// The failure of a thread of [Program::eval_full_for_export_parallel].
+enum WorkerError {
+    /// The program couldn't be loaded. Holds the report of the error.
+    Load(String),
+    /// The evaluation of this field failed.
+    Field(Ident),
+}
+
+/// The values exported by a thread of [Program::eval_full_for_export_parallel], together with its
+/// trace output.
+type WorkerOutput = (Result<Vec<Option<Vec<u8>>>, WorkerError>, Vec<u8>);
+
+/// Load the program described by `config` in a new [Program], and evaluate the `fields` of the
+/// result for export, in order, as done by each thread of [Program::eval_full_for_export_parallel].
+/// Return the fields encoded as the content of the main file, or `None` for the ones which can't
+/// be encoded, together with the trace output.
+fn export_fields<EC: EvalCache>(config: &WorkerConfig<'_>, fields: &[Ident]) -> WorkerOutput {
+    let trace = SharedTrace::default();
+
+    let exported =
+        Program::<EC>::new_from_source(Cursor::new(config.source), config.name, trace.clone())
+            .map_err(|err| WorkerError::Load(err.to_string()))
+            .and_then(|mut program| {
+                program.set_limits(config.limits.clone());
+                program.vm.share_steps(Some(config.steps.clone()));
+                program.set_import_policy(config.import_policy.clone());
+
+                if let Some(persistent) = &config.persistent {
+                    program
+                        .vm
+                        .import_resolver_mut()
+                        .set_persistent_cache(persistent.clone());
+                }
+
+                let (main, initial_env) =
+                    program
+                        .prepare_export_shared(config.overrides.clone())
+                        .map_err(|err| WorkerError::Load(program.report_as_str(err)))?;
+
+                fields
+                    .iter()
+                    .map(|id| {
+                        let value = program
+                            .export_field(&main, &initial_env, *id)
+                            .map_err(|_| WorkerError::Field(*id))?;
+
+                        Ok(codec::encode(&value, program.main_id)
+                            .ok()
+                            .filter(|(_, imports)| imports.is_empty())
+                            .map(|(data, _)| data))
+                    })
+                    .collect()
+            });
+
+    let trace = trace.0.take();
+    (exported, trace)
+}
+
 #[cfg(feature = "doc")]
 mod doc {
     use crate::error::{Error, ExportError, IOError};
@@ -775,13 +1394,12 @@ mod doc {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::error::EvalError;
+    use crate::error::ImportError;
     use crate::eval::cache::CacheImpl;
     use crate::identifier::LocIdent;
-    use crate::position::TermPos;
+    use crate::serialize::{self, ExportFormat};
     use crate::term::array::ArrayAttrs;
     use assert_matches::assert_matches;
-    use std::io::Cursor;
 
     fn eval_full(s: &str) -> Result<RichTerm, Error> {
         let src = Cursor::new(s);
@@ -847,6 +1465,418 @@ mod tests {
         eval_full("{y = fun x => x, x = fun y => y}").unwrap();
     }
 
+    impl SharedTrace {
+        fn count(&self, msg: &str) -> usize {
+            String::from_utf8_lossy(&self.0.borrow())
+                .lines()
+                .filter(|line| *line == format!("std.trace: {msg}"))
+                .count()
+        }
+    }
+
+    const INCREMENTAL_SRC: &str = r#"{
+        input = 1,
+        other = 1,
+        uses_input = std.trace "uses_input" (input + 1),
+        unrelated = std.trace "unrelated" (other + 1),
+        hidden | not_exported = input,
+    }"#;
+
+    fn input_override(value: &str) -> FieldOverride {
+        FieldOverride {
+            path: vec![String::from("input")],
+            value: String::from(value),
+            priority: MergePriority::Top,
+        }
+    }
+
+    /// Export [INCREMENTAL_SRC] with an override of `input`, without incremental evaluation.
+    fn export_with_input(value: &str) -> String {
+        let mut p: Program<CacheImpl> =
+            Program::new_from_source(Cursor::new(INCREMENTAL_SRC), "<test>", std::io::sink())
+                .unwrap();
+        p.eval_full_for_export([input_override(value)])
+            .unwrap()
+            .to_string()
+    }
+
+    /// Evaluate a program with two successive values for the field `input`, and return both
+    /// results, together with how many times the fields `uses_input` and `unrelated` have been
+    /// evaluated.
+    fn eval_incremental<EC: EvalCache>(eval_cache: EC) -> (String, String, usize, usize) {
+        let trace = SharedTrace::default();
+        let mut p: Program<EC> =
+            Program::new_from_source(Cursor::new(INCREMENTAL_SRC), "<test>", trace.clone())
+                .unwrap();
+        p.set_eval_cache(eval_cache);
+
+        p.set_override(input_override("2")).unwrap();
+        let first = p.eval_incremental().unwrap().to_string();
+        p.set_override(input_override("5")).unwrap();
+        let second = p.eval_incremental().unwrap().to_string();
+
+        (
+            first,
+            second,
+            trace.count("uses_input"),
+            trace.count("unrelated"),
+        )
+    }
+
+    #[test]
+    fn incremental_overrides() {
+        use crate::eval::cache::{incremental::IncCache, SelectedCache};
+
+        let expected = (export_with_input("2"), export_with_input("5"));
+
+        let (first, second, uses_input, unrelated) = eval_incremental(IncCache::new());
+        assert_eq!((first, second), expected);
+        assert_eq!((uses_input, unrelated), (2, 1));
+
+        // Without dependency tracking, the overrides are merged again, and the fields depending
+        // on other fields are evaluated again.
+        let (first, second, uses_input, unrelated) =
+            eval_incremental(SelectedCache::with_incremental(false));
+        assert_eq!((first, second), expected);
+        assert_eq!((uses_input, unrelated), (2, 2));
+    }
+
+    const PARALLEL_SRC: &str = r#"
+        let shared = std.trace "shared" 1 in
+        {
+          a = shared + 1,
+          b = { c = [shared, "x"], d = std.trace "d" null },
+          e | default = 5,
+          f | optional,
+          g = "%{std.string.from_number a}",
+          hidden | not_exported = 1 + "a",
+        }
+    "#;
+
+    /// Export `src` with an override of `e` on `threads` threads, and return the result serialized
+    /// to JSON together with the trace output.
+    fn export_parallel(src: &str, threads: usize) -> (Result<String, Error>, SharedTrace) {
+        let trace = SharedTrace::default();
+        let mut p: Program<CacheImpl> =
+            Program::new_from_source(Cursor::new(src), "<test>", trace.clone()).unwrap();
+        let overrides = vec![FieldOverride {
+            path: vec![String::from("e")],
+            value: String::from("7"),
+            priority: MergePriority::Top,
+        }];
+
+        let result = p
+            .eval_full_for_export_parallel(overrides, NonZeroUsize::new(threads).unwrap())
+            .and_then(|rt| serialize::to_string(ExportFormat::Json, &rt).map_err(Error::from));
+
+        (result, trace)
+    }
+
+    /// Evaluate `src`, and return how many times `std.trace` printed `msg`.
+    fn trace_count(src: &str, msg: &str) -> usize {
+        let trace = SharedTrace::default();
+        let mut p: Program<CacheImpl> =
+            Program::new_from_source(Cursor::new(src), "<test>", trace.clone()).unwrap();
+        p.eval_full().unwrap();
+        trace.count(msg)
+    }
+
+    #[test]
+    fn contract_deduplication() {
+        let contracts = r#"
+            let C = std.contract.from_predicate (fun x => std.trace "c" true) in
+            let D = std.contract.from_predicate (fun x => std.trace "d" true) in
+            let f | Array C -> Array C = fun x => x in
+            let GreaterThan = fun n =>
+              std.contract.from_predicate (fun x => std.trace "g%{std.string.from_number n}" (x > n))
+            in
+        "#;
+        let count = |body: &str, msg: &str| trace_count(&format!("{contracts} {body}"), msg);
+
+        let once = count("({ a | C = 1 } & {}).a", "c");
+        assert!(once > 0);
+        assert_eq!(
+            count("({ a | C = 1 } & { a | C } & { a | C }).a", "c"),
+            once
+        );
+
+        let once = count("std.array.at 0 (f [1])", "c");
+        assert!(once > 0);
+        assert_eq!(count("std.array.at 0 (f (f (f [1])))", "c"), once);
+
+        // Different contracts are all applied.
+        assert!(count("({ a | C = 1 } & { a | D }).a", "d") > 0);
+        let different_args = "({ a | GreaterThan 1 = 5 } & { a | GreaterThan 2 }).a";
+        assert!(count(different_args, "g1") > 0);
+        assert!(count(different_args, "g2") > 0);
+    }
+
+    #[test]
+    fn parallel_export() {
+        let (sequential, _) = export_parallel(PARALLEL_SRC, 1);
+        let (parallel, trace) = export_parallel(PARALLEL_SRC, 3);
+
+        assert_eq!(parallel.unwrap(), sequential.unwrap());
+        // The trace output of the threads is gathered, and each field is evaluated only once.
+        assert_eq!(trace.count("d"), 1);
+
+        assert_matches!(
+            export_parallel("{ a = 1, b = 1 + \"a\", c = 2 }", 2).0,
+            Err(Error::EvalError(_))
+        );
+        // The error of the first failing field in alphabetical order is reported, whatever the
+        // thread evaluating it.
+        assert_matches!(
+            export_parallel("{ z = 1 + \"a\", a = std.fail_with \"a\", c = 2 }", 2).0,
+            Err(Error::EvalError(EvalError::BlameError { .. }))
+        );
+    }
+
+    #[test]
+    fn parallel_export_shares_steps() {
+        use crate::eval::limits::Limit;
+
+        let work = "std.array.fold_left (+) 0 (std.array.range 0 1000)";
+        let new_program = |src: String| -> Program<CacheImpl> {
+            Program::new_from_source(Cursor::new(src), "<test>", std::io::sink()).unwrap()
+        };
+
+        let mut single = new_program(format!("{{ a = {work} }}"));
+        single.eval_full_for_export(Vec::new()).unwrap();
+        let max_steps = 2 * single.vm.steps();
+
+        // Each field fits in the limit, but not the four of them together.
+        let mut p = new_program(format!(
+            "{{ a = {work}, b = {work}, c = {work}, d = {work} }}"
+        ));
+        p.set_limits(Limits {
+            max_steps: Some(max_steps),
+            ..Limits::default()
+        });
+
+        assert_matches!(
+            p.eval_full_for_export_parallel(Vec::new(), NonZeroUsize::new(4).unwrap()),
+            Err(Error::EvalError(EvalError::ResourceLimitExceeded {
+                limit: Limit::Steps(_),
+                ..
+            }))
+        );
+    }
+
+    #[test]
+    fn reload_modified_import() {
+        use std::{
+            fs,
+            time::{Duration, SystemTime},
+        };
+
+        let dir = tempfile::tempdir().unwrap();
+        let main = dir.path().join("main.ncl");
+        let dep = dir.path().join("dep.ncl");
+        fs::write(&main, "(import \"dep.ncl\") + 1").unwrap();
+        fs::write(&dep, "1").unwrap();
+
+        let mut p: Program<CacheImpl> = Program::new_from_file(&main, std::io::sink()).unwrap();
+        assert_eq!(p.eval_full().unwrap().to_string(), "2");
+        assert_eq!(
+            p.source_files(),
+            vec![
+                normalize_path(&dep).unwrap(),
+                normalize_path(&main).unwrap()
+            ]
+        );
+        assert!(!p.reload().unwrap());
+
+        fs::write(&dep, "41").unwrap();
+        // Make sure the timestamp changes even on filesystems with a coarse resolution.
+        fs::File::options()
+            .write(true)
+            .open(&dep)
+            .unwrap()
+            .set_modified(SystemTime::now() + Duration::from_secs(60))
+            .unwrap();
+
+        assert!(p.reload().unwrap());
+        assert_eq!(p.eval_full().unwrap().to_string(), "42");
+        assert!(!p.reload().unwrap());
+    }
+
+    #[test]
+    fn reload_created_import() {
+        use std::fs;
+
+        let dir = tempfile::tempdir().unwrap();
+        let main = dir.path().join("main.ncl");
+        let dep = dir.path().join("dep.ncl");
+        fs::write(&main, "(import \"dep.ncl\") + 1").unwrap();
+
+        let mut p: Program<CacheImpl> = Program::new_from_file(&main, std::io::sink()).unwrap();
+        assert_matches!(
+            p.eval_full(),
+            Err(Error::ImportError(ImportError::IOError(..)))
+        );
+        assert_eq!(p.source_files(), vec![normalize_path(&main).unwrap()]);
+        assert_eq!(
+            p.watched_files(),
+            vec![
+                normalize_path(&dep).unwrap(),
+                normalize_path(&main).unwrap()
+            ]
+        );
+        assert!(!p.reload().unwrap());
+
+        fs::write(&dep, "41").unwrap();
+
+        assert!(p.reload().unwrap());
+        assert_eq!(p.eval_full().unwrap().to_string(), "42");
+        assert!(!p.reload().unwrap());
+    }
+
+    #[test]
+    fn incremental_reload() {
+        use crate::eval::cache::SelectedCache;
+        use std::{
+            fs,
+            time::{Duration, SystemTime},
+        };
+
+        let dir = tempfile::tempdir().unwrap();
+        let main = dir.path().join("main.ncl");
+        let unchanged = dir.path().join("unchanged.ncl");
+        let modified = dir.path().join("modified.ncl");
+        fs::write(
+            &main,
+            "(import \"unchanged.ncl\") + (import \"modified.ncl\")",
+        )
+        .unwrap();
+        fs::write(&unchanged, "std.trace \"unchanged\" 1").unwrap();
+        fs::write(&modified, "std.trace \"modified\" 2").unwrap();
+
+        let trace = SharedTrace::default();
+        let mut p: Program<CacheImpl> = Program::new_from_file(&main, trace.clone()).unwrap();
+        p.set_eval_cache(SelectedCache::with_incremental(true));
+        assert_eq!(p.eval_incremental().unwrap().to_string(), "3");
+
+        fs::write(&modified, "std.trace \"modified\" 3").unwrap();
+        // Make sure the timestamp changes even on filesystems with a coarse resolution.
+        fs::File::options()
+            .write(true)
+            .open(&modified)
+            .unwrap()
+            .set_modified(SystemTime::now() + Duration::from_secs(60))
+            .unwrap();
+
+        assert!(p.reload().unwrap());
+        assert_eq!(p.eval_incremental().unwrap().to_string(), "4");
+        // The file which didn't change isn't evaluated again.
+        assert_eq!((trace.count("unchanged"), trace.count("modified")), (1, 2));
+    }
+
+    #[test]
+    fn import_policy() {
+        use std::fs;
+
+        let dir = tempfile::tempdir().unwrap();
+        let allowed = dir.path().join("allowed");
+        let outside = dir.path().join("outside");
+        fs::create_dir_all(allowed.join("sub")).unwrap();
+        fs::create_dir(&outside).unwrap();
+        fs::write(allowed.join("sub/dep.ncl"), "1").unwrap();
+        fs::write(outside.join("secret.ncl"), "2").unwrap();
+
+        let eval_in = |main: &str, policy: ImportPolicy| {
+            let path = allowed.join("main.ncl");
+            fs::write(&path, main).unwrap();
+            let mut p: Program<CacheImpl> = Program::new_from_file(&path, std::io::sink()).unwrap();
+            p.set_import_policy(policy);
+            p.eval_full().map(|t| t.to_string())
+        };
+        let roots = ImportPolicy::Roots(vec![allowed.clone()]);
+
+        assert_eq!(
+            eval_in("import \"sub/dep.ncl\"", roots.clone()).unwrap(),
+            "1"
+        );
+        assert_matches!(
+            eval_in("import \"sub/dep.ncl\"", ImportPolicy::Deny),
+            Err(Error::ImportError(ImportError::Forbidden(..)))
+        );
+        assert_matches!(
+            eval_in("import \"../outside/secret.ncl\"", roots.clone()),
+            Err(Error::ImportError(ImportError::Forbidden(..)))
+        );
+        assert_matches!(
+            eval_in("import \"sub/../sub/dep.ncl\"", roots.clone()),
+            Err(Error::ImportError(ImportError::Forbidden(..)))
+        );
+        assert_matches!(
+            eval_in(
+                &format!("import {:?}", outside.join("secret.ncl")),
+                roots.clone()
+            ),
+            Err(Error::ImportError(ImportError::Forbidden(..)))
+        );
+
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(&outside, allowed.join("link")).unwrap();
+            assert_matches!(
+                eval_in("import \"link/secret.ncl\"", roots),
+                Err(Error::ImportError(ImportError::Forbidden(..)))
+            );
+        }
+    }
+
+    #[test]
+    fn virtual_file_system() {
+        use crate::vfs::MemoryFileSystem;
+
+        let fs: MemoryFileSystem = [
+            ("lib/main.ncl", "{ value = (import \"dep.ncl\").x }"),
+            ("lib/dep.ncl", "{ x = 1 }"),
+        ]
+        .into_iter()
+        .collect();
+
+        let mut p: Program<CacheImpl> = Program::new_from_source(
+            Cursor::new("(import \"lib/main.ncl\").value + 1"),
+            "main.ncl",
+            std::io::sink(),
+        )
+        .unwrap();
+        p.set_file_system(fs.clone());
+        assert_eq!(p.eval_full().unwrap().to_string(), "2");
+
+        let mut p: Program<CacheImpl> = Program::new_from_source(
+            Cursor::new("import \"missing.ncl\""),
+            "main.ncl",
+            std::io::sink(),
+        )
+        .unwrap();
+        p.set_file_system(fs.clone());
+        assert_matches!(
+            p.eval_full(),
+            Err(Error::ImportError(ImportError::IOError(..)))
+        );
+
+        let mut p: Program<CacheImpl> = Program::new_from_source(
+            Cursor::new("(import \"lib/main.ncl\").value"),
+            "main.ncl",
+            std::io::sink(),
+        )
+        .unwrap();
+        p.set_file_system(fs.clone());
+        p.set_import_policy(ImportPolicy::Roots(vec![PathBuf::from("/lib")]));
+        assert_eq!(p.eval_full().unwrap().to_string(), "1");
+
+        // The main file is read from the filesystem of the program too.
+        let mut fs = fs;
+        fs.insert("lib/sum.ncl", "(import \"main.ncl\").value + 1");
+        let mut p: Program<CacheImpl> =
+            Program::new_from_file_system("lib/sum.ncl", fs, std::io::sink()).unwrap();
+        assert_eq!(p.eval_full().unwrap().to_string(), "2");
+    }
+
     #[test]
     // Regression test for issue 715 (https://github.com/tweag/nickel/issues/715)
     // Check that program::typecheck() fail on parse error
@@ -6,16 +6,18 @@
 //! Dually, the frontend is the user-facing part, which may be a CLI, a web application, a
 //! jupyter-kernel (which is not exactly user-facing, but still manages input/output and
 //! formatting), etc.
-use crate::cache::{Cache, Envs, ErrorTolerance, SourcePath};
-use crate::error::{Error, EvalError, IOError, ParseError, ParseErrors, ReplError};
+use crate::cache::{Cache, Envs, ErrorTolerance, ImportPolicy, SourcePath};
+use crate::error::{Error, EvalError, ParseError, ParseErrors, ReplError};
 use crate::eval::cache::Cache as EvalCache;
-use crate::eval::{Closure, VirtualMachine};
+use crate::eval::{limits::Limits, Closure, VirtualMachine};
 use crate::identifier::LocIdent;
 use crate::parser::{grammar, lexer, ErrorTolerantParser, ExtendedTerm};
+use crate::position::TermPos;
 use crate::program::QueryPath;
 use crate::term::{record::Field, RichTerm, Term, Traverse};
 use crate::transform::import_resolution;
 use crate::typ::Type;
+use crate::vfs::FileSystem;
 use crate::{eval, transform, typecheck};
 use codespan::FileId;
 use simple_counter::*;
@@ -95,6 +97,36 @@ impl<EC: EvalCache> ReplImpl<EC> {
         }
     }
 
+    /// Create a new empty REPL using the given evaluation cache.
+    pub fn new_with_cache(eval_cache: EC, trace: impl Write + 'static) -> Self {
+        ReplImpl {
+            parser: grammar::ExtendedTermParser::new(),
+            env: Envs::new(),
+            initial_type_ctxt: typecheck::Context::new(),
+            vm: VirtualMachine::new_with_cache(
+                Cache::new(ErrorTolerance::Strict),
+                eval_cache,
+                trace,
+            ),
+        }
+    }
+
+    /// Limit the resources used by the evaluation of each input. See [crate::eval::limits].
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.vm.set_limits(limits);
+    }
+
+    /// Read the files loaded or imported by the inputs from `file_system` instead of the
+    /// filesystem of the operating system. See [crate::vfs].
+    pub fn set_file_system(&mut self, file_system: impl FileSystem + 'static) {
+        self.vm.import_resolver_mut().set_file_system(file_system);
+    }
+
+    /// Restrict the files which can be imported by the inputs. See [ImportPolicy].
+    pub fn set_import_policy(&mut self, policy: ImportPolicy) {
+        self.vm.import_resolver_mut().set_import_policy(policy);
+    }
+
     /// Load and process the stdlib, and use it to populate the eval environment as well as the
     /// typing environment.
     pub fn load_stdlib(&mut self) -> Result<(), Error> {
@@ -198,6 +230,54 @@ impl<EC: EvalCache> ReplImpl<EC> {
     }
 }
 
+impl<EC: EvalCache> ReplImpl<EC> {
+    /// Replace the value of a toplevel binding, introduced either by a toplevel let or by loading
+    /// a file, with the expression `exp`.
+    ///
+    /// If the evaluation cache tracks dependencies (see [crate::eval::cache::incremental]), the
+    /// bindings depending on `name` see the new value, and only what depends on it is evaluated
+    /// again. Note that `name` then refers to the binding itself inside `exp`. Otherwise, `false`
+    /// is returned, and `name` is bound anew, as for a toplevel let: the other bindings keep using
+    /// the previous value.
+    pub fn override_binding(&mut self, name: &str, exp: &str) -> Result<bool, Error> {
+        let id = LocIdent::from(name);
+        let Some(idx) = self.env.eval_env.get(&id.ident()).cloned() else {
+            return Err(EvalError::UnboundIdentifier(id, TermPos::None).into());
+        };
+
+        self.vm.reset();
+
+        let file_id = self.vm.import_resolver_mut().add_string(
+            SourcePath::ReplInput(InputNameCounter::next()),
+            String::from(exp),
+        );
+        let (t, parse_errs) = self.vm.import_resolver().parse_nocache(file_id)?;
+
+        if !parse_errs.no_errors() {
+            return Err(parse_errs.into());
+        }
+
+        let t = self.prepare(Some(id), t)?;
+        let closure = Closure {
+            body: t,
+            env: self.env.eval_env.clone(),
+        };
+
+        if self.vm.cache.replace(&idx, closure.clone()) {
+            Ok(true)
+        } else {
+            eval::env_add(
+                &mut self.vm.cache,
+                &mut self.env.eval_env,
+                id,
+                closure.body,
+                closure.env,
+            );
+            Ok(false)
+        }
+    }
+}
+
 impl<EC: EvalCache> Repl for ReplImpl<EC> {
     fn eval(&mut self, exp: &str) -> Result<EvalResult, Error> {
         self.eval_(exp, false)
@@ -211,8 +291,7 @@ impl<EC: EvalCache> Repl for ReplImpl<EC> {
         let file_id = self
             .vm
             .import_resolver_mut()
-            .add_file(OsString::from(path.as_ref()))
-            .map_err(IOError::from)?;
+            .add_file_checked(OsString::from(path.as_ref()), &TermPos::None)?;
         self.vm.import_resolver_mut().parse(file_id)?;
 
         let term = self.vm.import_resolver().get_owned(file_id).unwrap();
@@ -220,6 +299,7 @@ impl<EC: EvalCache> Repl for ReplImpl<EC> {
 
         let term = self.prepare(None, term)?;
 
+        self.vm.reset();
         let (term, new_env) = self
             .vm
             .eval_closure(Closure::atomic_closure(term), &self.env.eval_env)?;
@@ -494,3 +574,60 @@ pub fn print_help(out: &mut impl Write, arg: Option<&str>) -> std::io::Result<()
         writeln!(out, "Available commands: help query load typecheck exit")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::cache::{incremental::IncCache, lazy::CBNCache};
+
+    fn eval_to_string<EC: EvalCache>(repl: &mut ReplImpl<EC>, exp: &str) -> String {
+        match repl.eval_full(exp) {
+            Ok(EvalResult::Evaluated(t)) => t.to_string(),
+            _ => panic!("expected {exp} to evaluate to a term"),
+        }
+    }
+
+    fn override_binding<EC: EvalCache>(mut repl: ReplImpl<EC>) -> (bool, String) {
+        repl.load_stdlib().unwrap();
+        repl.eval("let x = 1").unwrap();
+        repl.eval("let y = x + 1").unwrap();
+        assert_eq!(eval_to_string(&mut repl, "y"), "2");
+
+        let updated = repl.override_binding("x", "10").unwrap();
+        assert_eq!(eval_to_string(&mut repl, "x"), "10");
+        (updated, eval_to_string(&mut repl, "y"))
+    }
+
+    #[test]
+    fn override_binding_incremental() {
+        let repl = ReplImpl::new_with_cache(IncCache::new(), std::io::sink());
+        assert_eq!(override_binding(repl), (true, String::from("11")));
+    }
+
+    #[test]
+    fn override_binding_lazy() {
+        let repl = ReplImpl::new_with_cache(CBNCache::new(), std::io::sink());
+        assert_eq!(override_binding(repl), (false, String::from("2")));
+    }
+
+    #[test]
+    fn load_import_policy() {
+        use crate::error::ImportError;
+        use assert_matches::assert_matches;
+
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("lib.ncl");
+        std::fs::write(&file, "{ a = 1 }").unwrap();
+
+        let mut repl = ReplImpl::new_with_cache(CBNCache::new(), std::io::sink());
+        repl.set_import_policy(ImportPolicy::Deny);
+        assert_matches!(
+            repl.load(&file),
+            Err(Error::ImportError(ImportError::Forbidden(..)))
+        );
+
+        repl.set_import_policy(ImportPolicy::Roots(vec![dir.path().to_owned()]));
+        repl.load(&file).unwrap();
+        assert_eq!(eval_to_string(&mut repl, "a"), "1");
+    }
+}
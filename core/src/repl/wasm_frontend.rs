@@ -1,10 +1,12 @@
 //! Web assembly interface to the REPL.
 use super::simple_frontend::{input, serialize, InputError, InputResult};
 use super::{Repl, ReplImpl};
-use crate::cache::Cache;
+use crate::cache::{Cache, ImportPolicy};
 use crate::error::IntoDiagnostics;
 use crate::eval::cache::CacheImpl;
+use crate::eval::limits::Limits;
 use crate::serialize::ExportFormat;
+use crate::vfs::FileSystem;
 use codespan::{FileId, Files};
 use codespan_reporting::{
     diagnostic::{Diagnostic, Label, LabelStyle, Severity},
@@ -13,7 +15,9 @@ use codespan_reporting::{
 use serde::Serialize;
 use serde_repr::Serialize_repr;
 use std::convert::TryInto;
-use std::io::Cursor;
+use std::io::{self, Cursor};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 use wasm_bindgen::prelude::*;
 
 /// Return codes of the WASM REPL.
@@ -349,6 +353,95 @@ pub fn repl_init(trace_callback: Option<js_sys::Function>) -> WasmInitResult {
     }
 }
 
+/// Limit the resources used by the evaluation of each input of the WASM REPL. `None` disables
+/// the corresponding limit. See [crate::eval::limits::Limits]: the timeout isn't available in
+/// WebAssembly.
+#[wasm_bindgen]
+pub fn repl_set_limits(
+    state: &mut ReplState,
+    max_steps: Option<u32>,
+    max_stack_depth: Option<u32>,
+    max_size: Option<u32>,
+) {
+    state.0.set_limits(Limits {
+        max_steps: max_steps.map(u64::from),
+        max_stack_depth: max_stack_depth.map(|depth| depth as usize),
+        max_size: max_size.map(|size| size as usize),
+        timeout: None,
+    });
+}
+
+/// Forbid imports in the WASM REPL.
+#[wasm_bindgen]
+pub fn repl_deny_imports(state: &mut ReplState) {
+    state.0.set_import_policy(ImportPolicy::Deny);
+}
+
+/// Only allow the WASM REPL to import files located under one of the directories `roots`, given
+/// as an array of paths. Entries which aren't strings are ignored. See [ImportPolicy::Roots].
+#[wasm_bindgen]
+pub fn repl_set_import_roots(state: &mut ReplState, roots: Box<[JsValue]>) {
+    let roots = roots
+        .iter()
+        .filter_map(JsValue::as_string)
+        .map(PathBuf::from)
+        .collect();
+    state.0.set_import_policy(ImportPolicy::Roots(roots));
+}
+
+/// A filesystem whose files are provided by a JavaScript function, which takes a path and returns
+/// the content of the file as a string, or `null` or `undefined` if there is no such file.
+///
+/// The modification times of files are unknown: once a file has been loaded, it isn't read
+/// again. The current directory is the root `/`, and there are no symbolic links.
+#[derive(Debug)]
+pub struct JsFileSystem {
+    read: js_sys::Function,
+}
+
+impl FileSystem for JsFileSystem {
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        let path = path.to_string_lossy();
+        let content = self
+            .read
+            .call1(&JsValue::NULL, &JsValue::from_str(&path))
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, format!("{e:?}")))?;
+
+        if content.is_null() || content.is_undefined() {
+            Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no such file: {path}"),
+            ))
+        } else {
+            content.as_string().ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("the content of {path} isn't a string"),
+                )
+            })
+        }
+    }
+
+    fn modified(&self, _path: &Path) -> io::Result<SystemTime> {
+        Ok(SystemTime::UNIX_EPOCH)
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        Ok(path.to_owned())
+    }
+
+    fn current_dir(&self) -> io::Result<PathBuf> {
+        Ok(PathBuf::from("/"))
+    }
+}
+
+/// Read the files imported by the inputs of the WASM REPL with the JavaScript function `read`.
+/// See [JsFileSystem].
+#[wasm_bindgen]
+pub fn repl_set_file_system(state: &mut ReplState, read: js_sys::Function) {
+    state.0.set_file_system(JsFileSystem { read });
+}
+
 /// Evaluate an input in the WASM REPL.
 #[wasm_bindgen]
 pub fn repl_input(state: &mut ReplState, line: &str) -> WasmInputResult {
@@ -0,0 +1,98 @@
+//! Semantic versioning support for the `std.semver` module.
+//!
+//! Versions follow the [Semantic Versioning 2.0.0](https://semver.org) specification. Version
+//! requirements use the syntax of Cargo, e.g. `^1.2`, `>=1.0, <2.0`, `~1.2.3` or `*`.
+use std::cmp::Ordering;
+
+use ::semver::{BuildMetadata, Version, VersionReq};
+
+/// The components of a parsed version, as exposed by `std.semver.parse`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Components {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+    /// The pre-release identifiers, without the leading `-`, or the empty string.
+    pub pre: String,
+    /// The build metadata, without the leading `+`, or the empty string.
+    pub build: String,
+}
+
+/// Parse a semantic version.
+pub fn parse(s: &str) -> Result<Version, String> {
+    Version::parse(s).map_err(|err| err.to_string())
+}
+
+/// Parse a version requirement.
+pub fn parse_req(s: &str) -> Result<VersionReq, String> {
+    VersionReq::parse(s).map_err(|err| err.to_string())
+}
+
+/// Parse a semantic version and return its components.
+pub fn components(s: &str) -> Result<Components, String> {
+    let version = parse(s)?;
+
+    Ok(Components {
+        major: version.major,
+        minor: version.minor,
+        patch: version.patch,
+        pre: version.pre.to_string(),
+        build: version.build.to_string(),
+    })
+}
+
+/// Compare two versions by precedence. As mandated by the specification, build metadata is
+/// ignored, so the order isn't total on strings: `1.0.0+a` and `1.0.0+b` are equal.
+pub fn compare(s1: &str, s2: &str) -> Result<Ordering, String> {
+    let mut v1 = parse(s1)?;
+    let mut v2 = parse(s2)?;
+    v1.build = BuildMetadata::EMPTY;
+    v2.build = BuildMetadata::EMPTY;
+
+    Ok(v1.cmp(&v2))
+}
+
+/// Test if a version satisfies a requirement.
+pub fn matches(req: &str, version: &str) -> Result<bool, String> {
+    Ok(parse_req(req)?.matches(&parse(version)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_components() {
+        assert_eq!(
+            components("1.2.3-beta.1+build.5").unwrap(),
+            Components {
+                major: 1,
+                minor: 2,
+                patch: 3,
+                pre: "beta.1".into(),
+                build: "build.5".into(),
+            }
+        );
+
+        assert!(parse("1.2").is_err());
+        assert!(parse("01.2.3").is_err());
+        assert!(parse("v1.2.3").is_err());
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(compare("1.2.3", "1.10.0"), Ok(Ordering::Less));
+        assert_eq!(compare("1.0.0-alpha", "1.0.0"), Ok(Ordering::Less));
+        assert_eq!(compare("1.0.0+a", "1.0.0+b"), Ok(Ordering::Equal));
+        assert_eq!(compare("2.0.0", "1.99.99"), Ok(Ordering::Greater));
+    }
+
+    #[test]
+    fn requirements() {
+        assert_eq!(matches("^1.2", "1.9.0"), Ok(true));
+        assert_eq!(matches("^1.2", "2.0.0"), Ok(false));
+        assert_eq!(matches(">=1.0, <1.5", "1.4.9"), Ok(true));
+        assert_eq!(matches("~1.2.3", "1.3.0"), Ok(false));
+        assert!(matches(">=>1", "1.0.0").is_err());
+    }
+}
@@ -19,7 +19,7 @@ use serde::{
 
 use malachite::num::conversion::traits::IsInteger;
 
-use std::{fmt, io, rc::Rc};
+use std::{fmt, io};
 
 /// Available export formats.
 // If you add or remove variants, remember to update the CLI docs in `src/bin/nickel.rs'
@@ -160,7 +160,7 @@ pub fn deserialize_array<'de, D>(deserializer: D) -> Result<(Array, ArrayAttrs),
 where
     D: Deserializer<'de>,
 {
-    let terms = Array::new(Rc::from(Vec::deserialize(deserializer)?));
+    let terms = Array::from_iter(Vec::<RichTerm>::deserialize(deserializer)?);
     Ok((terms, Default::default()))
 }
 
@@ -270,6 +270,15 @@ pub fn to_string(format: ExportFormat, rt: &RichTerm) -> Result<String, ExportEr
     Ok(String::from_utf8_lossy(&buffer).into_owned())
 }
 
+/// Serialize a term to compact JSON. Record fields are always serialized in alphabetical order,
+/// so two equal values have the same serialization, whatever the order in which their fields
+/// were defined. This is used to compute content hashes of values.
+///
+/// The term must be fully evaluated and have been validated for [ExportFormat::Json].
+pub fn to_canonical_json(rt: &RichTerm) -> Result<String, ExportError> {
+    serde_json::to_string(rt).map_err(|err| ExportError::Other(err.to_string()))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1,5 +1,4 @@
-use std::mem::transmute;
-use std::mem::ManuallyDrop;
+use imbl::{vector, Vector};
 
 use super::*;
 
@@ -11,7 +10,8 @@ pub struct ArrayAttrs {
     ///   - The environment of the array's closure only contains those generated variables.
     pub closurized: bool,
     /// List of lazily-applied contracts.
-    /// These are only observed when data enters or leaves the array.
+    /// These are only observed when data enters or leaves the array. Use
+    /// [crate::eval::contract_eq::extend_pending] to add contracts without duplicates.
     pub pending_contracts: Vec<RuntimeContract>,
 }
 
@@ -34,127 +34,134 @@ impl ArrayAttrs {
         self.pending_contracts.clear();
         self
     }
-
-    /// Extend contracts from an iterator of `PendingContract`.
-    /// De-duplicate equal contracts. Note that current contract
-    /// equality testing is very limited, but this may change in the
-    /// future
-    pub fn with_extra_contracts<I>(mut self, iter: I) -> Self
-    where
-        I: IntoIterator<Item = RuntimeContract>,
-    {
-        for ctr in iter {
-            if !self.pending_contracts.contains(&ctr) {
-                self.pending_contracts.push(ctr)
-            }
-        }
-
-        self
-    }
 }
 
-/// A Nickel array, represented as a view (slice) into a shared backing array. The view is
-/// delimited by `start` (included) and `end` (excluded). This allows to take the tail of an array,
-/// or an arbitrary slice, in constant time, providing actual linear time iteration when
-/// imlementing recursive functions, such as folds, for example.
+/// A Nickel array, represented as a persistent vector (a relaxed radix balanced tree).
+///
+/// Taking a slice of an array, pushing an element at either end or concatenating two arrays are
+/// done in logarithmic time, while sharing most of the underlying storage with the original
+/// array(s). This makes recursive functions such as folds, or arrays built incrementally by
+/// repeated concatenations, run in (quasi) linear time. Small arrays are stored inline, without
+/// any tree structure.
 #[derive(Debug, Clone, PartialEq)]
 pub struct Array {
-    inner: Rc<[RichTerm]>,
-    start: usize,
-    end: usize,
+    inner: Vector<RichTerm>,
 }
 
 pub struct OutOfBoundError;
 
-impl Array {
-    /// Creates a Nickel array from reference-counted slice.
-    pub fn new(inner: Rc<[RichTerm]>) -> Self {
-        let start = 0;
-        let end = inner.len();
+/// An iterator moving the elements out of an array, cloning them when the storage is shared.
+pub type IntoIter = vector::ConsumingIter<RichTerm>;
 
-        Self { inner, start, end }
+impl Array {
+    /// Creates a Nickel array from a persistent vector.
+    pub fn new(inner: Vector<RichTerm>) -> Self {
+        Self { inner }
     }
 
-    /// Resize the view to be a a sub-view of the current one, by considering a slice `start`
+    /// Resize the array to be a sub-array of the current one, by considering a slice `start`
     /// (included) to `end` (excluded).
     ///
-    /// The parameters must satisfy `0 <= start <= end <= self.end - self.start`. Otherwise,
-    /// `Err(..)` is returned.
+    /// The parameters must satisfy `0 <= start <= end <= self.len()`. Otherwise, `Err(..)` is
+    /// returned.
     pub fn slice(&mut self, start: usize, end: usize) -> Result<(), OutOfBoundError> {
         if start > end || end > self.len() {
             return Err(OutOfBoundError);
         }
 
-        let prev_start = self.start;
-        self.start = prev_start + start;
-        self.end = prev_start + end;
+        self.inner = self.inner.skip(start).take(end - start);
 
         Ok(())
     }
 
-    /// Returns the effective length of the array.
+    /// Returns the length of the array.
     pub fn len(&self) -> usize {
-        self.end - self.start
+        self.inner.len()
     }
 
     /// Returns `true` if the array is empty.
     pub fn is_empty(&self) -> bool {
-        self.end == self.start
+        self.inner.is_empty()
     }
 
     /// Returns a reference to the term at the given index.
     pub fn get(&self, idx: usize) -> Option<&RichTerm> {
-        self.inner.get(self.start + idx)
+        self.inner.get(idx)
+    }
+
+    /// Splits the array into consecutive sub-arrays of `size` elements, sharing the underlying
+    /// storage. The last sub-array may be shorter if `size` doesn't divide the length of the
+    /// array.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `size` is `0`.
+    pub fn chunks(&self, size: usize) -> impl Iterator<Item = Array> + '_ {
+        assert!(size != 0, "chunk size must be non-zero");
+
+        (0..self.len()).step_by(size).map(move |start| Array {
+            inner: self.inner.skip(start).take(size),
+        })
+    }
+
+    /// Returns all the contiguous sub-arrays of `size` elements of the array, sharing the
+    /// underlying storage. If the array is shorter than `size`, no sub-array is returned.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `size` is `0`.
+    pub fn windows(&self, size: usize) -> impl Iterator<Item = Array> + '_ {
+        assert!(size != 0, "window size must be non-zero");
+
+        let count = (self.len() + 1).saturating_sub(size);
+
+        (0..count).map(move |start| Array {
+            inner: self.inner.skip(start).take(size),
+        })
     }
 
     /// Discards the first `diff` terms of the array.
     pub fn advance_by(mut self, diff: usize) -> Self {
-        self.start += usize::min(diff, self.len());
+        self.inner = self.inner.skip(usize::min(diff, self.len()));
         self
     }
 
-    /// Makes a mutable slice into the given `Array`.
-    pub fn make_mut(&mut self) -> &mut [RichTerm] {
-        // NOTE: trying to use `Rc::make_mut` will result in the following compiler error:
-        // > the trait `Clone` is not implemented for `[term::RichTerm]`
-        // This seems to be an edge-case in the standard library.
-        // As a workaround, if `get_mut` fails we recollect the array into a new `Rc` by cloning all terms.
-        // Thus we make sure that the second call to `get_mut` won't fail.
+    /// Adds a term at the end of the array.
+    pub fn push_back(&mut self, term: RichTerm) {
+        self.inner.push_back(term)
+    }
 
-        // This condition is the same as `!Rc::is_unique(&mut self.inner)`, but that function
-        // is not public.
-        if Rc::strong_count(&self.inner) != 1 || Rc::weak_count(&self.inner) != 0 {
-            self.inner = self.iter().cloned().collect::<Rc<[_]>>();
-        }
+    /// Adds a term at the beginning of the array.
+    pub fn push_front(&mut self, term: RichTerm) {
+        self.inner.push_front(term)
+    }
+
+    /// Appends the terms of `other` at the end of the array.
+    pub fn append(&mut self, other: Array) {
+        self.inner.append(other.inner)
+    }
 
-        Rc::get_mut(&mut self.inner).expect("non-unique Rc after deep-cloning Array")
+    /// Returns an iterator of mutable references over the array. The parts of the storage which
+    /// are shared with other arrays are copied first.
+    pub fn iter_mut(&mut self) -> vector::IterMut<'_, RichTerm> {
+        self.inner.iter_mut()
     }
 
     /// Returns an iterator of references over the array.
-    pub fn iter(&self) -> std::slice::Iter<'_, RichTerm> {
-        self.as_ref().iter()
+    pub fn iter(&self) -> vector::Iter<'_, RichTerm> {
+        self.inner.iter()
     }
 }
 
 impl Default for Array {
     fn default() -> Self {
-        Self::new(Rc::new([]))
+        Self::new(Vector::new())
     }
 }
 
 impl FromIterator<RichTerm> for Array {
     fn from_iter<T: IntoIterator<Item = RichTerm>>(iter: T) -> Self {
-        let inner = iter.into_iter().collect::<Rc<[_]>>();
-        let start = 0;
-        let end = inner.len();
-
-        Self { inner, start, end }
-    }
-}
-
-impl AsRef<[RichTerm]> for Array {
-    fn as_ref(&self) -> &[RichTerm] {
-        &self.inner[self.start..self.end]
+        Self::new(iter.into_iter().collect())
     }
 }
 
@@ -164,62 +171,6 @@ impl IntoIterator for Array {
     type IntoIter = IntoIter;
 
     fn into_iter(self) -> Self::IntoIter {
-        // SAFETY: If there are no other strong or weak references
-        // to the inner slice, then we can:
-        // - Drop the elements outside our inner view,
-        // - Move out the elements inside out inner view.
-        // Otherwise, we clone everything.
-
-        unsafe {
-            let mut inner: Rc<[ManuallyDrop<RichTerm>]> = transmute(self.inner);
-            let idx = self.start;
-            let end = self.end;
-
-            if let Some(slice) = Rc::get_mut(&mut inner) {
-                for term in &mut slice[..idx] {
-                    ManuallyDrop::drop(term)
-                }
-                for term in &mut slice[end..] {
-                    ManuallyDrop::drop(term)
-                }
-            }
-
-            IntoIter { inner, idx, end }
-        }
-    }
-}
-
-pub struct IntoIter {
-    inner: Rc<[ManuallyDrop<RichTerm>]>,
-    idx: usize,
-    end: usize,
-}
-
-impl Iterator for IntoIter {
-    type Item = RichTerm;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.idx == self.end {
-            None
-        } else {
-            let term = match Rc::get_mut(&mut self.inner) {
-                None => self.inner[..self.end]
-                    .get(self.idx)
-                    .cloned()
-                    .map(ManuallyDrop::into_inner),
-                Some(slice) => slice[..self.end]
-                    .get_mut(self.idx)
-                    .map(|t| unsafe { ManuallyDrop::take(t) }),
-            };
-
-            self.idx += 1;
-            term
-        }
-    }
-}
-
-impl ExactSizeIterator for IntoIter {
-    fn len(&self) -> usize {
-        self.end - self.idx
+        self.inner.into_iter()
     }
 }
@@ -1002,12 +1002,45 @@ pub enum UnaryOp {
     StrUppercase(),
     /// Transform a string to lowercase.
     StrLowercase(),
+    /// Encode the UTF-8 bytes of a string using the given encoding.
+    StrEncode(Encoding),
+    /// Decode a string using the given encoding. Fail if the input isn't valid for this encoding,
+    /// or if the decoded bytes aren't valid UTF-8.
+    StrDecode(Encoding),
+    /// Check that a string is valid for the given format. Return a record with a boolean field
+    /// `valid`, and a field `message` explaining what part of the string is invalid, which is
+    /// empty if the string is valid.
+    StrCheckFormat(StringFormat),
+    /// Parse a semantic version and return a record of its components. See [crate::semver].
+    SemVerParse(),
+    /// Parse an absolute URL and return a record of its components. See [crate::net].
+    UrlParse(),
     /// Return the length of a string.
     StrLength(),
     /// Transform a data to a string.
     ToStr(),
     /// Transform a string to a number.
     NumFromStr(),
+    /// Round a number down to the nearest integer.
+    NumFloor(),
+    /// Round a number up to the nearest integer.
+    NumCeil(),
+    /// Round a number to the nearest integer, rounding half away from zero.
+    NumRound(),
+    /// Compute the square root of a number. The result is exact if the number is the square of a
+    /// rational, and goes through a 64 bits float otherwise.
+    NumSqrt(),
+    /// Parse an RFC 3339 datetime and return a record of its components, including the Unix
+    /// timestamp and the offset from UTC. See [crate::datetime].
+    DateTimeParse(),
+    /// Test if a string is a valid RFC 3339 datetime.
+    DateTimeIsValid(),
+    /// Parse an ISO 8601 duration and return its length in seconds.
+    DurationParse(),
+    /// Test if a string is a valid ISO 8601 duration, as supported by [UnaryOp::DurationParse].
+    DurationIsValid(),
+    /// Render a number of seconds as an ISO 8601 duration.
+    DurationFormat(),
     /// Transform a string to an enum.
     EnumFromStr(),
     /// Test if a regex matches a string.
@@ -1018,10 +1051,19 @@ pub enum UnaryOp {
     /// Match a regex on a string, and returns the captured groups together, the index of the
     /// match, etc.
     StrFind(),
+    /// Like [`UnaryOp::StrFind`], but returns an array of all the non-overlapping matches.
+    StrFindAll(),
+    /// Match a regex on a string, and return a record of the named capture groups of the first
+    /// match.
+    StrCaptures(),
     /// Version of [`UnaryOp::StrIsMatch`] which remembers the compiled regex.
     StrIsMatchCompiled(CompiledRegex),
     /// Version of [`UnaryOp::StrFind`] which remembers the compiled regex.
     StrFindCompiled(CompiledRegex),
+    /// Version of [`UnaryOp::StrFindAll`] which remembers the compiled regex.
+    StrFindAllCompiled(CompiledRegex),
+    /// Version of [`UnaryOp::StrCaptures`] which remembers the compiled regex.
+    StrCapturesCompiled(CompiledRegex),
     /// Force full evaluation of a term and return it.
     ///
     /// This was added in the context of [`BinaryOp::ArrayLazyAssume`],
@@ -1143,14 +1185,32 @@ impl fmt::Display for UnaryOp {
             StrChars() => write!(f, "str_chars"),
             StrUppercase() => write!(f, "str_uppercase"),
             StrLowercase() => write!(f, "str_lowercase"),
+            StrEncode(encoding) => write!(f, "{encoding}_encode"),
+            StrDecode(encoding) => write!(f, "{encoding}_decode"),
+            StrCheckFormat(format) => write!(f, "{format}_check"),
+            SemVerParse() => write!(f, "semver_parse"),
+            UrlParse() => write!(f, "url_parse"),
             StrLength() => write!(f, "str_length"),
             ToStr() => write!(f, "to_str"),
             NumFromStr() => write!(f, "num_from_str"),
+            NumFloor() => write!(f, "floor"),
+            NumCeil() => write!(f, "ceil"),
+            NumRound() => write!(f, "round"),
+            NumSqrt() => write!(f, "sqrt"),
+            DateTimeParse() => write!(f, "datetime_parse"),
+            DateTimeIsValid() => write!(f, "datetime_is_valid"),
+            DurationParse() => write!(f, "duration_parse"),
+            DurationIsValid() => write!(f, "duration_is_valid"),
+            DurationFormat() => write!(f, "duration_format"),
             EnumFromStr() => write!(f, "enum_from_str"),
             StrIsMatch() => write!(f, "str_is_match"),
             StrFind() => write!(f, "str_find"),
+            StrFindAll() => write!(f, "str_find_all"),
+            StrCaptures() => write!(f, "str_captures"),
             StrIsMatchCompiled(_) => write!(f, "str_is_match_compiled"),
             StrFindCompiled(_) => write!(f, "str_find_compiled"),
+            StrFindAllCompiled(_) => write!(f, "str_find_all_compiled"),
+            StrCapturesCompiled(_) => write!(f, "str_captures_compiled"),
             Force { .. } => write!(f, "force"),
             RecDefault() => write!(f, "rec_default"),
             RecForce() => write!(f, "rec_force"),
@@ -1208,6 +1268,63 @@ impl UnaryOp {
     }
 }
 
+/// The binary-to-text encodings supported by [UnaryOp::StrEncode] and [UnaryOp::StrDecode].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Encoding {
+    /// Base64 with the standard alphabet and padding (RFC 4648, section 4).
+    Base64,
+    /// Base64 with the URL and filename safe alphabet and padding (RFC 4648, section 5).
+    Base64Url,
+    /// Lowercase hexadecimal.
+    Hex,
+    /// Percent-encoding of all the characters but the unreserved ones (RFC 3986, section 2).
+    Percent,
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Encoding::Base64 => write!(f, "base64"),
+            Encoding::Base64Url => write!(f, "base64_url"),
+            Encoding::Hex => write!(f, "hex"),
+            Encoding::Percent => write!(f, "percent"),
+        }
+    }
+}
+
+/// The string formats which can be validated by [UnaryOp::StrCheckFormat].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StringFormat {
+    /// A semantic version, as per the Semantic Versioning 2.0.0 specification.
+    SemVer,
+    /// A version requirement, using the syntax of Cargo.
+    SemVerReq,
+    /// An IPv4 address in dotted decimal notation.
+    Ipv4,
+    /// An IPv6 address.
+    Ipv6,
+    /// An IPv4 or IPv6 CIDR block.
+    Cidr,
+    /// A hostname, as per RFC 1123.
+    Hostname,
+    /// An absolute URL.
+    Url,
+}
+
+impl fmt::Display for StringFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StringFormat::SemVer => write!(f, "semver"),
+            StringFormat::SemVerReq => write!(f, "semver_req"),
+            StringFormat::Ipv4 => write!(f, "ipv4"),
+            StringFormat::Ipv6 => write!(f, "ipv6"),
+            StringFormat::Cidr => write!(f, "cidr"),
+            StringFormat::Hostname => write!(f, "hostname"),
+            StringFormat::Url => write!(f, "url"),
+        }
+    }
+}
+
 /// The kind of a dynamic record extension. Kind indicates if a definition is expected for the
 /// field being inserted, or if the inserted field doesn't have a definition.
 #[derive(Clone, Debug, PartialEq, Eq, Copy)]
@@ -1231,6 +1348,32 @@ pub enum BinaryOp {
     Modulo(),
     /// Raise a number to a power.
     Pow(),
+    /// Logarithm of a number (first argument) in a given base (second argument).
+    NumLog(),
+    /// Integer division, rounding the quotient towards zero.
+    IntDiv(),
+    /// Remainder of the integer division [Self::IntDiv], which has the sign of the dividend.
+    IntRem(),
+    /// Greatest common divisor of two integers.
+    Gcd(),
+    /// Convert a number to a string with a fixed number of decimal places, given by the first
+    /// argument. The conversion is exact up to the final rounding, which rounds half away from
+    /// zero.
+    NumToStrFixed(),
+    /// Render a Unix timestamp, given as the second argument, as an RFC 3339 datetime with the
+    /// offset from UTC in seconds given as the first argument.
+    DateTimeFromTimestamp(),
+    /// Format an RFC 3339 datetime, given as the second argument, according to the
+    /// `strftime`-like format string given as the first argument.
+    DateTimeFormat(),
+    /// Compare two semantic versions by precedence.
+    SemVerCompare(),
+    /// Test if a semantic version, given as the second argument, satisfies the version
+    /// requirement given as the first argument.
+    SemVerMatches(),
+    /// Test if an IP address, given as the second argument, belongs to the CIDR block given as the
+    /// first argument.
+    CidrContains(),
     /// Concatenation of strings.
     StrConcat(),
     /// Polymorphic equality.
@@ -1251,6 +1394,21 @@ pub enum BinaryOp {
     /// operation with its argument. Finally, this operator marks the location of the contract
     /// argument for better error reporting.
     Assume(),
+    /// Try to apply a contract to a label and a value, and report a failure as a value instead of
+    /// aborting the evaluation.
+    ///
+    /// The arguments are the same as for [`BinaryOp::Assume`]. The application of the contract is
+    /// performed in a nested evaluation, up to weak head normal form. The result is a record with
+    /// the fields `success`, `value`, `message` and `notes`, so that it has the same type in both
+    /// cases. If the contract succeeds, `success` is `true`, `value` is the result and the other
+    /// fields are empty. If it fails with a blame error, `success` is `false`, `value` is `null`,
+    /// and `message` and `notes` are taken from the diagnostic of the blamed label. Any other
+    /// error is propagated as is.
+    ///
+    /// Only the immediate part of the contract is checked this way: the delayed checks (e.g.
+    /// the contracts of record fields or of array elements) are still part of the returned value,
+    /// and will blame as usual when forced.
+    ContractTry(),
     /// Unseal a sealed term.
     ///
     /// See [`BinaryOp::Seal`].
@@ -1286,6 +1444,11 @@ pub enum BinaryOp {
     ArrayConcat(),
     /// Access the n-th element of an array.
     ArrayElemAt(),
+    /// Split an array into consecutive, non-overlapping sub-arrays of a given size. The last
+    /// chunk may be shorter.
+    ArrayChunks(),
+    /// Return all the contiguous sub-arrays of a given size, in order.
+    ArrayWindows(),
     /// The merge operator (see [crate::eval::merge]). `Merge` is parametrized by a
     /// [crate::label::MergeLabel], which carries additional information for error-reporting
     /// purpose.
@@ -1293,6 +1456,9 @@ pub enum BinaryOp {
 
     /// Hash a string.
     Hash(),
+    /// Hash the canonical JSON serialization of a value (see [crate::serialize::to_canonical_json]).
+    /// The value must have been fully evaluated.
+    HashValue(),
     /// Serialize a value to a string.
     Serialize(),
     /// Deserialize a string to a value.
@@ -1302,6 +1468,8 @@ pub enum BinaryOp {
     StrSplit(),
     /// Determine if a string is a substring of another one.
     StrContains(),
+    /// Compare two strings lexicographically, by Unicode code points.
+    StrCompare(),
     /// Seal a term with a sealing key (see [`Term::Sealed`]).
     Seal(),
 
@@ -1346,6 +1514,16 @@ impl fmt::Display for BinaryOp {
             Div() => write!(f, "div"),
             Modulo() => write!(f, "modulo"),
             Pow() => write!(f, "pow"),
+            NumToStrFixed() => write!(f, "num_to_str_fixed"),
+            DateTimeFromTimestamp() => write!(f, "datetime_from_timestamp"),
+            DateTimeFormat() => write!(f, "datetime_format"),
+            SemVerCompare() => write!(f, "semver_compare"),
+            SemVerMatches() => write!(f, "semver_matches"),
+            CidrContains() => write!(f, "cidr_contains"),
+            NumLog() => write!(f, "log"),
+            IntDiv() => write!(f, "int_div"),
+            IntRem() => write!(f, "int_rem"),
+            Gcd() => write!(f, "gcd"),
             StrConcat() => write!(f, "str_concat"),
             Eq() => write!(f, "eq"),
             LessThan() => write!(f, "less_than"),
@@ -1353,6 +1531,7 @@ impl fmt::Display for BinaryOp {
             GreaterThan() => write!(f, "greater_than"),
             GreaterOrEq() => write!(f, "greater_or_eq"),
             Assume() => write!(f, "assume"),
+            ContractTry() => write!(f, "contract_try"),
             Unseal() => write!(f, "unseal"),
             GoField() => write!(f, "go_field"),
             DynExtend { .. } => write!(f, "record_insert"),
@@ -1361,12 +1540,16 @@ impl fmt::Display for BinaryOp {
             HasField() => write!(f, "has_field"),
             ArrayConcat() => write!(f, "array_concat"),
             ArrayElemAt() => write!(f, "elem_at"),
+            ArrayChunks() => write!(f, "array_chunks"),
+            ArrayWindows() => write!(f, "array_windows"),
             Merge(_) => write!(f, "merge"),
             Hash() => write!(f, "hash"),
+            HashValue() => write!(f, "hash_value"),
             Serialize() => write!(f, "serialize"),
             Deserialize() => write!(f, "deserialize"),
             StrSplit() => write!(f, "str_split"),
             StrContains() => write!(f, "str_contains"),
+            StrCompare() => write!(f, "str_compare"),
             Seal() => write!(f, "seal"),
             ArrayLazyAssume() => write!(f, "array_lazy_assume"),
             RecordLazyAssume() => write!(f, "record_lazy_assume"),
@@ -1420,6 +1603,28 @@ pub enum NAryOp {
     /// hood, as long as the array isn't modified later, this operation is constant in time and
     /// memory.
     ArraySlice(),
+    /// Attach the location of a field of a record as a related location to the current diagnostic
+    /// of a label (see [crate::label::ContractDiagnostic::related]).
+    ///
+    /// Takes three arguments:
+    ///   - the name of the field, as a string,
+    ///   - the [record](Term::Record) containing the field,
+    ///   - the [label](Term::Lbl) on which to operate.
+    LabelWithRelatedField(),
+    /// Attach a record-level invariant to a record (see [crate::term::record::RecordData::invariants]).
+    ///
+    /// Takes three arguments:
+    ///   - the contract to check against the whole record,
+    ///   - the [label](Term::Lbl) of the contract,
+    ///   - the [record](Term::Record) on which to operate.
+    RecordAddInvariant(),
+    /// Compute the HMAC of a string.
+    ///
+    /// Takes three arguments:
+    ///   - the underlying hash function, as an enum tag,
+    ///   - the key, as a string,
+    ///   - the message, as a string.
+    Hmac(),
 }
 
 impl NAryOp {
@@ -1431,7 +1636,10 @@ impl NAryOp {
             | NAryOp::MergeContract()
             | NAryOp::RecordUnsealTail()
             | NAryOp::InsertTypeVar()
-            | NAryOp::ArraySlice() => 3,
+            | NAryOp::ArraySlice()
+            | NAryOp::LabelWithRelatedField()
+            | NAryOp::RecordAddInvariant()
+            | NAryOp::Hmac() => 3,
             NAryOp::RecordSealTail() => 4,
         }
     }
@@ -1449,6 +1657,9 @@ impl fmt::Display for NAryOp {
             RecordUnsealTail() => write!(f, "record_unseal_tail"),
             InsertTypeVar() => write!(f, "insert_type_variable"),
             ArraySlice() => write!(f, "array_slice"),
+            LabelWithRelatedField() => write!(f, "label_with_related_field"),
+            RecordAddInvariant() => write!(f, "record_add_invariant"),
+            Hmac() => write!(f, "hmac"),
         }
     }
 }
@@ -1713,10 +1924,10 @@ impl Traverse<RichTerm> for RichTerm {
                 )
             },
             Term::Array(ts, attrs) => {
-                let ts_res = Array::new(ts
+                let ts_res = ts
                     .into_iter()
                     .map(|t| t.traverse(f, state, order))
-                    .collect::<Result<Rc<[_]>, _>>()?);
+                    .collect::<Result<Array, _>>()?;
 
                 RichTerm::new(
                     Term::Array(ts_res, attrs),
@@ -2013,13 +2224,13 @@ pub mod make {
     macro_rules! mk_array {
         ( $( $terms:expr ),* ; $attrs:expr ) => {
             {
-                let ts = $crate::term::array::Array::new(std::rc::Rc::new([$( $crate::term::RichTerm::from($terms) ),*]));
+                let ts: $crate::term::array::Array = vec![$( $crate::term::RichTerm::from($terms) ),*].into_iter().collect();
                 $crate::term::RichTerm::from($crate::term::Term::Array(ts, $attrs))
             }
         };
         ( $( $terms:expr ),* ) => {
             {
-                let ts = $crate::term::array::Array::new(std::rc::Rc::new([$( $crate::term::RichTerm::from($terms) ),*]));
+                let ts: $crate::term::array::Array = vec![$( $crate::term::RichTerm::from($terms) ),*].into_iter().collect();
                 $crate::term::RichTerm::from(Term::Array(ts, ArrayAttrs::default()))
             }
         };
@@ -216,6 +216,11 @@ pub struct RecordData {
     pub attrs: RecordAttrs,
     /// The hidden part of a record under a polymorphic contract.
     pub sealed_tail: Option<SealedTail>,
+    /// Record-level invariants, which are contracts checked against the whole record once it is
+    /// fully evaluated (see [crate::term::UnaryOp::Force]), instead of when the record is
+    /// evaluated to a weak head normal form. Invariants are accumulated by merging, such that
+    /// they are checked against the final, merged record.
+    pub invariants: Vec<RuntimeContract>,
 }
 
 /// Error raised by [RecordData] methods when trying to access a field that doesn't have a
@@ -247,6 +252,7 @@ impl RecordData {
             fields,
             attrs,
             sealed_tail,
+            invariants: Vec::new(),
         }
     }
 
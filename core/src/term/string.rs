@@ -1,16 +1,73 @@
-use std::ops::{Deref, DerefMut};
-
+use std::{
+    cell::RefCell,
+    fmt,
+    ops::{Deref, DerefMut},
+    rc::Rc,
+};
+
+use imbl::Vector;
 use malachite::Rational;
-use serde::{Deserialize, Serialize};
+use once_cell::unsync::OnceCell;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use unicode_segmentation::UnicodeSegmentation;
 
 use super::{array::Array, CompiledRegex, Number, Term};
 
-/// A Nickel string is really just a Rust `String`, overlayed with some
-/// methods implementing custom logic (in particular, functions which
-/// avoid ever breaking up Unicode extended grapheme clusters.)
-#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
-pub struct NickelString(String);
+/// Strings shorter than this many bytes are always stored contiguously.
+const SMALL_STR_LEN: usize = 1024;
+
+/// When concatenating ropes, the last chunk of the left one and the first chunk of the right one
+/// are merged if the result is shorter than this many bytes. This avoids creating lots of tiny
+/// chunks when appending small strings one by one, while bounding the size of the copy.
+const MERGE_CHUNK_LEN: usize = 1024;
+
+/// A Nickel string is a Rust `String`, overlayed with some methods implementing custom logic (in
+/// particular, functions which avoid ever breaking up Unicode extended grapheme clusters.)
+///
+/// Concatenating large strings, which happens when building a big string piece by piece with
+/// `++` or with interpolation, doesn't copy them. The result is instead represented as a rope,
+/// that is a sequence of chunks shared with the original strings. A rope is flattened into an
+/// actual `String` the first time its content is accessed, which only happens for operations
+/// looking at the characters of the string (including serialization). The length in bytes is
+/// known without flattening.
+#[derive(Clone)]
+pub struct NickelString(Repr);
+
+#[derive(Clone)]
+enum Repr {
+    Flat(String),
+    Rope(Rc<Rope>),
+}
+
+struct Rope {
+    /// The chunks of the string, in order. No chunk is empty. The chunks are dropped once the
+    /// rope has been flattened, so that the string isn't kept twice in memory.
+    chunks: RefCell<Vector<Rc<str>>>,
+    /// The length of the string in bytes.
+    len: usize,
+    /// The concatenation of the chunks, computed on first access.
+    flat: OnceCell<String>,
+}
+
+impl Rope {
+    fn flatten(&self) -> &String {
+        self.flat.get_or_init(|| {
+            let chunks = std::mem::take(&mut *self.chunks.borrow_mut());
+            let mut flat = String::with_capacity(self.len);
+            chunks.iter().for_each(|chunk| flat.push_str(chunk));
+            flat
+        })
+    }
+
+    /// Returns the chunks of the rope, or a copy of the flattened string as a single chunk if the
+    /// rope has already been flattened.
+    fn chunks(&self) -> Vector<Rc<str>> {
+        match self.flat.get() {
+            Some(flat) => Vector::unit(Rc::from(flat.as_str())),
+            None => self.chunks.borrow().clone(),
+        }
+    }
+}
 
 // Conversions to & from String-like things.
 
@@ -19,7 +76,7 @@ where
     String: From<S>,
 {
     fn from(inner: S) -> Self {
-        Self(inner.into())
+        Self(Repr::Flat(inner.into()))
     }
 }
 
@@ -36,25 +93,67 @@ impl Deref for NickelString {
     type Target = String;
 
     fn deref(&self) -> &String {
-        &self.0
+        match &self.0 {
+            Repr::Flat(s) => s,
+            Repr::Rope(rope) => rope.flatten(),
+        }
     }
 }
 
 impl DerefMut for NickelString {
     fn deref_mut(&mut self) -> &mut String {
-        &mut self.0
+        if let Repr::Rope(rope) = &mut self.0 {
+            let flat = match Rc::get_mut(rope) {
+                Some(rope) => {
+                    rope.flatten();
+                    rope.flat.take().unwrap()
+                }
+                None => rope.flatten().clone(),
+            };
+
+            self.0 = Repr::Flat(flat);
+        }
+
+        match &mut self.0 {
+            Repr::Flat(s) => s,
+            Repr::Rope(_) => unreachable!("the string has just been flattened"),
+        }
     }
 }
 
-impl std::fmt::Display for NickelString {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.0)
+impl PartialEq for NickelString {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && **self == **other
+    }
+}
+
+impl fmt::Debug for NickelString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("NickelString").field(&**self).finish()
+    }
+}
+
+impl fmt::Display for NickelString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", &**self)
     }
 }
 
 impl AsRef<str> for NickelString {
     fn as_ref(&self) -> &str {
-        self.0.as_str()
+        self.as_str()
+    }
+}
+
+impl Serialize for NickelString {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_newtype_struct("NickelString", &**self)
+    }
+}
+
+impl<'de> Deserialize<'de> for NickelString {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(NickelString::from)
     }
 }
 
@@ -65,6 +164,90 @@ impl NickelString {
         String::new().into()
     }
 
+    /// The length of the string in bytes. Unlike most other methods, this doesn't require to
+    /// flatten a rope.
+    pub fn len(&self) -> usize {
+        match &self.0 {
+            Repr::Flat(s) => s.len(),
+            Repr::Rope(rope) => rope.len,
+        }
+    }
+
+    /// Returns `true` if the string is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the concatenation of `self` and `other`. Small strings are copied, but large
+    /// strings are shared with the result.
+    ///
+    /// This method has `O(log(self.len() + other.len()))` time complexity.
+    pub fn concat(&self, other: &NickelString) -> NickelString {
+        let len = self.len() + other.len();
+
+        if len < SMALL_STR_LEN {
+            let mut flat = String::with_capacity(len);
+            flat.push_str(self);
+            flat.push_str(other);
+            return flat.into();
+        }
+
+        let mut chunks = self.chunks();
+        let mut right = other.chunks();
+
+        let merged = match (chunks.back(), right.front()) {
+            (Some(last), Some(first)) if last.len() + first.len() < MERGE_CHUNK_LEN => {
+                Some(Rc::<str>::from([&**last, &**first].concat()))
+            }
+            _ => None,
+        };
+
+        if let Some(merged) = merged {
+            chunks.pop_back();
+            right.pop_front();
+            chunks.push_back(merged);
+        }
+
+        chunks.append(right);
+
+        NickelString(Repr::Rope(Rc::new(Rope {
+            chunks: RefCell::new(chunks),
+            len,
+            flat: OnceCell::new(),
+        })))
+    }
+
+    /// Appends `other` at the end of `self`, sharing it if it's large. See [Self::concat].
+    pub fn append(&mut self, other: &NickelString) {
+        if let Repr::Flat(s) = &mut self.0 {
+            if s.len() + other.len() < SMALL_STR_LEN {
+                s.push_str(other);
+                return;
+            }
+        }
+
+        *self = self.concat(other);
+    }
+
+    /// Appends `other` at the end of `self`. Flat strings are extended in place, while ropes get
+    /// a new chunk.
+    pub fn push_str(&mut self, other: &str) {
+        if let Repr::Flat(s) = &mut self.0 {
+            s.push_str(other);
+        } else {
+            *self = self.concat(&NickelString::from(other));
+        }
+    }
+
+    /// Returns the chunks of the string, copying it first if it's flat.
+    fn chunks(&self) -> Vector<Rc<str>> {
+        match &self.0 {
+            Repr::Flat(s) if s.is_empty() => Vector::new(),
+            Repr::Flat(s) => Vector::unit(Rc::from(s.as_str())),
+            Repr::Rope(rope) => rope.chunks(),
+        }
+    }
+
     /// The number of Unicode extended grapheme clusters the string contains.
     ///
     /// This method has `O(self.len())` time complexity.
@@ -182,6 +365,25 @@ impl NickelString {
         result.into()
     }
 
+    /// Returns the length in bytes of the result of [Self::replace] with the same arguments,
+    /// without building it.
+    ///
+    /// This method has time complexity `O(self.len() * from.len())`.
+    pub fn replaced_len(&self, from: &str, to: &str) -> usize {
+        if from.is_empty() {
+            let insertions = self.grapheme_cluster_count() + 1;
+            self.len()
+                .saturating_add(insertions.saturating_mul(to.len()))
+        } else {
+            use grapheme_cluster_preservation::SearchEvent;
+
+            let matches = grapheme_cluster_preservation::search(self, from)
+                .filter(|e| matches!(e, SearchEvent::Match { .. }))
+                .count();
+            (self.len() - matches * from.len()).saturating_add(matches.saturating_mul(to.len()))
+        }
+    }
+
     /// Returns the substring of `self` between `start` and `end`.
     ///
     /// Returns an error if:
@@ -270,6 +472,18 @@ impl NickelString {
         result.into()
     }
 
+    /// Returns the length in bytes of the result of [Self::replace_regex] with the same
+    /// arguments, without building it.
+    ///
+    /// The time complexity of this method is `O(self.len())`.
+    pub fn regex_replaced_len(&self, regex: &CompiledRegex, replacement: &NickelString) -> usize {
+        use grapheme_cluster_preservation::regex;
+
+        regex::find_iter(self, regex).fold(self.len(), |len, m| {
+            (len - (m.end() - m.start())).saturating_add(replacement.len())
+        })
+    }
+
     /// Find the first match in `self` for a given `regex`, and return the
     /// match itself, the index in `self` where it appears, and any capture
     /// groups specified.
@@ -280,46 +494,89 @@ impl NickelString {
     ///
     /// The time complexity of this method is `O(self.len())`.
     pub fn find_regex(&self, regex: &CompiledRegex) -> RegexFindResult {
+        match self.find_all_regex(regex).next() {
+            Some(RegexMatch {
+                mtch,
+                index,
+                groups,
+            }) => RegexFindResult::Match {
+                mtch,
+                index,
+                groups,
+            },
+            None => RegexFindResult::NoMatch,
+        }
+    }
+
+    /// Return an iterator over all the successive non-overlapping matches of
+    /// `regex` in `self`, with the same information as [Self::find_regex].
+    ///
+    /// Matches which begin or end in the middle of a Unicode extended grapheme
+    /// cluster are ignored, as for [Self::find_regex].
+    ///
+    /// Consuming the whole iterator takes `O(self.len())`.
+    pub fn find_all_regex<'a>(
+        &'a self,
+        regex: &'a CompiledRegex,
+    ) -> impl Iterator<Item = RegexMatch> + 'a {
         use grapheme_cluster_preservation::regex;
 
-        if let Some(capt) = regex::captures(self, regex) {
-            // If we found a capture group, we extract the whole match...
-            let first_match = capt.get(0).unwrap();
-            // and then convert each group into a `NickelString`
-            let groups = capt
-                .iter()
-                .skip(1)
-                .filter_map(|s_opt| s_opt.map(|s| s.as_str().into()))
-                .collect();
-
-            // The indices returned by the `regex` crate are byte offsets into
-            // the string, but we need to return the index into the Nickel string,
-            // i.e., the grapheme cluster index which starts at this byte offset.
-            let adjusted_index = self
-                .grapheme_indices(true)
-                .enumerate()
-                .find_map(|(grapheme_idx, (byte_offset, _))| {
-                    if byte_offset == first_match.start() {
-                        Some(grapheme_idx.into())
-                    } else {
-                        None
-                    }
-                })
-                .expect("We already know that `first_match.start()` occurs on a cluster boundary.");
+        // The indices returned by the `regex` crate are byte offsets into the
+        // string, but we need to return the index into the Nickel string, i.e.
+        // the number of grapheme clusters before the match. Matches are
+        // returned in order, so we count the clusters in a single pass.
+        let mut boundaries = self
+            .grapheme_indices(true)
+            .map(|(offset, _)| offset)
+            .peekable();
+        let mut index: usize = 0;
+
+        regex::captures_iter(self, regex).map(move |capt| {
+            let whole = capt.get(0).unwrap();
+
+            while boundaries
+                .next_if(|offset| *offset < whole.start())
+                .is_some()
+            {
+                index += 1;
+            }
 
-            RegexFindResult::Match {
-                mtch: first_match.as_str().into(),
-                index: adjusted_index,
-                groups,
+            RegexMatch {
+                mtch: whole.as_str().into(),
+                index: index.into(),
+                groups: capt
+                    .iter()
+                    .skip(1)
+                    .filter_map(|s_opt| s_opt.map(|s| s.as_str().into()))
+                    .collect(),
             }
-        } else {
-            RegexFindResult::NoMatch
-        }
+        })
+    }
+
+    /// Find the first match in `self` for a given `regex`, and return the
+    /// value of each named capture group which participated in the match,
+    /// or `None` if there is no match.
+    ///
+    /// Matches which begin or end in the middle of a Unicode extended
+    /// grapheme cluster are ignored, as for [Self::find_regex].
+    pub fn captures_regex(&self, regex: &CompiledRegex) -> Option<Vec<(String, NickelString)>> {
+        use grapheme_cluster_preservation::regex;
+
+        regex::captures_iter(self, regex).next().map(|capt| {
+            regex
+                .capture_names()
+                .flatten()
+                .filter_map(|name| {
+                    capt.name(name)
+                        .map(|m| (name.to_owned(), m.as_str().into()))
+                })
+                .collect()
+        })
     }
 
     /// Consumes `self`, returning the Rust `String`.
-    pub fn into_inner(self) -> String {
-        self.0
+    pub fn into_inner(mut self) -> String {
+        std::mem::take(self.deref_mut())
     }
 }
 
@@ -332,6 +589,13 @@ pub enum RegexFindResult {
     },
 }
 
+/// A match returned by [NickelString::find_all_regex].
+pub struct RegexMatch {
+    pub mtch: NickelString,
+    pub index: Number,
+    pub groups: Vec<NickelString>,
+}
+
 /// Errors returned by `NickelString`'s `substring` method.
 pub enum SubstringError {
     /// The start index was not an int
@@ -535,12 +799,15 @@ mod grapheme_cluster_preservation {
                 .filter(|m| does_match_start_and_end_on_boundary(haystack, m))
         }
 
-        /// Find the left-most match for `needle` in `haystack`, filtering out
-        /// any match where either the match itself, or any of its capture
-        /// groups, begin or end in the middle of a Unicode extended grapheme
-        /// cluster.
-        pub fn captures<'a>(haystack: &'a str, needle: &'a Regex) -> Option<regex::Captures<'a>> {
-            needle.captures_iter(haystack).find(|c| {
+        /// An iterator over the successive captures of `needle` in
+        /// `haystack`, filtering out any match where either the match itself,
+        /// or any of its capture groups, begin or end in the middle of a
+        /// Unicode extended grapheme cluster.
+        pub fn captures_iter<'a>(
+            haystack: &'a str,
+            needle: &'a Regex,
+        ) -> impl Iterator<Item = regex::Captures<'a>> {
+            needle.captures_iter(haystack).filter(|c| {
                 c.iter().all(|maybe_match| {
                     maybe_match
                         .map(|m| does_match_start_and_end_on_boundary(haystack, &m))
@@ -560,3 +827,63 @@ mod grapheme_cluster_preservation {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rope() {
+        let small = NickelString::from("a").concat(&NickelString::from("b"));
+        assert!(matches!(small.0, Repr::Flat(_)));
+
+        // A grapheme cluster made of an `e` and a combining accent, split across two chunks.
+        let left = format!("{}e", "a".repeat(SMALL_STR_LEN));
+        let right = format!("\u{301}{}", "b".repeat(SMALL_STR_LEN));
+        let rope = NickelString::from(left.as_str()).concat(&NickelString::from(right.as_str()));
+
+        assert!(matches!(rope.0, Repr::Rope(_)));
+        assert_eq!(rope.len(), 2 * SMALL_STR_LEN + 3);
+        assert_eq!(rope, NickelString::from(format!("{left}{right}")));
+        assert_eq!(rope.length(), 2 * SMALL_STR_LEN + 1);
+        assert!(matches!(
+            rope.substring(&SMALL_STR_LEN.into(), &(SMALL_STR_LEN + 1).into()),
+            Ok(sub) if sub.as_str() == "e\u{301}"
+        ));
+
+        let mut extended = rope.clone();
+        extended.push_str("!");
+        extended.append(&rope);
+        assert_eq!(extended.len(), 2 * rope.len() + 1);
+        assert_eq!(
+            extended.into_inner(),
+            format!("{left}{right}!{left}{right}")
+        );
+        assert_eq!(String::from(&rope), format!("{left}{right}"));
+    }
+
+    #[test]
+    fn rope_chunks() {
+        let mut s = NickelString::from("a".repeat(SMALL_STR_LEN));
+        for _ in 0..MERGE_CHUNK_LEN {
+            s.append(&NickelString::from("b"));
+        }
+
+        let Repr::Rope(rope) = &s.0 else {
+            panic!("expected a rope")
+        };
+        // The `b`s are merged into chunks shorter than `MERGE_CHUNK_LEN`: one with all of them but
+        // the last one, and one with the last one.
+        assert_eq!(rope.chunks.borrow().len(), 3);
+
+        assert_eq!(
+            s.as_str(),
+            format!(
+                "{}{}",
+                "a".repeat(SMALL_STR_LEN),
+                "b".repeat(MERGE_CHUNK_LEN)
+            )
+        );
+        assert!(rope.chunks.borrow().is_empty());
+    }
+}
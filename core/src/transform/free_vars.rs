@@ -155,7 +155,7 @@ impl CollectFreeVars for RichTerm {
                 *deps = Some(new_deps);
             }
             Term::Array(ts, _) => {
-                for t in ts.make_mut().iter_mut() {
+                for t in ts.iter_mut() {
                     t.collect_free_vars(free_vars);
                 }
             }
@@ -819,7 +819,7 @@ impl<E: TermEnvironment + Clone> GenericUnifType<E> {
     /// equality involving contracts.
     pub fn from_type(ty: Type, env: &E) -> Self {
         match ty.typ {
-            TypeF::Flat(t) => GenericUnifType::Contract(t, env.clone()),
+            TypeF::Flat(t) => GenericUnifType::from_contract(t, env),
             ty => GenericUnifType::concrete(ty.map(
                 |ty_| Box::new(GenericUnifType::from_type(*ty_, env)),
                 |rrows| GenericUnifRecordRows::from_record_rows(rrows, env),
@@ -827,6 +827,102 @@ impl<E: TermEnvironment + Clone> GenericUnifType<E> {
             )),
         }
     }
+
+    /// Create a [`GenericUnifType`] from a term used as a contract. If the contract is a record
+    /// contract which has an equivalent record type (see [`contract_as_static_type`]), this record
+    /// type is used instead, such that fields of a value annotated with this contract can be
+    /// accessed and checked statically. Otherwise, the contract is an opaque type.
+    pub fn from_contract(t: RichTerm, env: &E) -> Self {
+        match contract_as_static_type(&t, env, eq::MAX_GAS) {
+            Some((ty, def_env)) => GenericUnifType::from_type(ty, &def_env),
+            None => GenericUnifType::Contract(t, env.clone()),
+        }
+    }
+}
+
+/// Try to convert a contract to an equivalent static type, together with the environment in which
+/// this type must be interpreted.
+///
+/// A contract has an equivalent type if it is (possibly through variables) either a type wrapped
+/// as a term, such as `{port : Number}`, or a record literal without dynamic fields whose fields
+/// are all without definition and annotated with exactly one type or contract, such as `{port |
+/// Number, ..}`. In the latter case, the contract is converted to a record type, whose tail is
+/// `Dyn` if the record contract is open. Fields with a value, a default value, or which are
+/// optional can't be represented by a record type, and make the conversion fail.
+///
+/// Variables are followed at most `gas` times, using the term environment `env`.
+fn contract_as_static_type<E: TermEnvironment>(
+    t: &RichTerm,
+    env: &E,
+    gas: u8,
+) -> Option<(Type, E)> {
+    match t.as_ref() {
+        Term::Var(id) if gas > 0 => env.get_then(id.ident(), |binding| {
+            binding.and_then(|(t, env)| contract_as_static_type(t, env, gas - 1))
+        }),
+        Term::Type(ty) => Some((ty.clone(), env.clone())),
+        Term::Record(record) | Term::RecRecord(record, ..) => {
+            if matches!(t.as_ref(), Term::RecRecord(_, dyn_fields, _) if !dyn_fields.is_empty())
+                || record.sealed_tail.is_some()
+            {
+                return None;
+            }
+
+            let tail = if record.attrs.open {
+                RecordRowsF::TailDyn
+            } else {
+                RecordRowsF::Empty
+            };
+
+            let rrows =
+                record
+                    .fields
+                    .iter()
+                    .rev()
+                    .try_fold(RecordRows(tail), |tail, (id, field)| {
+                        let field_ty = field_as_static_type(field)?;
+
+                        Some(RecordRows(RecordRowsF::Extend {
+                            row: RecordRowF {
+                                id: *id,
+                                typ: Box::new(field_ty),
+                            },
+                            tail: Box::new(tail),
+                        }))
+                    })?;
+
+            Some((
+                Type {
+                    typ: TypeF::Record(rrows),
+                    pos: t.pos,
+                },
+                env.clone(),
+            ))
+        }
+        _ => None,
+    }
+}
+
+/// Return the type of a field of a record contract, if the field can be represented as a row of
+/// a record type. See [`contract_as_static_type`].
+fn field_as_static_type(field: &Field) -> Option<Type> {
+    if field.value.is_some() || field.metadata.opt {
+        return None;
+    }
+
+    let ty = match &field.metadata.annotation {
+        TypeAnnotation {
+            typ: Some(labeled_ty),
+            contracts,
+        } if contracts.is_empty() => &labeled_ty.typ,
+        TypeAnnotation {
+            typ: None,
+            contracts,
+        } if contracts.len() == 1 => &contracts[0].typ,
+        _ => return None,
+    };
+
+    (!has_wildcards(ty)).then(|| ty.clone())
 }
 
 type UnifTypeUnrolling = GenericUnifTypeUnrolling<SimpleTermEnvironment>;
@@ -1393,7 +1489,7 @@ fn walk<L: Linearizer>(
             // allocate all the term environments inside an arena, local to each statically typed
             // block, and use bare references to represent cycles. Then everything would be cleaned
             // at the end of the block.
-            ctxt.term_env.0.insert(x.ident(), (re.clone(), ctxt.term_env.clone()));
+            ctxt.term_env.0.insert(x.ident(), term_env_binding(state, re, &ctxt.term_env));
 
             if attrs.rec {
                 ctxt.type_env.insert(x.ident(), ty_let.clone());
@@ -1792,7 +1888,7 @@ fn check_visited<L: Linearizer>(
             // `Let` case in `walk`.
             ctxt.term_env
                 .0
-                .insert(x.ident(), (re.clone(), ctxt.term_env.clone()));
+                .insert(x.ident(), term_env_binding(state, re, &ctxt.term_env));
 
             if attrs.rec {
                 ctxt.type_env.insert(x.ident(), ty_let.clone());
@@ -2383,6 +2479,23 @@ fn binding_type(state: &mut State, t: &Term, ctxt: &Context, strict: bool) -> Un
     )
 }
 
+/// Return the value to bind in the term environment for a let-bound expression `rt`. Resolved
+/// imports are replaced by the imported term, such that contracts defined in another file can be
+/// compared or converted to static types (see [`GenericUnifType::from_contract`]).
+fn term_env_binding(
+    state: &State,
+    rt: &RichTerm,
+    term_env: &SimpleTermEnvironment,
+) -> (RichTerm, SimpleTermEnvironment) {
+    match rt.as_ref() {
+        Term::ResolvedImport(file_id) => match state.resolver.get(*file_id) {
+            Some(imported) => (imported, SimpleTermEnvironment::new()),
+            None => (rt.clone(), term_env.clone()),
+        },
+        _ => (rt.clone(), term_env.clone()),
+    }
+}
+
 /// Same as `binding_type` but for record field definition.
 fn field_type(state: &mut State, field: &Field, ctxt: &Context, strict: bool) -> UnifType {
     apparent_or_infer(
@@ -2438,7 +2551,7 @@ fn replace_wildcards_with_var(
 
     match ty.typ {
         TypeF::Wildcard(i) => get_wildcard_var(table, ctxt.var_level, wildcard_vars, i),
-        TypeF::Flat(t) => UnifType::Contract(t, ctxt.term_env.clone()),
+        TypeF::Flat(t) => UnifType::from_contract(t, &ctxt.term_env),
         _ => UnifType::concrete(ty.typ.map_state(
             |ty, (table, wildcard_vars)| {
                 Box::new(replace_wildcards_with_var(table, ctxt, wildcard_vars, *ty))
@@ -146,12 +146,73 @@ pub fn get_uop_type(
         UnaryOp::StrUppercase() => (mk_uniftype::str(), mk_uniftype::str()),
         // Str -> Str
         UnaryOp::StrLowercase() => (mk_uniftype::str(), mk_uniftype::str()),
+        // Str -> Str
+        UnaryOp::StrEncode(_) | UnaryOp::StrDecode(_) => (mk_uniftype::str(), mk_uniftype::str()),
+        // Str -> { valid: Bool, message: Str }
+        UnaryOp::StrCheckFormat(_) => (
+            mk_uniftype::str(),
+            mk_uty_record!(("valid", TypeF::Bool), ("message", TypeF::String)),
+        ),
+        // Str -> { major: Num, minor: Num, patch: Num, pre: Str, build: Str }
+        UnaryOp::SemVerParse() => (
+            mk_uniftype::str(),
+            mk_uty_record!(
+                ("major", TypeF::Number),
+                ("minor", TypeF::Number),
+                ("patch", TypeF::Number),
+                ("pre", TypeF::String),
+                ("build", TypeF::String)
+            ),
+        ),
+        // Str -> { scheme: Str, username: Str, password: Str, host: Str, port: Num, ... }
+        UnaryOp::UrlParse() => (
+            mk_uniftype::str(),
+            mk_uty_record!(
+                ("scheme", TypeF::String),
+                ("username", TypeF::String),
+                ("password", TypeF::String),
+                ("host", TypeF::String),
+                ("port", TypeF::Number),
+                ("path", TypeF::String),
+                ("query", TypeF::String),
+                ("fragment", TypeF::String)
+            ),
+        ),
         // Str -> Num
         UnaryOp::StrLength() => (mk_uniftype::str(), mk_uniftype::num()),
         // Dyn -> Str
         UnaryOp::ToStr() => (mk_uniftype::dynamic(), mk_uniftype::str()),
         // Str -> Num
         UnaryOp::NumFromStr() => (mk_uniftype::str(), mk_uniftype::num()),
+        // Num -> Num
+        UnaryOp::NumFloor() | UnaryOp::NumCeil() | UnaryOp::NumRound() | UnaryOp::NumSqrt() => {
+            (mk_uniftype::num(), mk_uniftype::num())
+        }
+        // Str -> { timestamp: Num, offset: Num, year: Num, ... }
+        UnaryOp::DateTimeParse() => (
+            mk_uniftype::str(),
+            mk_uty_record!(
+                ("timestamp", TypeF::Number),
+                ("offset", TypeF::Number),
+                ("year", TypeF::Number),
+                ("month", TypeF::Number),
+                ("day", TypeF::Number),
+                ("hour", TypeF::Number),
+                ("minute", TypeF::Number),
+                ("second", TypeF::Number),
+                ("nanosecond", TypeF::Number),
+                ("weekday", TypeF::Number),
+                ("day_of_year", TypeF::Number)
+            ),
+        ),
+        // Str -> Bool
+        UnaryOp::DateTimeIsValid() | UnaryOp::DurationIsValid() => {
+            (mk_uniftype::str(), mk_uniftype::bool())
+        }
+        // Str -> Num
+        UnaryOp::DurationParse() => (mk_uniftype::str(), mk_uniftype::num()),
+        // Num -> Str
+        UnaryOp::DurationFormat() => (mk_uniftype::num(), mk_uniftype::str()),
         // Str -> < | a> for a rigid type variable a
         UnaryOp::EnumFromStr() => (
             mk_uniftype::str(),
@@ -174,6 +235,23 @@ pub fn get_uop_type(
                 )
             ),
         ),
+        // Str -> Str -> Array {matched: Str, index: Num, groups: Array Str}
+        UnaryOp::StrFindAll() => (
+            mk_uniftype::str(),
+            mk_uty_arrow!(
+                mk_uniftype::str(),
+                mk_uniftype::array(mk_uty_record!(
+                    ("matched", TypeF::String),
+                    ("index", TypeF::Number),
+                    ("groups", mk_uniftype::array(TypeF::String))
+                ))
+            ),
+        ),
+        // Str -> Str -> {_: Str}
+        UnaryOp::StrCaptures() => (
+            mk_uniftype::str(),
+            mk_uty_arrow!(mk_uniftype::str(), mk_uniftype::dict(TypeF::String)),
+        ),
         // Str -> Bool
         UnaryOp::StrIsMatchCompiled(_) => (mk_uniftype::str(), mk_uniftype::bool()),
         // Str -> {matched: Str, index: Num, groups: Array Str}
@@ -185,8 +263,22 @@ pub fn get_uop_type(
                 ("groups", mk_uniftype::array(TypeF::String))
             ),
         ),
-        // Dyn -> Dyn
-        UnaryOp::Force { .. } => (mk_uniftype::dynamic(), mk_uniftype::dynamic()),
+        // Str -> Array {matched: Str, index: Num, groups: Array Str}
+        UnaryOp::StrFindAllCompiled(_) => (
+            mk_uniftype::str(),
+            mk_uniftype::array(mk_uty_record!(
+                ("matched", TypeF::String),
+                ("index", TypeF::Number),
+                ("groups", mk_uniftype::array(TypeF::String))
+            )),
+        ),
+        // Str -> {_: Str}
+        UnaryOp::StrCapturesCompiled(_) => (mk_uniftype::str(), mk_uniftype::dict(TypeF::String)),
+        // forall a. a -> a
+        UnaryOp::Force { .. } => {
+            let ty = state.table.fresh_type_uvar(var_level);
+            (ty.clone(), ty)
+        }
         // forall a. a -> a
         UnaryOp::RecDefault() => {
             let ty = state.table.fresh_type_uvar(var_level);
@@ -241,6 +333,21 @@ pub fn get_bop_type(
             mk_uniftype::dynamic(),
             mk_uty_arrow!(mk_uniftype::dynamic(), mk_uniftype::dynamic()),
         ),
+        // Ideally: Contract -> Label -> Dyn -> {success: Bool, value: Dyn, message: Str, notes: Array Str}
+        // Currently: Dyn -> Dyn -> (Dyn -> {success: Bool, value: Dyn, message: Str, notes: Array Str})
+        BinaryOp::ContractTry() => (
+            mk_uniftype::dynamic(),
+            mk_uniftype::dynamic(),
+            mk_uty_arrow!(
+                mk_uniftype::dynamic(),
+                mk_uty_record!(
+                    ("success", TypeF::Bool),
+                    ("value", TypeF::Dyn),
+                    ("message", TypeF::String),
+                    ("notes", mk_uniftype::array(TypeF::String))
+                )
+            ),
+        ),
         // Sym -> Dyn -> Dyn -> Dyn
         BinaryOp::Unseal() => (
             mk_uniftype::sym(),
@@ -327,18 +434,40 @@ pub fn get_bop_type(
                 ty_elt,
             )
         }
+        // forall a. Num -> Array a -> Array (Array a)
+        BinaryOp::ArrayChunks() | BinaryOp::ArrayWindows() => {
+            let ty_array = mk_uniftype::array(state.table.fresh_type_uvar(var_level));
+            (
+                mk_uniftype::num(),
+                ty_array.clone(),
+                mk_uniftype::array(ty_array),
+            )
+        }
         // Dyn -> Dyn -> Dyn
         BinaryOp::Merge(_) => (
             mk_uniftype::dynamic(),
             mk_uniftype::dynamic(),
             mk_uniftype::dynamic(),
         ),
-        // <Md5, Sha1, Sha256, Sha512> -> Str -> Str
+        // <Md5, Sha1, Sha256, Sha512, Sha3_256, Sha3_512, Blake3, Crc32> -> Str -> Str
         BinaryOp::Hash() => (
-            mk_uty_enum!("Md5", "Sha1", "Sha256", "Sha512"),
+            mk_uty_enum!(
+                "Md5", "Sha1", "Sha256", "Sha512", "Sha3_256", "Sha3_512", "Blake3", "Crc32"
+            ),
             mk_uniftype::str(),
             mk_uniftype::str(),
         ),
+        // forall a. <Md5, Sha1, Sha256, Sha512, Sha3_256, Sha3_512, Blake3, Crc32> -> a -> Str
+        BinaryOp::HashValue() => {
+            let ty_input = state.table.fresh_type_uvar(var_level);
+            (
+                mk_uty_enum!(
+                    "Md5", "Sha1", "Sha256", "Sha512", "Sha3_256", "Sha3_512", "Blake3", "Crc32"
+                ),
+                ty_input,
+                mk_uniftype::str(),
+            )
+        }
         // forall a. <Json, Yaml, Toml> -> a -> Str
         BinaryOp::Serialize() => {
             let ty_input = state.table.fresh_type_uvar(var_level);
@@ -356,8 +485,36 @@ pub fn get_bop_type(
         ),
         // Num -> Num -> Num
         BinaryOp::Pow() => (mk_uniftype::num(), mk_uniftype::num(), mk_uniftype::num()),
+        // Num -> Num -> Num
+        BinaryOp::NumLog() | BinaryOp::IntDiv() | BinaryOp::IntRem() | BinaryOp::Gcd() => {
+            (mk_uniftype::num(), mk_uniftype::num(), mk_uniftype::num())
+        }
+        // Num -> Num -> Str
+        BinaryOp::NumToStrFixed() => (mk_uniftype::num(), mk_uniftype::num(), mk_uniftype::str()),
+        // Num -> Num -> Str
+        BinaryOp::DateTimeFromTimestamp() => {
+            (mk_uniftype::num(), mk_uniftype::num(), mk_uniftype::str())
+        }
+        // Str -> Str -> Str
+        BinaryOp::DateTimeFormat() => (mk_uniftype::str(), mk_uniftype::str(), mk_uniftype::str()),
+        // Str -> Str -> <Lesser, Equal, Greater>
+        BinaryOp::SemVerCompare() => (
+            mk_uniftype::str(),
+            mk_uniftype::str(),
+            mk_uty_enum!("Lesser", "Equal", "Greater"),
+        ),
+        // Str -> Str -> Bool
+        BinaryOp::SemVerMatches() | BinaryOp::CidrContains() => {
+            (mk_uniftype::str(), mk_uniftype::str(), mk_uniftype::bool())
+        }
         // Str -> Str -> Bool
         BinaryOp::StrContains() => (mk_uniftype::str(), mk_uniftype::str(), mk_uniftype::bool()),
+        // Str -> Str -> <Lesser, Equal, Greater>
+        BinaryOp::StrCompare() => (
+            mk_uniftype::str(),
+            mk_uniftype::str(),
+            mk_uty_enum!("Lesser", "Equal", "Greater"),
+        ),
         // Str -> Str -> Array Str
         BinaryOp::StrSplit() => (
             mk_uniftype::str(),
@@ -477,5 +634,34 @@ pub fn get_nop_type(
             ],
             mk_uniftype::dynamic(),
         ),
+        // Morally: Str -> {_: Dyn} -> Lbl -> Lbl
+        // Actual: Str -> {_: Dyn} -> Dyn -> Dyn
+        NAryOp::LabelWithRelatedField() => (
+            vec![
+                mk_uniftype::str(),
+                mk_uniftype::dict(mk_uniftype::dynamic()),
+                mk_uniftype::dynamic(),
+            ],
+            mk_uniftype::dynamic(),
+        ),
+        // Morally: Contract -> Lbl -> {_: Dyn} -> {_: Dyn}
+        // Actual: Dyn -> Dyn -> {_: Dyn} -> {_: Dyn}
+        NAryOp::RecordAddInvariant() => (
+            vec![
+                mk_uniftype::dynamic(),
+                mk_uniftype::dynamic(),
+                mk_uniftype::dict(mk_uniftype::dynamic()),
+            ],
+            mk_uniftype::dict(mk_uniftype::dynamic()),
+        ),
+        // <Sha256, Sha512> -> Str -> Str -> Str
+        NAryOp::Hmac() => (
+            vec![
+                mk_uty_enum!("Sha256", "Sha512"),
+                mk_uniftype::str(),
+                mk_uniftype::str(),
+            ],
+            mk_uniftype::str(),
+        ),
     })
 }
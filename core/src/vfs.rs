@@ -0,0 +1,156 @@
+//! Abstraction of the filesystem used by the cache to read files.
+//!
+//! By default, the main program and its imports are read from the filesystem of the operating
+//! system. Hosts which don't have one, such as the WebAssembly REPL, or which want to control
+//! what programs can read, can provide their own implementation of [FileSystem] instead (see
+//! [crate::cache::Cache::set_file_system]).
+use std::{
+    collections::HashMap,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+/// The operations used by the cache to read files.
+///
+/// The cache normalizes paths and makes them absolute, relatively to [FileSystem::current_dir],
+/// before passing them to the other methods.
+pub trait FileSystem: fmt::Debug {
+    /// Return the content of the file at `path`.
+    fn read_to_string(&self, path: &Path) -> io::Result<String>;
+
+    /// Return the last modification time of the file at `path`. This is only used to detect that
+    /// a file has changed since it was loaded, so it doesn't have to be an actual time, as long
+    /// as it changes when the content of the file does.
+    fn modified(&self, path: &Path) -> io::Result<SystemTime>;
+
+    /// Return the canonical form of `path`, with all symbolic links resolved.
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf>;
+
+    /// Return the directory against which relative paths are resolved.
+    fn current_dir(&self) -> io::Result<PathBuf>;
+}
+
+/// The filesystem of the operating system.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct OsFileSystem;
+
+impl FileSystem for OsFileSystem {
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        fs::read_to_string(path)
+    }
+
+    fn modified(&self, path: &Path) -> io::Result<SystemTime> {
+        fs::metadata(path)?.modified()
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        fs::canonicalize(path)
+    }
+
+    fn current_dir(&self) -> io::Result<PathBuf> {
+        std::env::current_dir()
+    }
+}
+
+/// A filesystem living in memory, populated by the host. There are no symbolic links, and the
+/// current directory is the root `/`.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryFileSystem {
+    /// The content of each file, together with the version it was written at.
+    files: HashMap<PathBuf, (String, u64)>,
+    /// The number of writes so far, used as modification times. Unlike [SystemTime::now], this
+    /// works on all targets.
+    version: u64,
+}
+
+impl MemoryFileSystem {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a file, or replace its content if it already exists. Relative paths are relative to
+    /// the root.
+    pub fn insert(&mut self, path: impl AsRef<Path>, content: impl Into<String>) {
+        self.version += 1;
+        self.files
+            .insert(normalize(path.as_ref()), (content.into(), self.version));
+    }
+
+    /// Remove a file, returning its content if it existed.
+    pub fn remove(&mut self, path: impl AsRef<Path>) -> Option<String> {
+        self.files
+            .remove(&normalize(path.as_ref()))
+            .map(|(content, _)| content)
+    }
+
+    fn get(&self, path: &Path) -> io::Result<&(String, u64)> {
+        self.files.get(&normalize(path)).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no such file: {}", path.display()),
+            )
+        })
+    }
+}
+
+impl<P: AsRef<Path>, S: Into<String>> FromIterator<(P, S)> for MemoryFileSystem {
+    fn from_iter<I: IntoIterator<Item = (P, S)>>(iter: I) -> Self {
+        let mut fs = MemoryFileSystem::new();
+
+        for (path, content) in iter {
+            fs.insert(path, content);
+        }
+
+        fs
+    }
+}
+
+impl FileSystem for MemoryFileSystem {
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        self.get(path).map(|(content, _)| content.clone())
+    }
+
+    fn modified(&self, path: &Path) -> io::Result<SystemTime> {
+        self.get(path)
+            .map(|(_, version)| SystemTime::UNIX_EPOCH + Duration::from_secs(*version))
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        self.get(path).map(|_| normalize(path))
+    }
+
+    fn current_dir(&self) -> io::Result<PathBuf> {
+        Ok(PathBuf::from("/"))
+    }
+}
+
+/// Make a path absolute relatively to the root and remove its `.` and `..` components.
+fn normalize(path: &Path) -> PathBuf {
+    crate::cache::normalize_abs_path(&Path::new("/").join(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn memory_file_system() {
+        let mut fs: MemoryFileSystem = [("lib/a.ncl", "1")].into_iter().collect();
+
+        assert_eq!(fs.read_to_string(Path::new("/lib/a.ncl")).unwrap(), "1");
+        assert_eq!(fs.read_to_string(Path::new("lib/./a.ncl")).unwrap(), "1");
+        assert_eq!(
+            fs.read_to_string(Path::new("/b.ncl")).unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+
+        let modified = fs.modified(Path::new("/lib/a.ncl")).unwrap();
+        fs.insert("/lib/a.ncl", "2");
+        assert_eq!(fs.read_to_string(Path::new("/lib/a.ncl")).unwrap(), "2");
+        assert_ne!(fs.modified(Path::new("/lib/a.ncl")).unwrap(), modified);
+
+        assert_eq!(fs.remove("lib/a.ncl").as_deref(), Some("2"));
+        assert!(fs.read_to_string(Path::new("/lib/a.ncl")).is_err());
+    }
+}
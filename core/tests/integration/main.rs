@@ -14,6 +14,7 @@ use test_generator::test_resources;
 
 mod contract_label_path;
 mod free_vars;
+mod persistent_cache;
 mod pretty;
 mod query;
 mod stdlib_typecheck;
@@ -159,6 +160,8 @@ enum ErrorExpectation {
     EvalMissingFieldDef { field: String },
     #[serde(rename = "EvalError::MergeIncompatibleArgs")]
     EvalMergeIncompatibleArgs,
+    #[serde(rename = "EvalError::DecodingError")]
+    EvalDecodingError,
     #[serde(rename = "TypecheckError::UnboundIdentifier")]
     TypecheckUnboundIdentifier { identifier: String },
     #[serde(rename = "TypecheckError::UnboundTypeVariable")]
@@ -218,6 +221,7 @@ impl PartialEq<Error> for ErrorExpectation {
                 Error::EvalError(EvalError::MergeIncompatibleArgs { .. }),
             )
             | (EvalOther, Error::EvalError(EvalError::Other(..)))
+            | (EvalDecodingError, Error::EvalError(EvalError::DecodingError(..)))
             | (TypecheckRowMismatch, Error::TypecheckError(TypecheckError::RowMismatch(..)))
             | (
                 TypecheckMissingDynTail,
@@ -344,6 +348,7 @@ impl std::fmt::Display for ErrorExpectation {
             EvalEqError => "EvalError::EqError".to_owned(),
             EvalOther => "EvalError::Other".to_owned(),
             EvalMergeIncompatibleArgs => "EvalError::MergeIncompatibleArgs".to_owned(),
+            EvalDecodingError => "EvalError::DecodingError".to_owned(),
             EvalNAryPrimopTypeError => "EvalError::NAryPrimopTypeError".to_owned(),
             EvalInfiniteRecursion => "EvalError::InfiniteRecursion".to_owned(),
             EvalIllegalPolymorphicTailAccess => {
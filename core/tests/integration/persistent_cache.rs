@@ -0,0 +1,69 @@
+use nickel_lang_utils::test_program::TestProgram;
+use std::{fs, path::Path};
+
+fn eval_with_cache(main: &Path, cache_dir: &Path) -> String {
+    let mut program = TestProgram::new_from_file(main, std::io::stderr()).unwrap();
+    program.enable_persistent_cache(cache_dir);
+    program.eval_full().unwrap().to_string()
+}
+
+fn cache_entries(cache_dir: &Path) -> usize {
+    fs::read_dir(cache_dir)
+        .unwrap()
+        .filter(|entry| entry.as_ref().unwrap().path().extension() == Some("bin".as_ref()))
+        .count()
+}
+
+#[test]
+fn warm_runs_reuse_and_invalidate_entries() {
+    let sources = tempfile::tempdir().unwrap();
+    let cache_dir = tempfile::tempdir().unwrap();
+    let main = sources.path().join("main.ncl");
+    let lib = sources.path().join("lib.ncl");
+
+    fs::write(
+        &main,
+        r#"
+        let lib = import "lib.ncl" in
+        let double : Number -> Number = fun x => 2 * x in
+        { value = double lib.x, label = std.string.uppercase lib.name }
+        "#,
+    )
+    .unwrap();
+    fs::write(&lib, r#"{ x = 1, name = "one" }"#).unwrap();
+
+    let cold = eval_with_cache(&main, cache_dir.path());
+    // Two stdlib modules at two different stages, plus the program and its import.
+    assert_eq!(cache_entries(cache_dir.path()), 6);
+
+    let warm = eval_with_cache(&main, cache_dir.path());
+    assert_eq!(cold, warm);
+    assert_eq!(cache_entries(cache_dir.path()), 6);
+
+    // Changing an import must invalidate the entry of the file importing it as well.
+    fs::write(&lib, r#"{ x = 2, name = "two" }"#).unwrap();
+    let updated = eval_with_cache(&main, cache_dir.path());
+    assert_ne!(cold, updated);
+    assert!(updated.contains("TWO"));
+    assert_eq!(cache_entries(cache_dir.path()), 7);
+
+    // The entry of the importing file has been updated.
+    assert_eq!(eval_with_cache(&main, cache_dir.path()), updated);
+    assert_eq!(cache_entries(cache_dir.path()), 7);
+}
+
+#[test]
+fn corrupted_entries_are_ignored() {
+    let sources = tempfile::tempdir().unwrap();
+    let cache_dir = tempfile::tempdir().unwrap();
+    let main = sources.path().join("main.ncl");
+    fs::write(&main, "[1, 2, 3] |> std.array.map (fun x => x + 1)").unwrap();
+
+    let cold = eval_with_cache(&main, cache_dir.path());
+
+    for entry in fs::read_dir(cache_dir.path()).unwrap() {
+        fs::write(entry.unwrap().path(), b"NCLC garbage").unwrap();
+    }
+
+    assert_eq!(eval_with_cache(&main, cache_dir.path()), cold);
+}
@@ -1,5 +1,9 @@
 use assert_matches::assert_matches;
-use nickel_lang_core::cache::{Cache, ErrorTolerance};
+use nickel_lang_core::{
+    cache::{Cache, ErrorTolerance},
+    stdlib::StdlibModule,
+    term::{RichTerm, Term},
+};
 
 #[test]
 fn stdlib_typecheck() {
@@ -7,3 +11,66 @@ fn stdlib_typecheck() {
     assert_matches!(cache.load_stdlib(), Ok(_));
     assert_matches!(cache.typecheck_stdlib(), Ok(_));
 }
+
+/// Public functions of the stdlib which are deliberately left without a static type, together
+/// with the reason why.
+const UNTYPED_ALLOWLIST: &[(&str, &str)] = &[(
+    "string.to_enum",
+    "the tag returned depends on the value of the string, which can't be expressed statically",
+)];
+
+/// Collect the path of the fields of a stdlib module which don't have a type annotation,
+/// descending into submodules. Contracts, whose name is capitalized, are meant to be used as
+/// contracts and not as typed functions, and are thus ignored.
+fn collect_untyped(prefix: &str, rt: &RichTerm, untyped: &mut Vec<String>) {
+    let (Term::Record(record) | Term::RecRecord(record, ..)) = rt.as_ref() else {
+        return;
+    };
+
+    for (id, field) in record.fields.iter() {
+        let name = id.label();
+
+        if name.starts_with(|c: char| c.is_uppercase()) {
+            continue;
+        }
+
+        let path = if prefix.is_empty() {
+            name.to_owned()
+        } else {
+            format!("{prefix}.{name}")
+        };
+
+        match field.value.as_ref().map(|value| value.as_ref()) {
+            Some(Term::Record(..) | Term::RecRecord(..)) => {
+                collect_untyped(&path, field.value.as_ref().unwrap(), untyped)
+            }
+            _ if field.metadata.annotation.typ.is_none() => untyped.push(path),
+            _ => (),
+        }
+    }
+}
+
+#[test]
+fn stdlib_functions_are_typed() {
+    let mut cache = Cache::new(ErrorTolerance::Strict);
+    assert_matches!(cache.load_stdlib(), Ok(_));
+
+    let file_id = cache.get_submodule_file_id(StdlibModule::Std).unwrap();
+    let std = cache.get_ref(file_id).unwrap();
+
+    let mut untyped = Vec::new();
+    collect_untyped("", std, &mut untyped);
+    untyped.sort();
+
+    let mut allowed: Vec<_> = UNTYPED_ALLOWLIST
+        .iter()
+        .map(|(path, _reason)| path.to_string())
+        .collect();
+    allowed.sort();
+
+    assert_eq!(
+        untyped, allowed,
+        "public stdlib functions without a static type must be given one, or be added to \
+        `UNTYPED_ALLOWLIST` with a reason"
+    );
+}
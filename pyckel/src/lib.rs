@@ -1,13 +1,26 @@
-use std::io::Cursor;
+use std::{
+    io::{self, Cursor},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
 
 use nickel_lang_core::{
+    cache::ImportPolicy,
     error::Error,
-    eval::cache::{Cache, CacheImpl},
+    eval::{
+        cache::{Cache, CacheImpl},
+        limits::Limits,
+    },
     program::Program,
     serialize,
+    vfs::FileSystem,
 };
 
-use pyo3::{create_exception, exceptions::PyException, prelude::*};
+use pyo3::{
+    create_exception,
+    exceptions::{PyException, PyValueError},
+    prelude::*,
+};
 
 create_exception!(pyckel, NickelException, PyException);
 
@@ -16,11 +29,102 @@ fn error_to_exception<E: Into<Error>, EC: Cache>(error: E, program: &mut Program
     NickelException::new_err(program.report_as_str(error.into()))
 }
 
+/// A filesystem whose files are provided by a Python callable, which takes an absolute path and
+/// returns the content of the file as a str, or `None` if there is no such file. Files are only
+/// read once.
+#[derive(Debug)]
+struct PyFileSystem {
+    read: PyObject,
+}
+
+impl FileSystem for PyFileSystem {
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        let path = path.to_string_lossy();
+
+        Python::with_gil(|py| {
+            let content = self.read.call1(py, (&*path,))?;
+
+            if content.is_none(py) {
+                Ok(None)
+            } else {
+                content.extract::<String>(py).map(Some)
+            }
+        })
+        .map_err(|err| io::Error::new(io::ErrorKind::Other, err.to_string()))?
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("no such file: {path}")))
+    }
+
+    fn modified(&self, _path: &Path) -> io::Result<SystemTime> {
+        Ok(SystemTime::UNIX_EPOCH)
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        Ok(path.to_owned())
+    }
+
+    fn current_dir(&self) -> io::Result<PathBuf> {
+        Ok(PathBuf::from("/"))
+    }
+}
+
 /// Evaluate from a Python str of a Nickel expression to a Python str of the resulting JSON.
-#[pyfunction]
-pub fn run(s: String) -> PyResult<String> {
+///
+/// The optional arguments limit the resources used by the evaluation: the number of evaluation
+/// steps, the depth of the stack, the size of strings and arrays, and the duration in seconds.
+///
+/// Imports can be forbidden with `no_imports`, or restricted to the files under `import_roots`.
+///
+/// Imported files are read by calling `read_file` if provided, instead of reading them from the
+/// filesystem. See [PyFileSystem].
+#[pyfunction(
+    max_steps = "None",
+    max_stack_depth = "None",
+    max_size = "None",
+    timeout = "None",
+    no_imports = "false",
+    import_roots = "None",
+    read_file = "None"
+)]
+#[allow(clippy::too_many_arguments)]
+pub fn run(
+    s: String,
+    max_steps: Option<u64>,
+    max_stack_depth: Option<usize>,
+    max_size: Option<usize>,
+    timeout: Option<f64>,
+    no_imports: bool,
+    import_roots: Option<Vec<PathBuf>>,
+    read_file: Option<PyObject>,
+) -> PyResult<String> {
+    let timeout = timeout
+        .map(Duration::try_from_secs_f64)
+        .transpose()
+        .map_err(|err| PyValueError::new_err(format!("invalid timeout: {err}")))?;
+
+    let import_policy = match (no_imports, import_roots) {
+        (false, None) => ImportPolicy::Allow,
+        (true, None) => ImportPolicy::Deny,
+        (false, Some(roots)) => ImportPolicy::Roots(roots),
+        (true, Some(_)) => {
+            return Err(PyValueError::new_err(
+                "no_imports and import_roots are mutually exclusive",
+            ))
+        }
+    };
+
     let mut program: Program<CacheImpl> =
         Program::new_from_source(Cursor::new(s), "python", std::io::sink())?;
+    program.set_limits(Limits {
+        max_steps,
+        max_stack_depth,
+        max_size,
+        timeout,
+    });
+    program.set_import_policy(import_policy);
+
+    if let Some(read) = read_file {
+        program.set_file_system(PyFileSystem { read });
+    }
 
     let term = program
         .eval_full()
@@ -1,10 +1,7 @@
 use criterion::Criterion;
 use nickel_lang_core::{
     cache::{Cache, Envs, ErrorTolerance},
-    eval::{
-        cache::{Cache as EvalCache, CacheImpl},
-        VirtualMachine,
-    },
+    eval::{cache::SelectedCache, VirtualMachine},
     term::RichTerm,
     transform::import_resolution,
 };
@@ -96,9 +93,22 @@ impl<'b> Bench<'b> {
     }
 }
 
+/// The name of the environment variable selecting the evaluation cache used by the benchmarks.
+pub const EVAL_CACHE_VAR: &str = "NICKEL_BENCH_EVAL_CACHE";
+
+/// Return the evaluation cache to benchmark with. This is the incremental cache if the
+/// [EVAL_CACHE_VAR] environment variable is set to `incremental`, and the default lazy cache
+/// otherwise, so that both can be compared by running the same suite against a saved baseline.
+pub fn eval_cache() -> SelectedCache {
+    SelectedCache::with_incremental(matches!(
+        std::env::var(EVAL_CACHE_VAR).as_deref(),
+        Ok("incremental")
+    ))
+}
+
 pub fn bench_terms<'r>(rts: Vec<Bench<'r>>) -> Box<dyn Fn(&mut Criterion) + 'r> {
     let mut cache = Cache::new(ErrorTolerance::Strict);
-    let mut eval_cache = CacheImpl::new();
+    let mut eval_cache = eval_cache();
     let Envs {
         eval_env,
         type_ctxt,
@@ -170,14 +180,14 @@ macro_rules! ncl_bench_group {
         pub fn $group_name() {
             use nickel_lang_core::{
                 cache::{Envs, Cache, ErrorTolerance, ImportResolver},
-                eval::{VirtualMachine, cache::{CacheImpl, Cache as EvalCache}},
+                eval::VirtualMachine,
                 transform::import_resolution::strict::resolve_imports,
             };
 
             let mut c: criterion::Criterion<_> = $config
                 .configure_from_args();
             let mut cache = Cache::new(ErrorTolerance::Strict);
-            let mut eval_cache = CacheImpl::new();
+            let mut eval_cache = $crate::bench::eval_cache();
             let Envs {eval_env, type_ctxt} = cache.prepare_stdlib(&mut eval_cache).unwrap();
             $(
                 let bench = $crate::ncl_bench!$b;